@@ -5,6 +5,7 @@ pub fn bench(c: &mut Criterion) {
     predictor::u16(c);
     predictor::u32(c);
     predictor::u64(c);
+    predictor::dem(c);
     predictor::f32(c);
     predictor::f64(c);
 }
@@ -140,6 +141,42 @@ mod predictor {
         }
     }
 
+    /// Decodes tiles of single sample 16-bit elevation models one row at a time.
+    pub fn dem(c: &mut Criterion) {
+        const TILE_SIZES: &[u32] = &[256, 512, 1024];
+
+        for byteorder in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let group_name = format!("tiff/predictor/dem/u16/{}", DisplayByteOrder(byteorder));
+            let mut group = c.benchmark_group(group_name);
+            group.sampling_mode(SamplingMode::Flat).sample_size(10);
+
+            for &tile_size in TILE_SIZES {
+                let row_size = tile_size as usize * size_of::<u16>();
+                let size = row_size * tile_size as usize;
+
+                group.throughput(Throughput::Bytes(size as u64));
+                group.bench_with_input(
+                    BenchmarkId::from_parameter(tile_size),
+                    &size,
+                    |b, &size| {
+                        let mut tile = repeat_with(|| fastrand::u8(..))
+                            .take(size)
+                            .collect::<Vec<_>>();
+                        let mut predictor = assert_ok!(IntPredictor::new(byteorder, 1, 2));
+
+                        b.iter(|| {
+                            tile.chunks_exact_mut(row_size)
+                                .for_each(|row| predictor.decode(row));
+                            std::hint::black_box(&tile[..]);
+                        });
+                    },
+                );
+            }
+
+            group.finish();
+        }
+    }
+
     pub fn f32(c: &mut Criterion) {
         for &samples in SAMPLES {
             let group_name = format!("tiff/predictor/f32/{samples}",);
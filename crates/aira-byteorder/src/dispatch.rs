@@ -0,0 +1,427 @@
+//! Selection of the implementation used to swap the bytes of slices.
+//!
+//! The fastest implementation supported by the CPU is detected the first time a slice is
+//! converted, and the function pointers are cached for the following calls. Without the `std`
+//! feature, the implementation is selected using the target features enabled at compile time.
+//!
+//! The implementation can be forced with [`force`], for instance to test each one of them.
+
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+#[cfg(target_arch = "aarch64")]
+use core::arch::aarch64::*;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64::*;
+
+/// An implementation of the byte swap of slices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Implementation {
+    /// Portable implementation swapping one value at a time.
+    Scalar,
+    /// SSE2 instructions, SSSE3 instructions are used when enabled at compile time.
+    #[cfg(target_arch = "x86_64")]
+    #[cfg_attr(docsrs, doc(cfg(target_arch = "x86_64")))]
+    Sse,
+    /// AVX2 instructions.
+    #[cfg(target_arch = "x86_64")]
+    #[cfg_attr(docsrs, doc(cfg(target_arch = "x86_64")))]
+    Avx2,
+    /// NEON instructions.
+    #[cfg(target_arch = "aarch64")]
+    #[cfg_attr(docsrs, doc(cfg(target_arch = "aarch64")))]
+    Neon,
+}
+
+impl Implementation {
+    /// All the implementations available for the target architecture.
+    pub const ALL: &'static [Self] = &[
+        Self::Scalar,
+        #[cfg(target_arch = "x86_64")]
+        Self::Sse,
+        #[cfg(target_arch = "x86_64")]
+        Self::Avx2,
+        #[cfg(target_arch = "aarch64")]
+        Self::Neon,
+    ];
+
+    /// Returns the fastest implementation supported by the CPU.
+    pub fn detect() -> Self {
+        Self::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|implementation| implementation.is_supported())
+            .unwrap_or(Self::Scalar)
+    }
+
+    /// Returns `true` if the implementation is supported by the CPU.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Scalar => true,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse => true,
+            #[cfg(all(target_arch = "x86_64", feature = "std"))]
+            Self::Avx2 => std::is_x86_feature_detected!("avx2"),
+            #[cfg(all(target_arch = "x86_64", not(feature = "std")))]
+            Self::Avx2 => cfg!(target_feature = "avx2"),
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => true,
+        }
+    }
+
+    fn kernels(self) -> &'static Kernels {
+        match self {
+            Self::Scalar => &SCALAR,
+            #[cfg(target_arch = "x86_64")]
+            Self::Sse => &SSE,
+            #[cfg(target_arch = "x86_64")]
+            Self::Avx2 => &AVX2,
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => &NEON,
+        }
+    }
+}
+
+/// Returns the implementation used to swap the bytes of slices.
+pub fn selected() -> Implementation {
+    kernels().implementation
+}
+
+/// Forces the implementation used to swap the bytes of slices, `None` restores the detection.
+///
+/// The implementation is changed for the whole process.
+///
+/// # Panics
+///
+/// Panics if the implementation is not supported by the CPU.
+pub fn force(implementation: Option<Implementation>) {
+    let kernels = match implementation {
+        Some(implementation) => {
+            assert!(
+                implementation.is_supported(),
+                "{implementation:?} is not supported by the CPU"
+            );
+            core::ptr::from_ref(implementation.kernels()).cast_mut()
+        }
+        None => core::ptr::null_mut(),
+    };
+    SELECTED.store(kernels, Ordering::Relaxed);
+}
+
+/// The functions swapping the bytes of slices.
+///
+/// The kernels work on the bytes of the values, which don't need to be aligned. The length of the
+/// slices is a multiple of the size of the values.
+struct Kernels {
+    implementation: Implementation,
+    swap_u16: unsafe fn(&mut [u8]),
+    swap_u32: unsafe fn(&mut [u8]),
+    swap_u64: unsafe fn(&mut [u8]),
+    swap_u128: unsafe fn(&mut [u8]),
+}
+
+/// The kernels in use, null until the first detection.
+///
+/// Only the kernels of a supported implementation are stored.
+static SELECTED: AtomicPtr<Kernels> = AtomicPtr::new(core::ptr::null_mut());
+
+#[inline]
+fn kernels() -> &'static Kernels {
+    let kernels = SELECTED.load(Ordering::Relaxed);
+    if !kernels.is_null() {
+        // SAFETY: The pointer is either null or it points to one of the static kernels.
+        return unsafe { &*kernels };
+    }
+
+    let kernels = Implementation::detect().kernels();
+    SELECTED.store(core::ptr::from_ref(kernels).cast_mut(), Ordering::Relaxed);
+    kernels
+}
+
+macro_rules! impl_swap {
+    ($($name:ident, $bytes_name:ident, $ty:ty;)+) => {$(
+        /// Swaps the bytes of each value using the selected implementation.
+        #[inline]
+        pub(crate) fn $name(values: &mut [$ty]) {
+            let len = size_of_val(values);
+            // SAFETY: The bytes cover exactly the values, and any bit pattern is valid for them.
+            let bytes =
+                unsafe { core::slice::from_raw_parts_mut(values.as_mut_ptr().cast::<u8>(), len) };
+            // SAFETY: Only the kernels of a supported implementation are selected.
+            unsafe { (kernels().$name)(bytes) }
+        }
+
+        /// Swaps the bytes of each value stored in `bytes` using the selected implementation.
+        ///
+        /// The values don't need to be aligned, trailing bytes not forming a whole value are left
+        /// untouched.
+        #[inline]
+        pub(crate) fn $bytes_name(bytes: &mut [u8]) {
+            // SAFETY: Only the kernels of a supported implementation are selected.
+            unsafe { (kernels().$name)(bytes) }
+        }
+    )+};
+}
+
+impl_swap! {
+    swap_u16, swap_bytes_u16, u16;
+    swap_u32, swap_bytes_u32, u32;
+    swap_u64, swap_bytes_u64, u64;
+    swap_u128, swap_bytes_u128, u128;
+}
+
+macro_rules! impl_swap_cast {
+    ($($(#[$meta:meta])* $name:ident, $ty:ty, $swap:ident, $uint:ty;)+) => {$(
+        $(#[$meta])*
+        /// Swaps the bytes of each value using the selected implementation.
+        #[inline]
+        pub(crate) fn $name(values: &mut [$ty]) {
+            const {
+                assert!(size_of::<$ty>() == size_of::<$uint>());
+                assert!(align_of::<$ty>() == align_of::<$uint>());
+            }
+
+            // SAFETY: The two types have the same size and alignment, and any bit pattern is
+            // valid for both of them.
+            let values = unsafe {
+                core::slice::from_raw_parts_mut(values.as_mut_ptr().cast::<$uint>(), values.len())
+            };
+            $swap(values)
+        }
+    )+};
+}
+
+impl_swap_cast! {
+    swap_i16, i16, swap_u16, u16;
+    swap_i32, i32, swap_u32, u32;
+    swap_i64, i64, swap_u64, u64;
+    swap_i128, i128, swap_u128, u128;
+
+    #[cfg(feature = "f16")]
+    swap_f16, f16, swap_u16, u16;
+    swap_f32, f32, swap_u32, u32;
+    swap_f64, f64, swap_u64, u64;
+    #[cfg(feature = "f128")]
+    swap_f128, f128, swap_u128, u128;
+}
+
+static SCALAR: Kernels = Kernels {
+    implementation: Implementation::Scalar,
+    swap_u16: scalar::swap_u16,
+    swap_u32: scalar::swap_u32,
+    swap_u64: scalar::swap_u64,
+    swap_u128: scalar::swap_u128,
+};
+
+#[cfg(target_arch = "x86_64")]
+static SSE: Kernels = Kernels {
+    implementation: Implementation::Sse,
+    swap_u16: sse::swap_u16,
+    swap_u32: sse::swap_u32,
+    swap_u64: sse::swap_u64,
+    swap_u128: sse::swap_u128,
+};
+
+#[cfg(target_arch = "x86_64")]
+static AVX2: Kernels = Kernels {
+    implementation: Implementation::Avx2,
+    swap_u16: avx2::swap_u16,
+    swap_u32: avx2::swap_u32,
+    swap_u64: avx2::swap_u64,
+    swap_u128: avx2::swap_u128,
+};
+
+#[cfg(target_arch = "aarch64")]
+static NEON: Kernels = Kernels {
+    implementation: Implementation::Neon,
+    swap_u16: neon::swap_u16,
+    swap_u32: neon::swap_u32,
+    swap_u64: neon::swap_u64,
+    swap_u128: neon::swap_u128,
+};
+
+mod scalar {
+    macro_rules! impl_scalar {
+        ($($name:ident, $ty:ty;)+) => {$(
+            pub fn $name(bytes: &mut [u8]) {
+                bytes.chunks_exact_mut(size_of::<$ty>()).for_each(<[u8]>::reverse);
+            }
+        )+};
+    }
+
+    impl_scalar! {
+        swap_u16, u16;
+        swap_u32, u32;
+        swap_u64, u64;
+        swap_u128, u128;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod sse {
+    use super::*;
+
+    macro_rules! impl_sse {
+        ($($name:ident, $bswap:path;)+) => {$(
+            pub fn $name(bytes: &mut [u8]) {
+                let mut chunks = bytes.chunks_exact_mut(size_of::<__m128i>());
+                for chunk in &mut chunks {
+                    // SAFETY: SSE2 is always available on x86_64, and the register is read from
+                    // and written to a chunk of 16 bytes.
+                    unsafe {
+                        let x = _mm_loadu_si128(chunk.as_ptr().cast());
+                        _mm_storeu_si128(chunk.as_mut_ptr().cast(), $bswap(x));
+                    }
+                }
+                super::scalar::$name(chunks.into_remainder());
+            }
+        )+};
+    }
+
+    impl_sse! {
+        swap_u16, crate::sse::bswap_u16;
+        swap_u32, crate::sse::bswap_u32;
+        swap_u64, crate::sse::bswap_u64;
+        swap_u128, crate::sse::bswap_u128;
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod avx2 {
+    use super::*;
+
+    macro_rules! impl_avx2 {
+        ($($name:ident, $bswap:path;)+) => {$(
+            #[target_feature(enable = "avx2")]
+            pub unsafe fn $name(bytes: &mut [u8]) {
+                let mut chunks = bytes.chunks_exact_mut(size_of::<__m256i>());
+                for chunk in &mut chunks {
+                    // SAFETY: The register is read from and written to a chunk of 32 bytes.
+                    unsafe {
+                        let x = _mm256_loadu_si256(chunk.as_ptr().cast());
+                        _mm256_storeu_si256(chunk.as_mut_ptr().cast(), $bswap(x));
+                    }
+                }
+                super::scalar::$name(chunks.into_remainder());
+            }
+        )+};
+    }
+
+    impl_avx2! {
+        swap_u16, crate::avx::bswap_u16;
+        swap_u32, crate::avx::bswap_u32;
+        swap_u64, crate::avx::bswap_u64;
+        swap_u128, crate::avx::bswap_u128;
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use super::*;
+
+    macro_rules! impl_neon {
+        ($($name:ident, $bswap:path;)+) => {$(
+            pub fn $name(bytes: &mut [u8]) {
+                let mut chunks = bytes.chunks_exact_mut(size_of::<uint8x16_t>());
+                for chunk in &mut chunks {
+                    // SAFETY: NEON is always available on aarch64, and the register is read from
+                    // and written to a chunk of 16 bytes.
+                    unsafe {
+                        let x = vld1q_u8(chunk.as_ptr().cast());
+                        vst1q_u8(chunk.as_mut_ptr().cast(), $bswap(x));
+                    }
+                }
+                super::scalar::$name(chunks.into_remainder());
+            }
+        )+};
+    }
+
+    impl_neon! {
+        swap_u16, crate::neon::bswap_u16;
+        swap_u32, crate::neon::bswap_u32;
+        swap_u64, crate::neon::bswap_u64;
+        swap_u128, crate::neon::bswap_u128;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test_kernel {
+        ($($name:ident, $ty:ty;)+) => {$(
+            #[test]
+            fn $name() {
+                for &implementation in Implementation::ALL {
+                    if !implementation.is_supported() {
+                        continue;
+                    }
+
+                    // Slices not filling the last register are swapped too.
+                    for len in [0, 1, 7, 8, 9, 31, 32, 33, 100] {
+                        const SEED: u128 = 0x9e37_79b9_7f4a_7c15_f39c_c060_5ced_c835;
+                        let values = (0..len as $ty)
+                            .map(|value| value.wrapping_mul(SEED as $ty))
+                            .collect::<Vec<_>>();
+
+                        // The kernels don't require the values to be aligned.
+                        let mut actual = core::iter::once(0)
+                            .chain(values.iter().flat_map(|value| value.to_ne_bytes()))
+                            .collect::<Vec<_>>();
+                        // SAFETY: The implementation is supported.
+                        unsafe { (implementation.kernels().$name)(&mut actual[1..]) };
+                        let actual = actual[1..]
+                            .chunks_exact(size_of::<$ty>())
+                            .map(|bytes| <$ty>::from_ne_bytes(bytes.try_into().unwrap()))
+                            .collect::<Vec<_>>();
+
+                        let expected = values
+                            .iter()
+                            .map(|value| value.swap_bytes())
+                            .collect::<Vec<_>>();
+                        assert_eq!(actual, expected, "{implementation:?} with {len} values");
+                    }
+                }
+            }
+        )+};
+    }
+
+    test_kernel! {
+        swap_u16, u16;
+        swap_u32, u32;
+        swap_u64, u64;
+        swap_u128, u128;
+    }
+
+    #[test]
+    fn detect_is_supported() {
+        assert!(Implementation::detect().is_supported());
+    }
+
+    #[test]
+    fn force_each_implementation() {
+        use crate::{ByteOrder, BE, LE};
+
+        let values = [0x0102_0304u32, 0x0506_0708, 0x090a_0b0c];
+        for &implementation in Implementation::ALL {
+            if !implementation.is_supported() {
+                continue;
+            }
+
+            force(Some(implementation));
+            assert_eq!(selected(), implementation);
+
+            let mut swapped = values;
+            if cfg!(target_endian = "little") {
+                BE::decode_slice_u32(&mut swapped);
+            } else {
+                LE::decode_slice_u32(&mut swapped);
+            }
+            assert_eq!(swapped, values.map(u32::swap_bytes), "{implementation:?}");
+        }
+
+        force(None);
+        assert_eq!(selected(), Implementation::detect());
+    }
+}
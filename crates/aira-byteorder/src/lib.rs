@@ -15,6 +15,8 @@ pub use self::io::{ReadBytesExt, WriteBytesExt};
 #[cfg(feature = "std")]
 mod io;
 
+pub mod dispatch;
+
 #[cfg(target_arch = "x86_64")]
 use core::arch::x86_64::{__m128, __m128d, __m128i, __m256, __m256d, __m256i};
 
@@ -72,6 +74,25 @@ macro_rules! impl_decode_slice {
     )+};
 }
 
+macro_rules! impl_to_native_in_place {
+    ($($(#[$meta:meta])* $name:ident, $ty:ty, $read:ident;)+) => {$(
+        $(#[$meta])*
+        #[inline]
+        fn $name(bytes: &mut [u8]) -> Option<&mut [$ty]> {
+            assert_eq!(
+                bytes.len() % size_of::<$ty>(),
+                0,
+                "the length of the slice is not a multiple of the size of the values"
+            );
+            bytes.chunks_exact_mut(size_of::<$ty>()).for_each(|bytes| {
+                let value = Self::$read(bytes);
+                bytes.copy_from_slice(&value.to_ne_bytes());
+            });
+            cast_slice_mut(bytes)
+        }
+    )+};
+}
+
 macro_rules! impl_encode_signed {
     ($($(#[$meta:meta])* $name:ident, $ty:ty, $uencode:ident;)+) => {$(
         $(#[$meta])*
@@ -344,6 +365,144 @@ pub trait ByteOrder {
         decode_slice_f128, f128, decode_f128;
     }
 
+    impl_to_native_in_place! {
+        /// Converts the unsigned 16-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `u16`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `u16`.
+        to_native_in_place_u16, u16, read_u16;
+
+        /// Converts the unsigned 32-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `u32`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `u32`.
+        to_native_in_place_u32, u32, read_u32;
+
+        /// Converts the unsigned 64-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `u64`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `u64`.
+        to_native_in_place_u64, u64, read_u64;
+
+        /// Converts the unsigned 128-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `u128`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `u128`.
+        to_native_in_place_u128, u128, read_u128;
+
+        /// Converts the signed 16-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `i16`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `i16`.
+        to_native_in_place_i16, i16, read_i16;
+
+        /// Converts the signed 32-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `i32`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `i32`.
+        to_native_in_place_i32, i32, read_i32;
+
+        /// Converts the signed 64-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `i64`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `i64`.
+        to_native_in_place_i64, i64, read_i64;
+
+        /// Converts the signed 128-bit integers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `i128`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `i128`.
+        to_native_in_place_i128, i128, read_i128;
+
+        #[cfg(feature = "f16")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "f16")))]
+        /// Converts the 16-bit floating point numbers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `f16`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `f16`.
+        to_native_in_place_f16, f16, read_f16;
+
+        /// Converts the 32-bit floating point numbers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `f32`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `f32`.
+        to_native_in_place_f32, f32, read_f32;
+
+        /// Converts the 64-bit floating point numbers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `f64`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `f64`.
+        to_native_in_place_f64, f64, read_f64;
+
+        #[cfg(feature = "f128")]
+        #[cfg_attr(docsrs, doc(cfg(feature = "f128")))]
+        /// Converts the 128-bit floating point numbers stored in `bytes` from a particular byte order to the
+        /// native byte order, in place.
+        ///
+        /// `bytes` doesn't need to be aligned. The converted values are returned if `bytes` is
+        /// aligned for `f128`, `None` otherwise.
+        ///
+        /// # Panics
+        ///
+        /// Panics if the length of `bytes` is not a multiple of the size of `f128`.
+        to_native_in_place_f128, f128, read_f128;
+    }
+
     /// Encodes an unsigned 16-bit integer to a particular byte order.
     fn encode_u16(value: u16) -> u16;
 
@@ -837,6 +996,132 @@ macro_rules! impl_write {
     )+};
 }
 
+macro_rules! impl_swap_slice {
+    ($endian:literal; $($(#[$meta:meta])* $name:ident, $ty:ty, $swap:ident;)+) => {$(
+        $(#[$meta])*
+        #[inline]
+        fn $name(values: &mut [$ty]) {
+            // Values already in native byte order are left untouched.
+            if cfg!(target_endian = $endian) {
+                dispatch::$swap(values);
+            }
+        }
+    )+};
+}
+
+macro_rules! impl_swap_in_place {
+    ($endian:literal; $($(#[$meta:meta])* $name:ident, $ty:ty, $swap:ident;)+) => {$(
+        $(#[$meta])*
+        #[inline]
+        fn $name(bytes: &mut [u8]) -> Option<&mut [$ty]> {
+            assert_eq!(
+                bytes.len() % size_of::<$ty>(),
+                0,
+                "the length of the slice is not a multiple of the size of the values"
+            );
+            if cfg!(target_endian = $endian) {
+                dispatch::$swap(bytes);
+            }
+            cast_slice_mut(bytes)
+        }
+    )+};
+}
+
+macro_rules! impl_swap_slices {
+    ($endian:literal) => {
+        impl_swap_slice! {
+            $endian;
+            decode_slice_u16, u16, swap_u16;
+            decode_slice_u32, u32, swap_u32;
+            decode_slice_u64, u64, swap_u64;
+            decode_slice_u128, u128, swap_u128;
+            decode_slice_i16, i16, swap_i16;
+            decode_slice_i32, i32, swap_i32;
+            decode_slice_i64, i64, swap_i64;
+            decode_slice_i128, i128, swap_i128;
+            #[cfg(feature = "f16")]
+            decode_slice_f16, f16, swap_f16;
+            decode_slice_f32, f32, swap_f32;
+            decode_slice_f64, f64, swap_f64;
+            #[cfg(feature = "f128")]
+            decode_slice_f128, f128, swap_f128;
+
+            encode_slice_u16, u16, swap_u16;
+            encode_slice_u32, u32, swap_u32;
+            encode_slice_u64, u64, swap_u64;
+            encode_slice_u128, u128, swap_u128;
+            encode_slice_i16, i16, swap_i16;
+            encode_slice_i32, i32, swap_i32;
+            encode_slice_i64, i64, swap_i64;
+            encode_slice_i128, i128, swap_i128;
+            #[cfg(feature = "f16")]
+            encode_slice_f16, f16, swap_f16;
+            encode_slice_f32, f32, swap_f32;
+            encode_slice_f64, f64, swap_f64;
+            #[cfg(feature = "f128")]
+            encode_slice_f128, f128, swap_f128;
+        }
+
+        impl_swap_in_place! {
+            $endian;
+            to_native_in_place_u16, u16, swap_bytes_u16;
+            to_native_in_place_u32, u32, swap_bytes_u32;
+            to_native_in_place_u64, u64, swap_bytes_u64;
+            to_native_in_place_u128, u128, swap_bytes_u128;
+            to_native_in_place_i16, i16, swap_bytes_u16;
+            to_native_in_place_i32, i32, swap_bytes_u32;
+            to_native_in_place_i64, i64, swap_bytes_u64;
+            to_native_in_place_i128, i128, swap_bytes_u128;
+            #[cfg(feature = "f16")]
+            to_native_in_place_f16, f16, swap_bytes_u16;
+            to_native_in_place_f32, f32, swap_bytes_u32;
+            to_native_in_place_f64, f64, swap_bytes_u64;
+            #[cfg(feature = "f128")]
+            to_native_in_place_f128, f128, swap_bytes_u128;
+        }
+    };
+}
+
+/// Primitive numbers, which can be viewed from bytes.
+///
+/// # Safety
+///
+/// The type must not have padding bytes, and any bit pattern must be a valid value.
+unsafe trait Number {}
+
+macro_rules! impl_number {
+    ($($(#[$meta:meta])* $ty:ty;)+) => {$(
+        $(#[$meta])*
+        // SAFETY: Primitive numbers have no padding bytes and any bit pattern is valid.
+        unsafe impl Number for $ty {}
+    )+};
+}
+
+impl_number! {
+    u16;
+    u32;
+    u64;
+    u128;
+    i16;
+    i32;
+    i64;
+    i128;
+    #[cfg(feature = "f16")]
+    f16;
+    f32;
+    f64;
+    #[cfg(feature = "f128")]
+    f128;
+}
+
+/// Views `bytes` as a slice of numbers, if it is aligned and its length is a multiple of their
+/// size.
+fn cast_slice_mut<T: Number>(bytes: &mut [u8]) -> Option<&mut [T]> {
+    // SAFETY: Any bit pattern is valid for `T`.
+    let (head, values, tail) = unsafe { bytes.align_to_mut::<T>() };
+    (head.is_empty() && tail.is_empty()).then_some(values)
+}
+
 #[cfg(target_arch = "x86_64")]
 macro_rules! impl_sse {
     ($($name:ident, $big:path, $little:path;)+) => {$(
@@ -902,6 +1187,8 @@ impl ByteOrder for BigEndian {
         write_u128, u128, to_be_bytes;
     }
 
+    impl_swap_slices!("little");
+
     #[cfg(target_arch = "x86_64")]
     impl_sse! {
         sse_decode_u16, sse::identity, sse::bswap_u16;
@@ -958,6 +1245,8 @@ impl ByteOrder for LittleEndian {
         write_u128, u128, to_le_bytes;
     }
 
+    impl_swap_slices!("big");
+
     #[cfg(target_arch = "x86_64")]
     impl_sse! {
         sse_decode_u16, sse::bswap_u16, sse::identity;
@@ -1118,6 +1407,38 @@ mod avx {
     }
 }
 
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use core::arch::aarch64::*;
+
+    /// Swap bytes order of 16-bit integers.
+    #[inline(always)]
+    pub unsafe fn bswap_u16(x: uint8x16_t) -> uint8x16_t {
+        unsafe { vrev16q_u8(x) }
+    }
+
+    /// Swap bytes order of 32-bit integers.
+    #[inline(always)]
+    pub unsafe fn bswap_u32(x: uint8x16_t) -> uint8x16_t {
+        unsafe { vrev32q_u8(x) }
+    }
+
+    /// Swap bytes order of 64-bit integers.
+    #[inline(always)]
+    pub unsafe fn bswap_u64(x: uint8x16_t) -> uint8x16_t {
+        unsafe { vrev64q_u8(x) }
+    }
+
+    /// Swap bytes order of 128-bit integers.
+    #[inline(always)]
+    pub unsafe fn bswap_u128(x: uint8x16_t) -> uint8x16_t {
+        unsafe {
+            let x = vrev64q_u8(x);
+            vextq_u8::<8>(x, x)
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1740,6 +2061,82 @@ mod tests {
         (read_f128, write_f128),
     );
 
+    #[repr(C, align(16))]
+    #[derive(Clone, Copy)]
+    struct Aligned<const L: usize>([u8; L]);
+
+    macro_rules! test_in_place {
+        ($name:ident, $ty:ident, $to_native:ident, $read:ident $(,)?) => {
+            mod $name {
+                use super::*;
+
+                const N: usize = size_of::<$ty>();
+                // Several registers and a remainder, to cover both the vectorized and scalar paths.
+                const LEN: usize = 37 * N;
+
+                fn check<E: ByteOrder>(bytes: [u8; LEN + N]) {
+                    let bytes = Aligned(bytes);
+                    for offset in 0..N {
+                        let expected = bytes.0[offset..offset + LEN]
+                            .chunks_exact(N)
+                            .map(E::$read)
+                            .collect::<Vec<_>>();
+
+                        let mut converted = bytes;
+                        let converted = &mut converted.0[offset..offset + LEN];
+                        let values = E::$to_native(converted);
+                        assert_eq!(values.is_some(), offset % align_of::<$ty>() == 0);
+                        if let Some(values) = values {
+                            assert!(values.iter().zip(&expected).all(|(a, b)| a.bits_eq(b)));
+                        }
+
+                        let actual = converted
+                            .chunks_exact(N)
+                            .map(|bytes| $ty::from_ne_bytes(bytes.try_into().unwrap()));
+                        assert!(actual.zip(&expected).all(|(a, b)| a.bits_eq(b)));
+                    }
+                }
+
+                #[test]
+                fn be_to_native() {
+                    run_arbitrary_test(check::<BE> as fn([u8; _]));
+                }
+
+                #[test]
+                fn le_to_native() {
+                    run_arbitrary_test(check::<LE> as fn([u8; _]));
+                }
+
+                #[test]
+                #[should_panic = "not a multiple of the size"]
+                fn be_partial_value() {
+                    BE::$to_native(&mut [0u8; N + 1]);
+                }
+
+                #[test]
+                #[should_panic = "not a multiple of the size"]
+                fn le_partial_value() {
+                    LE::$to_native(&mut [0u8; N + 1]);
+                }
+            }
+        };
+    }
+
+    test_in_place!(in_place_u16, u16, to_native_in_place_u16, read_u16);
+    test_in_place!(in_place_u32, u32, to_native_in_place_u32, read_u32);
+    test_in_place!(in_place_u64, u64, to_native_in_place_u64, read_u64);
+    test_in_place!(in_place_u128, u128, to_native_in_place_u128, read_u128);
+    test_in_place!(in_place_i16, i16, to_native_in_place_i16, read_i16);
+    test_in_place!(in_place_i32, i32, to_native_in_place_i32, read_i32);
+    test_in_place!(in_place_i64, i64, to_native_in_place_i64, read_i64);
+    test_in_place!(in_place_i128, i128, to_native_in_place_i128, read_i128);
+    #[cfg(feature = "f16")]
+    test_in_place!(in_place_f16, f16, to_native_in_place_f16, read_f16);
+    test_in_place!(in_place_f32, f32, to_native_in_place_f32, read_f32);
+    test_in_place!(in_place_f64, f64, to_native_in_place_f64, read_f64);
+    #[cfg(feature = "f128")]
+    test_in_place!(in_place_f128, f128, to_native_in_place_f128, read_f128);
+
     #[cfg(target_arch = "x86_64")]
     mod test_sse {
         use super::*;
@@ -1,3 +1,5 @@
+pub use tifflint::TiffLint;
 pub use tiffdump::TiffDump;
 
 mod tiffdump;
+mod tifflint;
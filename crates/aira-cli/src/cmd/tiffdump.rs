@@ -134,6 +134,8 @@ impl TiffDump {
 fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
     let mut writer = crate::utils::JsonWriter::new(std::io::stdout());
     let multiple_files = files.len() > 1;
+    // The first error encountered while decoding a file, the remaining files are still dumped.
+    let mut failure = None;
 
     if multiple_files {
         writer.start_array()?;
@@ -186,7 +188,19 @@ fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
         let mut visited_offsets = HashSet::new();
 
         writer.start_array()?;
-        while let Some(directory) = directories.next_directory()? {
+        'directories: loop {
+            let directory = match directories.next_directory() {
+                Ok(Some(directory)) => directory,
+                Ok(None) => break,
+                Err(err) => {
+                    writer.start_object()?;
+                    write_json_error(&mut writer, &err)?;
+                    writer.end_object()?;
+                    failure.get_or_insert(err);
+                    break;
+                }
+            };
+
             ensure!(
                 visited_offsets.insert(directory.offset),
                 "Cycle detected in chaining of TIFF directories"
@@ -201,8 +215,23 @@ fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
 
             writer.write_key("entries")?;
             writer.start_array()?;
+            let mut sparse_chunks = SparseChunks::default();
             let mut entries = directory.entries();
-            while let Some(entry) = entries.next_entry()? {
+            loop {
+                let entry = match entries.next_entry() {
+                    Ok(Some(entry)) => entry,
+                    Ok(None) => break,
+                    Err(err) => {
+                        writer.start_object()?;
+                        write_json_error(&mut writer, &err)?;
+                        writer.end_object()?;
+                        writer.end_array()?;
+                        writer.end_object()?;
+                        failure.get_or_insert(err);
+                        break 'directories;
+                    }
+                };
+
                 writer.start_object()?;
 
                 writer.write_key("tag")?;
@@ -226,8 +255,22 @@ fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
                 writer.write_key("count")?;
                 writer.write_u64(entry.count)?;
 
+                let tag = entry.tag;
+                let value = match tiff::Entry::from_decoder(entry) {
+                    Ok(value) => value,
+                    Err(err) => {
+                        write_json_error(&mut writer, &err)?;
+                        writer.end_object()?;
+                        writer.end_array()?;
+                        writer.end_object()?;
+                        failure.get_or_insert(err);
+                        break 'directories;
+                    }
+                };
+
+                sparse_chunks.record(tag, &value);
                 writer.write_key("value")?;
-                match tiff::Entry::from_decoder(entry)? {
+                match value {
                     tiff::Entry::Ascii(string) => writer.write_str(&string)?,
                     tiff::Entry::Bytes(values) => print_json_values!(writer.write_u8(values)),
                     tiff::Entry::U8(values) => print_json_values!(writer.write_u8(values)),
@@ -246,6 +289,12 @@ fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
             }
             writer.end_array()?;
 
+            let sparse_count = sparse_chunks.count();
+            if sparse_count > 0 {
+                writer.write_key("sparse_chunks")?;
+                writer.write_u64(sparse_count as u64)?;
+            }
+
             writer.end_object()?;
         }
         writer.end_array()?;
@@ -257,7 +306,102 @@ fn dump_json(files: &[PathBuf]) -> anyhow::Result<()> {
         writer.end_array()?;
     }
 
-    Ok(())
+    match failure {
+        Some(err) => Err(err.into()),
+        None => Ok(()),
+    }
+}
+
+/// The offsets and byte counts of the strips or tiles of a directory.
+#[derive(Default)]
+struct SparseChunks {
+    offsets: Vec<u64>,
+    byte_counts: Vec<u64>,
+}
+
+impl SparseChunks {
+    /// Keeps the values of the entry if it holds the offsets or byte counts of the chunks.
+    fn record(&mut self, tag: tiff::Tag, value: &tiff::Entry) {
+        let values = match tag {
+            tiff::Tag::STRIP_OFFSETS | tiff::Tag::TILE_OFFSETS => &mut self.offsets,
+            tiff::Tag::STRIP_BYTE_COUNTS | tiff::Tag::TILE_BYTE_COUNTS => &mut self.byte_counts,
+            _ => return,
+        };
+        *values = match value {
+            tiff::Entry::U16(values) => values.iter().map(|&value| value as u64).collect(),
+            tiff::Entry::U32(values) => values.iter().map(|&value| value as u64).collect(),
+            tiff::Entry::U64(values) => values.clone(),
+            _ => return,
+        };
+    }
+
+    /// Returns the number of sparse chunks, whose offset and byte count are both zero.
+    fn count(&self) -> usize {
+        self.offsets
+            .iter()
+            .zip(&self.byte_counts)
+            .filter(|&(&offset, &byte_count)| offset == 0 && byte_count == 0)
+            .count()
+    }
+}
+
+/// Writes the error and the location where it occurred as the `error` key of the current object.
+fn write_json_error<W>(
+    writer: &mut crate::utils::JsonWriter<W>,
+    err: &tiff::Error,
+) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    writer.write_key("error")?;
+    writer.start_object()?;
+    writer.write_key("message")?;
+    writer.write_str(&format!("{err:#}"))?;
+
+    if let Some(location) = err.location() {
+        writer.write_key("location")?;
+        write_json_location(writer, &location)?;
+    }
+
+    writer.end_object()
+}
+
+/// Writes the location in the file as an object.
+pub(super) fn write_json_location<W>(
+    writer: &mut crate::utils::JsonWriter<W>,
+    location: &tiff::Location,
+) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    writer.start_object()?;
+    writer.write_key("offset")?;
+    writer.write_u64(location.offset)?;
+    if let Some(directory) = location.directory {
+        writer.write_key("directory")?;
+        writer.write_u64(directory as u64)?;
+    }
+    if let Some(entry) = location.entry {
+        writer.write_key("entry")?;
+        writer.write_u64(entry)?;
+    }
+    if let Some(tag) = location.tag {
+        writer.write_key("tag")?;
+        writer.start_object()?;
+        writer.write_key("id")?;
+        writer.write_u16(tag.0)?;
+        writer.write_key("name")?;
+        writer.write_str(tag.name())?;
+        writer.end_object()?;
+    }
+    if let Some(part) = location.part {
+        writer.write_key("part")?;
+        writer.write_str(match part {
+            tiff::EntryPart::Header => "header",
+            tiff::EntryPart::Payload => "payload",
+        })?;
+    }
+    writer.end_object()
 }
 
 fn dump_terminal(files: &[PathBuf], maxitems: usize) -> anyhow::Result<()> {
@@ -314,10 +458,14 @@ fn dump_terminal(files: &[PathBuf], maxitems: usize) -> anyhow::Result<()> {
                 next = directory.next_offset,
             );
 
+            let mut sparse_chunks = SparseChunks::default();
             let mut entries = directory.entries();
             while let Some(entry) = entries.next_entry()? {
                 print!("{:?} {:?} {}<", entry.tag, entry.dtype, entry.count);
-                match tiff::Entry::from_decoder(entry)? {
+                let tag = entry.tag;
+                let value = tiff::Entry::from_decoder(entry)?;
+                sparse_chunks.record(tag, &value);
+                match value {
                     tiff::Entry::Ascii(string) => print_string!(string[..maxitems]),
                     tiff::Entry::Bytes(bytes) => print_bytes!(bytes[..maxitems]),
                     tiff::Entry::U8(values) => print_values!(values[..maxitems]),
@@ -336,6 +484,14 @@ fn dump_terminal(files: &[PathBuf], maxitems: usize) -> anyhow::Result<()> {
                 println!(">");
             }
 
+            let sparse_count = sparse_chunks.count();
+            if sparse_count > 0 {
+                println!(
+                    "Sparse chunks: {sparse_count} of {}",
+                    sparse_chunks.offsets.len()
+                );
+            }
+
             directory_index += 1;
         }
     }
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use aira::tiff;
+use anyhow::{bail, Context};
+
+pub struct TiffLint;
+
+impl From<TiffLint> for clap::Command {
+    fn from(_: TiffLint) -> Self {
+        clap::Command::new(TiffLint::ID)
+            .about("Check the conformance of TIFF files to the TIFF, BigTIFF and GeoTIFF specifications")
+            .arg(
+                clap::Arg::new("json")
+                    .long("json")
+                    .help("The output is formatted as a JSON string")
+                    .action(clap::ArgAction::SetTrue),
+            )
+            .arg(
+                clap::Arg::new("files")
+                    .help("The list of files to be checked")
+                    .action(clap::ArgAction::Append)
+                    .value_parser(clap::value_parser!(PathBuf))
+                    .required(true),
+            )
+    }
+}
+
+impl TiffLint {
+    pub const ID: &'static str = "tiff-lint";
+
+    pub fn run(matches: &clap::ArgMatches) -> anyhow::Result<()> {
+        let json = matches.get_flag("json");
+        let files = matches
+            .get_many::<PathBuf>("files")
+            .expect("Files are required")
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let mut violations_count = 0;
+        let mut reports = Vec::with_capacity(files.len());
+        for path in files {
+            let file = std::fs::File::open(&path)
+                .with_context(|| format!("Failed to open {}", path.display()))?;
+            let reader = std::io::BufReader::new(file);
+            let violations = match tiff::Decoder::new(reader) {
+                Ok(mut decoder) => tiff::lint::check(&mut decoder),
+                // The file header cannot be decoded, there is nothing more to check.
+                Err(err) => vec![err.into()],
+            };
+            violations_count += violations.len();
+            reports.push((path, violations));
+        }
+
+        if json {
+            print_json(&reports)?;
+        } else {
+            print_terminal(&reports);
+        }
+
+        if violations_count > 0 {
+            bail!("Found {violations_count} violations");
+        }
+        Ok(())
+    }
+}
+
+fn print_json(reports: &[(PathBuf, Vec<tiff::lint::Violation>)]) -> std::io::Result<()> {
+    let mut writer = crate::utils::JsonWriter::new(std::io::stdout());
+
+    writer.start_array()?;
+    for (path, violations) in reports {
+        writer.start_object()?;
+        writer.write_key("path")?;
+        writer.write_str(&path.display().to_string())?;
+
+        writer.write_key("violations")?;
+        writer.start_array()?;
+        for violation in violations {
+            writer.start_object()?;
+            writer.write_key("rule")?;
+            writer.write_str(violation.rule.name())?;
+            writer.write_key("message")?;
+            writer.write_str(&violation.message)?;
+            writer.write_key("location")?;
+            super::tiffdump::write_json_location(&mut writer, &violation.location)?;
+            writer.end_object()?;
+        }
+        writer.end_array()?;
+
+        writer.end_object()?;
+    }
+    writer.end_array()
+}
+
+fn print_terminal(reports: &[(PathBuf, Vec<tiff::lint::Violation>)]) {
+    for (path, violations) in reports {
+        for violation in violations {
+            println!(
+                "{}: {}: [{}] {}",
+                path.display(),
+                violation.location,
+                violation.rule.name(),
+                violation.message
+            );
+        }
+    }
+}
@@ -7,12 +7,14 @@ fn main() -> ExitCode {
     let command = clap::command!()
         .subcommand_required(true)
         .subcommand(cmd::TiffDump)
+        .subcommand(cmd::TiffLint)
         .max_term_width(100);
 
     let matches = command.get_matches();
     let subcommand = matches.subcommand().expect("Missing required subcommand");
     let result = match subcommand {
         (cmd::TiffDump::ID, matches) => cmd::TiffDump::run(matches),
+        (cmd::TiffLint::ID, matches) => cmd::TiffLint::run(matches),
         (cmd, _) => unreachable!("Unhandled command {cmd}"),
     };
 
@@ -0,0 +1,609 @@
+//! Conversion of the pixels to RGB for display.
+//!
+//! The [`ColorConverter`] maps the samples of an image to 8-bit sRGB colors, with or without an
+//! alpha channel, according to its photometric interpretation:
+//!
+//! * WhiteIsZero and BlackIsZero are converted to gray levels.
+//! * Palette colors are looked up in the `ColorMap`.
+//! * Separated colors are converted from CMYK, other inks are not supported.
+//! * YCbCr colors are converted using the `YCbCrCoefficients` and the `ReferenceBlackWhite`.
+//! * CIELab and ICCLab colors are converted using the D65 white point of sRGB.
+//!
+//! LogL and LogLuv pixels hold floating-point values, they are read with the
+//! [`LogLuvReader`](crate::logluv::LogLuvReader).
+//!
+//! The alpha channel is the first extra sample marked as associated or unassociated alpha in
+//! `ExtraSamples`, the other extra samples are ignored. RGBA8 colors are returned with
+//! unassociated alpha, and RGB8 colors are composited over black.
+//!
+//! The samples are given as unsigned integers, either unpacked with an
+//! [`Unpacker`](crate::unpack::Unpacker) or read with a [`PixelReader`](crate::reader::PixelReader).
+//! Subsampled YCbCr pixels must be upsampled to full resolution first, as done by the
+//! [`PixelReader`](crate::reader::PixelReader).
+//!
+//! ```
+//! use aira_tiff::{
+//!     color::{ColorConverter, ColorFormat},
+//!     reader::PixelReader,
+//!     Decoder, Metadata,
+//! };
+//!
+//! let file = std::fs::File::open("tests/images/minisblack-2c-8b-alpha.tiff")?;
+//! let mut decoder = Decoder::new(std::io::BufReader::new(file))?;
+//! let byteorder = decoder.byteorder();
+//! let mut directories = decoder.directories();
+//! let directory = directories.next_directory()?.unwrap();
+//! let metadata = Metadata::from_decoder(directory)?;
+//!
+//! let pixels = PixelReader::new(&metadata, byteorder)?;
+//! let mut samples = vec![0u8; pixels.image_len(2)];
+//! pixels.read_image(&mut decoder.into_inner(), &mut samples)?;
+//!
+//! let converter = ColorConverter::new(&metadata, ColorFormat::Rgba8)?;
+//! let mut rgba = vec![0u8; samples.len() / 2 * 4];
+//! converter.convert_u8(&samples, &mut rgba);
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use crate::{
+    entry::EntryRef, error::ErrorContext, metadata::ColorMap, Error, ExtraSample, Interpretation,
+    Metadata, SampleFormat, Tag, Unsupported,
+};
+
+/// The layout of the converted colors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ColorFormat {
+    /// Red, green and blue bytes.
+    Rgb8,
+    /// Red, green, blue and alpha bytes, the alpha is not associated.
+    Rgba8,
+}
+
+impl ColorFormat {
+    /// Returns the number of bytes of a color.
+    pub fn channels(self) -> usize {
+        match self {
+            ColorFormat::Rgb8 => 3,
+            ColorFormat::Rgba8 => 4,
+        }
+    }
+}
+
+/// Converts the samples of an image to RGB colors.
+#[derive(Clone, Debug)]
+pub struct ColorConverter {
+    model: Model,
+    format: ColorFormat,
+    /// The number of bits of each sample.
+    bits: u16,
+    /// The number of samples of a pixel, including the extra samples.
+    samples: usize,
+    alpha: Option<Alpha>,
+}
+
+/// The conversion of the color samples of a pixel.
+#[derive(Clone, Debug)]
+enum Model {
+    Gray { inverted: bool },
+    Rgb,
+    Palette(Vec<[u8; 3]>),
+    Cmyk,
+    YCbCr(YCbCr),
+    Lab(LabEncoding),
+}
+
+/// The position and the kind of the alpha sample.
+#[derive(Clone, Copy, Debug)]
+struct Alpha {
+    index: usize,
+    associated: bool,
+}
+
+/// Value of the `InkSet` tag for CMYK inks.
+const INK_SET_CMYK: u16 = 1;
+
+impl ColorConverter {
+    /// Creates a new instance of [`ColorConverter`] for the image described by `metadata`.
+    ///
+    /// All the samples must be unsigned integers of the same size, from 1 to 16 bits. CIELab and
+    /// ICCLab samples must have 8 or 16 bits.
+    pub fn new(metadata: &Metadata, format: ColorFormat) -> Result<Self, Error> {
+        let interpretation = metadata.interpretation;
+        Self::build(metadata, format).with_context(|| {
+            format!("Failed to convert pixels with photometric interpretation {interpretation:?}")
+        })
+    }
+
+    fn build(metadata: &Metadata, format: ColorFormat) -> Result<Self, Error> {
+        let samples = metadata.samples();
+        let Some(first) = samples.first() else {
+            return Err(Error::from_static_str(
+                "Pixels without samples cannot be converted",
+            ));
+        };
+        if samples.iter().any(|sample| *sample != *first) {
+            return Err(Error::from_static_str(
+                "Pixels with samples of different types cannot be converted",
+            ));
+        }
+        if first.format != SampleFormat::UNSIGNED {
+            return Err(Error::from_args(format_args!(
+                "Pixels with {:?} samples cannot be converted",
+                first.format
+            )));
+        }
+        if !(1..=16).contains(&first.bits) {
+            return Err(Unsupported::BitsPerSample(first.bits).into());
+        }
+        let bits = first.bits;
+
+        let (model, colors) = match metadata.interpretation {
+            Interpretation::WHITE_IS_ZERO => (Model::Gray { inverted: true }, 1),
+            Interpretation::BLACK_IS_ZERO => (Model::Gray { inverted: false }, 1),
+            Interpretation::RGB => (Model::Rgb, 3),
+            Interpretation::PALETTE => (Model::Palette(read_color_map(metadata, bits)?), 1),
+            Interpretation::SEPARATED => {
+                let inkset = read_u16(metadata, Tag::INK_SET)?.unwrap_or(INK_SET_CMYK);
+                if inkset != INK_SET_CMYK {
+                    return Err(Error::from_args(format_args!(
+                        "Separated pixels with ink set {inkset} cannot be converted"
+                    )));
+                }
+                (Model::Cmyk, 4)
+            }
+            Interpretation::YCBCR => (Model::YCbCr(YCbCr::new(metadata, bits)?), 3),
+            Interpretation::CIELAB => (Model::Lab(LabEncoding::new(false, bits)?), 3),
+            Interpretation::ICCLAB => (Model::Lab(LabEncoding::new(true, bits)?), 3),
+            interpretation => return Err(Unsupported::Interpretation(interpretation).into()),
+        };
+
+        // The metadata ensures that there are no more extra samples than samples.
+        let extra_samples = metadata.extra_samples();
+        let first_extra = samples.len() - extra_samples.len();
+        if first_extra < colors {
+            return Err(Error::from_args(format_args!(
+                "Pixels have {first_extra} color samples, {colors} are expected"
+            )));
+        }
+
+        let alpha = extra_samples
+            .iter()
+            .position(|&extra| {
+                matches!(
+                    extra,
+                    ExtraSample::ASSOCIATED_ALPHA | ExtraSample::UNASSOCIATED_ALPHA
+                )
+            })
+            .map(|position| Alpha {
+                index: first_extra + position,
+                associated: extra_samples[position] == ExtraSample::ASSOCIATED_ALPHA,
+            });
+
+        Ok(Self {
+            model,
+            format,
+            bits,
+            samples: samples.len(),
+            alpha,
+        })
+    }
+
+    /// Returns the layout of the converted colors.
+    pub fn format(&self) -> ColorFormat {
+        self.format
+    }
+
+    /// Returns the number of samples of a pixel, including the extra samples.
+    pub fn samples_per_pixel(&self) -> usize {
+        self.samples
+    }
+
+    /// Returns `true` if the pixels have an alpha sample.
+    pub fn has_alpha(&self) -> bool {
+        self.alpha.is_some()
+    }
+
+    /// Converts the pixels of samples of at most 8 bits stored in `src` to colors in `dst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the samples have more than 8 bits, if the length of `src` is not a multiple of
+    /// [`samples_per_pixel`](Self::samples_per_pixel), or if `dst` cannot hold exactly the colors
+    /// of all the pixels.
+    pub fn convert_u8(&self, src: &[u8], dst: &mut [u8]) {
+        assert!(
+            self.bits <= 8,
+            "samples of {} bits do not fit in a byte",
+            self.bits
+        );
+        self.convert(src, dst);
+    }
+
+    /// Converts the pixels of samples of at most 16 bits stored in `src` to colors in `dst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `src` is not a multiple of
+    /// [`samples_per_pixel`](Self::samples_per_pixel), or if `dst` cannot hold exactly the colors
+    /// of all the pixels.
+    pub fn convert_u16(&self, src: &[u16], dst: &mut [u8]) {
+        self.convert(src, dst);
+    }
+
+    fn convert<T>(&self, src: &[T], dst: &mut [u8])
+    where
+        T: Copy + Into<u32>,
+    {
+        let channels = self.format.channels();
+        assert_eq!(
+            src.len() % self.samples,
+            0,
+            "source slice does not hold whole pixels"
+        );
+        assert_eq!(
+            dst.len(),
+            src.len() / self.samples * channels,
+            "destination slice does not match the number of pixels"
+        );
+
+        let max = (1u32 << self.bits) - 1;
+        let pixels = src.chunks_exact(self.samples);
+        for (pixel, dst) in pixels.zip(dst.chunks_exact_mut(channels)) {
+            // Values larger than the size of samples are clamped.
+            let mut values = [0; 4];
+            for (value, &sample) in values.iter_mut().zip(pixel) {
+                *value = sample.into().min(max);
+            }
+
+            let mut rgb = self.model.to_rgb(values, max);
+            let alpha = match self.alpha {
+                Some(alpha) => {
+                    let value = scale(pixel[alpha.index].into().min(max), max);
+                    match (self.format, alpha.associated) {
+                        (ColorFormat::Rgba8, true) => rgb.iter_mut().for_each(|c| {
+                            *c = dissociate(*c, value);
+                        }),
+                        (ColorFormat::Rgb8, false) => rgb.iter_mut().for_each(|c| {
+                            *c = associate(*c, value);
+                        }),
+                        _ => {}
+                    }
+                    value
+                }
+                None => u8::MAX,
+            };
+
+            dst[..3].copy_from_slice(&rgb);
+            if let ColorFormat::Rgba8 = self.format {
+                dst[3] = alpha;
+            }
+        }
+    }
+}
+
+impl Model {
+    /// Converts the color samples of a pixel, at most `max`, to RGB.
+    #[inline(always)]
+    fn to_rgb(&self, values: [u32; 4], max: u32) -> [u8; 3] {
+        match self {
+            Model::Gray { inverted } => {
+                let value = match inverted {
+                    true => max - values[0],
+                    false => values[0],
+                };
+                [scale(value, max); 3]
+            }
+            Model::Rgb => [
+                scale(values[0], max),
+                scale(values[1], max),
+                scale(values[2], max),
+            ],
+            Model::Palette(colors) => colors[values[0] as usize],
+            Model::Cmyk => {
+                let [c, m, y, k] = values.map(|value| max - value);
+                [c, m, y].map(|value| scale(value * k / max, max))
+            }
+            Model::YCbCr(ycbcr) => ycbcr.to_rgb([values[0], values[1], values[2]]),
+            Model::Lab(encoding) => {
+                let lab = encoding.decode([values[0], values[1], values[2]]);
+                encode_srgb(xyz_to_linear_rgb(lab_to_xyz(lab)))
+            }
+        }
+    }
+}
+
+/// The parameters of the conversion from YCbCr to RGB.
+#[derive(Clone, Debug)]
+struct YCbCr {
+    /// The coefficients of red, green and blue in the luma.
+    luma: [f32; 3],
+    /// The reference black and white of the luma and the chroma samples.
+    reference: [f32; 6],
+}
+
+impl YCbCr {
+    fn new(metadata: &Metadata, bits: u16) -> Result<Self, Error> {
+        let luma = match read_ratios(metadata, Tag::YCBCR_COEFFICIENTS)?.as_deref() {
+            Some(&[red, green, blue]) => [red, green, blue],
+            Some(_) => return Err(invalid_count(Tag::YCBCR_COEFFICIENTS, 3)),
+            None => [0.299, 0.587, 0.114],
+        };
+        if luma[1] == 0.0 {
+            return Err(Error::from_static_str(
+                "The green coefficient of the luma is zero",
+            ));
+        }
+
+        let max = ((1u32 << bits) - 1) as f32;
+        let half = (1u32 << (bits - 1)) as f32;
+        let reference = match read_ratios(metadata, Tag::REFERENCE_BLACK_WHITE)? {
+            Some(values) => <[f32; 6]>::try_from(values)
+                .map_err(|_| invalid_count(Tag::REFERENCE_BLACK_WHITE, 6))?,
+            None => [0.0, max, half, max, half, max],
+        };
+        if reference.chunks_exact(2).any(|pair| pair[0] == pair[1]) {
+            return Err(Error::from_static_str(
+                "The reference black and white are equal",
+            ));
+        }
+
+        Ok(Self { luma, reference })
+    }
+
+    #[inline(always)]
+    fn to_rgb(&self, [y, cb, cr]: [u32; 3]) -> [u8; 3] {
+        let [black_y, white_y, black_cb, white_cb, black_cr, white_cr] = self.reference;
+        let [luma_red, luma_green, luma_blue] = self.luma;
+
+        let y = (y as f32 - black_y) * 255.0 / (white_y - black_y);
+        let cb = (cb as f32 - black_cb) * 127.0 / (white_cb - black_cb);
+        let cr = (cr as f32 - black_cr) * 127.0 / (white_cr - black_cr);
+
+        let red = y + cr * (2.0 - 2.0 * luma_red);
+        let blue = y + cb * (2.0 - 2.0 * luma_blue);
+        let green = (y - luma_blue * blue - luma_red * red) / luma_green;
+        [red, green, blue].map(|value| value.round().clamp(0.0, 255.0) as u8)
+    }
+}
+
+/// The encoding of CIELab samples.
+#[derive(Clone, Copy, Debug)]
+enum LabEncoding {
+    /// The a* and b* samples are signed integers of 8 bits.
+    Cie8,
+    /// The a* and b* samples are signed integers of 16 bits.
+    Cie16,
+    /// The a* and b* samples are unsigned integers of 8 bits offset by 128.
+    Icc8,
+    /// The a* and b* samples are unsigned integers of 16 bits offset by 32768.
+    Icc16,
+}
+
+impl LabEncoding {
+    fn new(icc: bool, bits: u16) -> Result<Self, Error> {
+        match (icc, bits) {
+            (false, 8) => Ok(LabEncoding::Cie8),
+            (false, 16) => Ok(LabEncoding::Cie16),
+            (true, 8) => Ok(LabEncoding::Icc8),
+            (true, 16) => Ok(LabEncoding::Icc16),
+            (_, bits) => Err(Unsupported::BitsPerSample(bits).into()),
+        }
+    }
+
+    /// Decodes the L*, a* and b* components.
+    #[inline(always)]
+    fn decode(self, [l, a, b]: [u32; 3]) -> [f32; 3] {
+        match self {
+            LabEncoding::Cie8 => [
+                l as f32 * 100.0 / 255.0,
+                a as u8 as i8 as f32,
+                b as u8 as i8 as f32,
+            ],
+            LabEncoding::Cie16 => [
+                l as f32 * 100.0 / 65535.0,
+                a as u16 as i16 as f32 / 256.0,
+                b as u16 as i16 as f32 / 256.0,
+            ],
+            LabEncoding::Icc8 => [l as f32 * 100.0 / 255.0, a as f32 - 128.0, b as f32 - 128.0],
+            LabEncoding::Icc16 => [
+                l as f32 * 100.0 / 65535.0,
+                a as f32 / 256.0 - 128.0,
+                b as f32 / 256.0 - 128.0,
+            ],
+        }
+    }
+}
+
+/// The XYZ coordinates of the D65 white point.
+const D65: [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Converts CIELab components to XYZ coordinates relative to the D65 white point.
+fn lab_to_xyz([l, a, b]: [f32; 3]) -> [f32; 3] {
+    const DELTA: f32 = 6.0 / 29.0;
+
+    let fy = (l + 16.0) / 116.0;
+    let f = [fy + a / 500.0, fy, fy - b / 200.0];
+    let mut xyz = f.map(|t| match t > DELTA {
+        true => t * t * t,
+        false => 3.0 * DELTA * DELTA * (t - 4.0 / 29.0),
+    });
+    for (value, white) in xyz.iter_mut().zip(D65) {
+        *value *= white;
+    }
+    xyz
+}
+
+/// Converts CIE XYZ coordinates to linear sRGB components.
+///
+/// The components are not clamped, colors out of the sRGB gamut have negative components.
+pub fn xyz_to_linear_rgb([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [
+        3.240_454_2 * x - 1.537_138_5 * y - 0.498_531_4 * z,
+        -0.969_266 * x + 1.876_010_8 * y + 0.041_556 * z,
+        0.055_643_4 * x - 0.204_025_9 * y + 1.057_225_2 * z,
+    ]
+}
+
+/// Applies the sRGB transfer function to linear components, and quantizes them to 8 bits.
+fn encode_srgb(rgb: [f32; 3]) -> [u8; 3] {
+    rgb.map(|value| {
+        let value = value.clamp(0.0, 1.0);
+        let value = match value <= 0.003_130_8 {
+            true => 12.92 * value,
+            false => 1.055 * value.powf(1.0 / 2.4) - 0.055,
+        };
+        (value * 255.0).round() as u8
+    })
+}
+
+/// Scales a value at most `max` to 8 bits.
+#[inline(always)]
+fn scale(value: u32, max: u32) -> u8 {
+    ((value * 255 + max / 2) / max) as u8
+}
+
+/// Multiplies a color component by its alpha.
+#[inline(always)]
+fn associate(value: u8, alpha: u8) -> u8 {
+    ((value as u32 * alpha as u32 + 127) / 255) as u8
+}
+
+/// Divides a color component by its alpha.
+#[inline(always)]
+fn dissociate(value: u8, alpha: u8) -> u8 {
+    match alpha {
+        0 => 0,
+        alpha => ((value as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8,
+    }
+}
+
+/// Reads the palette of `2^bits` colors from the `ColorMap` entry.
+///
+/// Some writers store 8-bit values in the color map, they are detected as libtiff does when all
+/// the values are less than 256.
+fn read_color_map(metadata: &Metadata, bits: u16) -> Result<Vec<[u8; 3]>, Error> {
+    if bits > 8 {
+        return Err(Error::from(Unsupported::BitsPerSample(bits))
+            .with_context(|| "Palette with more than 256 colors cannot be converted".to_string()));
+    }
+
+    // The metadata ensures that the color map holds `2^bits` colors.
+    let Some(ColorMap { red, green, blue }) = metadata.color_map() else {
+        return Err(crate::metadata::MissingRequiredTag(Tag::COLOR_MAP).into());
+    };
+
+    let max = match [red, green, blue]
+        .iter()
+        .all(|values| values.iter().all(|&value| value < 256))
+    {
+        true => 255,
+        false => 65535,
+    };
+    let colors = red
+        .iter()
+        .zip(green)
+        .zip(blue)
+        .map(|((&red, &green), &blue)| [red, green, blue].map(|value| scale(value as u32, max)))
+        .collect();
+    Ok(colors)
+}
+
+/// Reads the single value of an entry of 16-bit integers.
+fn read_u16(metadata: &Metadata, tag: Tag) -> Result<Option<u16>, Error> {
+    match read_u16s(metadata, tag)?.as_deref() {
+        Some(&[value]) => Ok(Some(value)),
+        Some(_) => Err(invalid_count(tag, 1)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the values of an entry of 16-bit integers.
+fn read_u16s(metadata: &Metadata, tag: Tag) -> Result<Option<Vec<u16>>, Error> {
+    match metadata.custom_entry(tag) {
+        Some(EntryRef::U16(values)) => Ok(Some(values.to_vec())),
+        Some(EntryRef::U8(values)) => Ok(Some(values.iter().map(|&v| v as u16).collect())),
+        Some(_) => Err(Error::from_args(format_args!(
+            "Entry {tag:?} does not hold 16-bit integers"
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Reads the values of an entry of rational numbers.
+fn read_ratios(metadata: &Metadata, tag: Tag) -> Result<Option<Vec<f32>>, Error> {
+    match metadata.custom_entry(tag) {
+        Some(EntryRef::Ratio(values)) => Ok(Some(
+            values
+                .iter()
+                .map(|ratio| ratio.num as f32 / ratio.den as f32)
+                .collect(),
+        )),
+        Some(_) => Err(Error::from_args(format_args!(
+            "Entry {tag:?} does not hold rational numbers"
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn invalid_count(tag: Tag, expected: usize) -> Error {
+    Error::from_args(format_args!(
+        "Entry {tag:?} does not hold {expected} values"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_to_8bits() {
+        assert_eq!(scale(0, 1), 0);
+        assert_eq!(scale(1, 1), 255);
+        assert_eq!(scale(7, 15), 119);
+        assert_eq!(scale(65535, 65535), 255);
+        assert_eq!(scale(32768, 65535), 128);
+    }
+
+    #[test]
+    fn associated_alpha() {
+        assert_eq!(associate(200, 255), 200);
+        assert_eq!(associate(200, 0), 0);
+        assert_eq!(associate(200, 128), 100);
+        assert_eq!(dissociate(100, 128), 199);
+        assert_eq!(dissociate(100, 0), 0);
+        assert_eq!(dissociate(200, 100), 255);
+    }
+
+    #[test]
+    fn ycbcr_to_rgb() {
+        let ycbcr = YCbCr {
+            luma: [0.299, 0.587, 0.114],
+            reference: [0.0, 255.0, 128.0, 255.0, 128.0, 255.0],
+        };
+        assert_eq!(ycbcr.to_rgb([0, 128, 128]), [0, 0, 0]);
+        assert_eq!(ycbcr.to_rgb([255, 128, 128]), [255, 255, 255]);
+        assert_eq!(ycbcr.to_rgb([76, 85, 255]), [254, 0, 0]);
+        assert_eq!(ycbcr.to_rgb([29, 255, 107]), [0, 0, 254]);
+    }
+
+    #[test]
+    fn lab_to_srgb() {
+        let convert = |encoding: LabEncoding, lab| {
+            encode_srgb(xyz_to_linear_rgb(lab_to_xyz(encoding.decode(lab))))
+        };
+        assert_eq!(convert(LabEncoding::Cie8, [0, 0, 0]), [0, 0, 0]);
+        assert_eq!(convert(LabEncoding::Cie8, [255, 0, 0]), [255, 255, 255]);
+        assert_eq!(convert(LabEncoding::Icc8, [255, 128, 128]), [255, 255, 255]);
+        assert_eq!(convert(LabEncoding::Cie16, [65535, 0, 0]), [255, 255, 255]);
+        assert_eq!(
+            convert(LabEncoding::Icc16, [65535, 32768, 32768]),
+            [255, 255, 255]
+        );
+
+        // The red primary of sRGB is L*=53.24, a*=80.09, b*=67.20.
+        let red = convert(LabEncoding::Cie8, [136, 80, 67]);
+        assert!(red[0] >= 253 && red[1] <= 2 && red[2] <= 2, "{red:?}");
+        let red = convert(LabEncoding::Cie16, [34891, 80 << 8, 67 << 8]);
+        assert!(red[0] >= 253 && red[1] <= 2 && red[2] <= 2, "{red:?}");
+    }
+}
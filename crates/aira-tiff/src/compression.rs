@@ -1,16 +1,22 @@
 //! TIFF compression algorithms.
 
-use crate::Error;
+use crate::{Error, Unsupported};
 
 #[cfg(feature = "deflate")]
 mod deflate;
+#[cfg(feature = "lzw")]
+mod lzw;
 
 mod packbits;
+mod sgilog;
 
 #[cfg(feature = "deflate")]
-pub use deflate::DeflateReader;
+pub use deflate::{DeflateReader, DeflateWriter};
+#[cfg(feature = "lzw")]
+pub use lzw::{LzwReader, LzwWriter};
 
-pub use packbits::PackBitsReader;
+pub use packbits::{PackBitsReader, PackBitsWriter};
+pub use sgilog::SgiLogReader;
 
 /// Data compression algorithm.
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
@@ -49,6 +55,10 @@ impl Compression {
     pub const LEGACY_DEFLATE: Self = Self(32946);
     /// PackBits compression.
     pub const PACKBITS: Self = Self(32773);
+    /// SGI Log run-length compression of LogL and LogLuv pixels.
+    pub const SGILOG: Self = Self(34676);
+    /// SGI Log compression of LogLuv pixels encoded in 24 bits.
+    pub const SGILOG24: Self = Self(34677);
 }
 
 impl Compression {
@@ -65,12 +75,17 @@ impl Compression {
             8 => "Deflate",
             32946 => "Deflate",
             32773 => "PackBits",
+            34676 => "SGILog",
+            34677 => "SGILog24",
             _ => "Unknown",
         }
     }
 }
 
 /// TIFF decompression reader.
+///
+/// The SGI Log compressions are not supported, LogL and LogLuv pixels are read with
+/// [`LogLuvReader`](crate::logluv::LogLuvReader).
 #[derive(Debug)]
 pub struct DecompressReader<R> {
     inner: DecompressReaderInner<R>,
@@ -82,6 +97,8 @@ enum DecompressReaderInner<R> {
     PackBits(PackBitsReader<R>),
     #[cfg(feature = "deflate")]
     Deflate(DeflateReader<R>),
+    #[cfg(feature = "lzw")]
+    Lzw(LzwReader<R>),
 }
 
 impl<R> DecompressReader<R> {
@@ -97,11 +114,9 @@ impl<R> DecompressReader<R> {
             Compression::DEFLATE | Compression::LEGACY_DEFLATE => {
                 DecompressReaderInner::Deflate(DeflateReader::new(reader))
             }
-            unsupported => {
-                return Err(Error::from_args(format_args!(
-                    "Unsupported compression algorithm: {unsupported:?}"
-                )))
-            }
+            #[cfg(feature = "lzw")]
+            Compression::LZW => DecompressReaderInner::Lzw(LzwReader::new(reader)),
+            unsupported => return Err(Unsupported::Compression(unsupported).into()),
         };
         Ok(Self { inner })
     }
@@ -117,6 +132,118 @@ where
             DecompressReaderInner::PackBits(reader) => reader.read(buf),
             #[cfg(feature = "deflate")]
             DecompressReaderInner::Deflate(reader) => reader.read(buf),
+            #[cfg(feature = "lzw")]
+            DecompressReaderInner::Lzw(reader) => reader.read(buf),
+        }
+    }
+}
+
+/// TIFF compression writer.
+///
+/// The compressed data is complete once [`CompressWriter::finish`] is called.
+#[derive(Debug)]
+pub struct CompressWriter<W>
+where
+    W: std::io::Write,
+{
+    inner: CompressWriterInner<W>,
+}
+
+#[derive(Debug)]
+enum CompressWriterInner<W>
+where
+    W: std::io::Write,
+{
+    None(W),
+    PackBits(PackBitsWriter<W>),
+    #[cfg(feature = "deflate")]
+    Deflate(DeflateWriter<W>),
+    #[cfg(feature = "lzw")]
+    Lzw(LzwWriter<W>),
+}
+
+impl<W> CompressWriter<W>
+where
+    W: std::io::Write,
+{
+    /// Creates a new [`CompressWriter`] from the given writer and compression type.
+    ///
+    /// The data is written by rows of `row_size` bytes, which some compression algorithms do not
+    /// compress across. The default compression level is used.
+    pub fn new(writer: W, compression: Compression, row_size: usize) -> Result<Self, Error> {
+        #[cfg(feature = "deflate")]
+        let level = DeflateWriter::<W>::DEFAULT_LEVEL;
+        #[cfg(not(feature = "deflate"))]
+        let level = 0;
+
+        Self::with_level(writer, compression, row_size, level)
+    }
+
+    /// Creates a new [`CompressWriter`] from the given writer, compression type and level.
+    ///
+    /// The compression `level` is only used by the Deflate compression, see [`DeflateWriter`].
+    #[cfg_attr(not(feature = "deflate"), allow(unused_variables))]
+    pub fn with_level(
+        writer: W,
+        compression: Compression,
+        row_size: usize,
+        level: u32,
+    ) -> Result<Self, Error> {
+        let inner = match compression {
+            Compression::NONE => CompressWriterInner::None(writer),
+            Compression::PACKBITS => {
+                CompressWriterInner::PackBits(PackBitsWriter::new(writer, row_size))
+            }
+            #[cfg(feature = "deflate")]
+            Compression::DEFLATE | Compression::LEGACY_DEFLATE => {
+                CompressWriterInner::Deflate(DeflateWriter::new(writer, level))
+            }
+            #[cfg(feature = "lzw")]
+            Compression::LZW => CompressWriterInner::Lzw(LzwWriter::new(writer)),
+            unsupported => return Err(Unsupported::Compression(unsupported).into()),
+        };
+        Ok(Self { inner })
+    }
+
+    /// Writes the remaining compressed data, flushes the inner writer and returns it.
+    pub fn finish(self) -> std::io::Result<W> {
+        match self.inner {
+            CompressWriterInner::None(mut writer) => {
+                writer.flush()?;
+                Ok(writer)
+            }
+            CompressWriterInner::PackBits(writer) => writer.finish(),
+            #[cfg(feature = "deflate")]
+            CompressWriterInner::Deflate(writer) => writer.finish(),
+            #[cfg(feature = "lzw")]
+            CompressWriterInner::Lzw(writer) => writer.finish(),
+        }
+    }
+}
+
+impl<W> std::io::Write for CompressWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.inner {
+            CompressWriterInner::None(writer) => writer.write(buf),
+            CompressWriterInner::PackBits(writer) => writer.write(buf),
+            #[cfg(feature = "deflate")]
+            CompressWriterInner::Deflate(writer) => writer.write(buf),
+            #[cfg(feature = "lzw")]
+            CompressWriterInner::Lzw(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.inner {
+            CompressWriterInner::None(writer) => writer.flush(),
+            CompressWriterInner::PackBits(writer) => writer.flush(),
+            #[cfg(feature = "deflate")]
+            CompressWriterInner::Deflate(writer) => writer.flush(),
+            #[cfg(feature = "lzw")]
+            CompressWriterInner::Lzw(writer) => writer.flush(),
         }
     }
 }
@@ -25,3 +25,83 @@ where
         self.inner.read(buf)
     }
 }
+
+/// Deflate encoder.
+#[derive(Debug)]
+pub struct DeflateWriter<W>
+where
+    W: std::io::Write,
+{
+    inner: flate2::write::ZlibEncoder<W>,
+}
+
+impl<W> DeflateWriter<W>
+where
+    W: std::io::Write,
+{
+    /// The default compression level, a compromise between speed and size.
+    pub const DEFAULT_LEVEL: u32 = 6;
+    /// The highest compression level.
+    pub const MAX_LEVEL: u32 = 9;
+
+    /// Creates a new [`DeflateWriter`] from the given writer.
+    ///
+    /// The compression `level` is between 0 (no compression) and [`DeflateWriter::MAX_LEVEL`],
+    /// higher levels are clamped.
+    pub fn new(writer: W, level: u32) -> Self {
+        let level = flate2::Compression::new(level.min(Self::MAX_LEVEL));
+        Self {
+            inner: flate2::write::ZlibEncoder::new(writer, level),
+        }
+    }
+
+    /// Writes the remaining compressed data, flushes the inner writer and returns it.
+    pub fn finish(self) -> std::io::Result<W> {
+        let mut writer = self.inner.finish()?;
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W> std::io::Write for DeflateWriter<W>
+where
+    W: std::io::Write,
+{
+    #[inline(always)]
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::*;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        use std::io::{Read, Write};
+
+        let data = std::iter::repeat_with(|| fastrand::u8(..4))
+            .take(100_000)
+            .collect::<Vec<_>>();
+
+        for level in [0, 1, DeflateWriter::<Vec<u8>>::DEFAULT_LEVEL, 42] {
+            let mut writer = DeflateWriter::new(Vec::new(), level);
+            for chunk in data.chunks(1000) {
+                assert_ok!(writer.write_all(chunk));
+            }
+            let encoded = assert_ok!(writer.finish());
+
+            let mut reader = DeflateReader::new(&encoded[..]);
+            let mut decoded = Vec::new();
+            assert_ok!(reader.read_to_end(&mut decoded));
+            assert_eq!(decoded, data, "Level {level}");
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use weezl::{decode, encode, BitOrder, LzwStatus};
+
+/// The size of the LZW code of a single byte.
+const CODE_SIZE: u8 = 8;
+
+/// The size of the buffers used to hold data between the codec and the inner reader or writer.
+const BUFFER_SIZE: usize = 1 << 14;
+
+/// LZW decoder.
+pub struct LzwReader<R> {
+    inner: R,
+    decoder: decode::Decoder,
+    /// The buffer to hold the data read from the inner reader.
+    buffer: Box<[u8]>,
+    /// The range of the buffer not yet decoded.
+    start: usize,
+    end: usize,
+    /// Whether the end of the inner reader is reached.
+    eof: bool,
+}
+
+impl<R> std::fmt::Debug for LzwReader<R>
+where
+    R: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LzwReader")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<R> LzwReader<R> {
+    /// Creates a new [`LzwReader`] from the given reader.
+    pub fn new(reader: R) -> Self
+    where
+        R: std::io::Read,
+    {
+        Self {
+            inner: reader,
+            decoder: decode::Decoder::with_tiff_size_switch(BitOrder::Msb, CODE_SIZE),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+            start: 0,
+            end: 0,
+            eof: false,
+        }
+    }
+}
+
+impl<R> std::io::Read for LzwReader<R>
+where
+    R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.decoder.has_ended() {
+            return Ok(0);
+        }
+
+        loop {
+            if self.start == self.end && !self.eof {
+                self.start = 0;
+                self.end = self.inner.read(&mut self.buffer)?;
+                self.eof = self.end == 0;
+            }
+
+            // The decoder may hold decoded data until it is called again, even without input.
+            let result = self
+                .decoder
+                .decode_bytes(&self.buffer[self.start..self.end], buf);
+            self.start += result.consumed_in;
+
+            match result.status {
+                Ok(LzwStatus::Done) => return Ok(result.consumed_out),
+                Ok(_) if result.consumed_out != 0 => return Ok(result.consumed_out),
+                // Some encoders do not write the end of information code.
+                Ok(_) if self.eof => return Ok(0),
+                Ok(LzwStatus::Ok) => {}
+                Ok(LzwStatus::NoProgress) => {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "LZW decoder cannot make progress",
+                    ));
+                }
+                Err(err) => {
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, err));
+                }
+            }
+        }
+    }
+}
+
+/// LZW encoder.
+///
+/// The end of information code is written by [`LzwWriter::finish`].
+pub struct LzwWriter<W> {
+    inner: W,
+    encoder: encode::Encoder,
+    /// The buffer to hold the encoded data before writing it to the inner writer.
+    buffer: Box<[u8]>,
+}
+
+impl<W> std::fmt::Debug for LzwWriter<W>
+where
+    W: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LzwWriter")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<W> LzwWriter<W> {
+    /// Creates a new [`LzwWriter`] from the given writer.
+    pub fn new(writer: W) -> Self
+    where
+        W: std::io::Write,
+    {
+        Self {
+            inner: writer,
+            encoder: encode::Encoder::with_tiff_size_switch(BitOrder::Msb, CODE_SIZE),
+            buffer: vec![0; BUFFER_SIZE].into_boxed_slice(),
+        }
+    }
+
+    /// Writes the end of information code, flushes the inner writer and returns it.
+    pub fn finish(mut self) -> std::io::Result<W>
+    where
+        W: std::io::Write,
+    {
+        self.encoder.finish();
+        loop {
+            let result = self.encoder.encode_bytes(&[], &mut self.buffer);
+            self.inner.write_all(&self.buffer[..result.consumed_out])?;
+
+            match result.status {
+                Ok(LzwStatus::Done) => break,
+                Ok(LzwStatus::Ok) => {}
+                Ok(LzwStatus::NoProgress) => {
+                    return Err(std::io::Error::other("LZW encoder cannot make progress"));
+                }
+                Err(err) => return Err(std::io::Error::other(err)),
+            }
+        }
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W> std::io::Write for LzwWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        loop {
+            let result = self.encoder.encode_bytes(buf, &mut self.buffer);
+            self.inner.write_all(&self.buffer[..result.consumed_out])?;
+
+            match result.status {
+                Ok(_) if result.consumed_in != 0 => return Ok(result.consumed_in),
+                Ok(LzwStatus::Ok) => {}
+                Ok(_) => {
+                    return Err(std::io::Error::other("LZW encoder cannot make progress"));
+                }
+                Err(err) => return Err(std::io::Error::other(err)),
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::*;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        use std::io::{Read, Write};
+
+        let data = std::iter::repeat_with(|| fastrand::u8(..4))
+            .take(100_000)
+            .collect::<Vec<_>>();
+
+        let mut writer = LzwWriter::new(Vec::new());
+        for chunk in data.chunks(1000) {
+            assert_ok!(writer.write_all(chunk));
+        }
+        let encoded = assert_ok!(writer.finish());
+        assert_lt!(encoded.len(), data.len());
+
+        let mut reader = LzwReader::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        assert_ok!(reader.read_to_end(&mut decoded));
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn decode_without_end_of_information() {
+        use std::io::Read;
+
+        let mut writer = LzwWriter::new(Vec::new());
+        assert_ok!(std::io::Write::write_all(&mut writer, b"Hello"));
+        let mut encoded = assert_ok!(writer.finish());
+        // The clear code, the 5 literal codes and the end of information code use 9 bits each,
+        // removing the last byte truncates the end of information code.
+        encoded.truncate(encoded.len() - 1);
+
+        let mut reader = LzwReader::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        assert_ok!(reader.read_to_end(&mut decoded));
+        assert_eq!(decoded, b"Hello");
+    }
+
+    #[test]
+    fn decode_invalid() {
+        use std::io::Read;
+
+        // A code that is not yet defined just after the clear code.
+        let encoded = [0x80, 0x4b, 0x00];
+
+        let mut reader = LzwReader::new(&encoded[..]);
+        let mut decoded = Vec::new();
+        assert_err!(reader.read_to_end(&mut decoded));
+    }
+}
@@ -32,6 +32,10 @@ where
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         use aira_byteorder::ReadBytesExt;
 
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
         let mut start = 0;
 
         loop {
@@ -81,6 +85,12 @@ where
 
                     let copied = count.min(buf[start..].len());
                     let copied = self.inner.read(&mut buf[start..start + copied])?;
+                    if copied == 0 {
+                        return Err(std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "PackBits literal run is truncated",
+                        ));
+                    }
                     start += copied;
 
                     let count = (count - copied) as u8;
@@ -101,6 +111,128 @@ where
     }
 }
 
+/// The maximum number of bytes of a packet.
+const MAX_PACKET_SIZE: usize = 128;
+
+/// PackBits encoder.
+///
+/// The data is packed separately for each row, as required by the TIFF specification. The data
+/// buffered is written by [`PackBitsWriter::finish`].
+#[derive(Debug)]
+pub struct PackBitsWriter<W> {
+    inner: W,
+    /// The number of bytes of a row, zero if packets may cross rows.
+    row_size: usize,
+    /// The number of bytes of the current row written so far.
+    row_len: usize,
+    /// The bytes not yet packed.
+    pending: Vec<u8>,
+    /// The buffer to hold the packets before writing them to the inner writer.
+    packed: Vec<u8>,
+}
+
+impl<W> PackBitsWriter<W> {
+    /// Creates a new [`PackBitsWriter`] from the given writer.
+    ///
+    /// No packet will cross the boundary of rows of `row_size` bytes, unless `row_size` is zero.
+    pub fn new(writer: W, row_size: usize) -> Self
+    where
+        W: std::io::Write,
+    {
+        Self {
+            inner: writer,
+            row_size,
+            row_len: 0,
+            pending: Vec::with_capacity(2 * MAX_PACKET_SIZE),
+            packed: Vec::with_capacity(2 * MAX_PACKET_SIZE),
+        }
+    }
+
+    /// Packs the remaining data, flushes the inner writer and returns it.
+    pub fn finish(mut self) -> std::io::Result<W>
+    where
+        W: std::io::Write,
+    {
+        self.pack(true)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Packs the pending data and writes the packets to the inner writer.
+    ///
+    /// Unless `all` is set, the data is packed only while enough data is available to select the
+    /// best packet.
+    fn pack(&mut self, all: bool) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        let mut start = 0;
+        while self.pending.len() - start >= MAX_PACKET_SIZE || (all && start < self.pending.len()) {
+            let data = &self.pending[start..];
+
+            let repeat = repeat_len(data);
+            if repeat >= 3 {
+                self.packed.push((257 - repeat) as u8);
+                self.packed.push(data[0]);
+                start += repeat;
+                continue;
+            }
+
+            // The literal run ends at the next run of 3 identical bytes.
+            let mut count = 1;
+            while count < data.len().min(MAX_PACKET_SIZE) && repeat_len(&data[count..]) < 3 {
+                count += 1;
+            }
+            self.packed.push((count - 1) as u8);
+            self.packed.extend_from_slice(&data[..count]);
+            start += count;
+        }
+
+        self.pending.drain(..start);
+        self.inner.write_all(&self.packed)?;
+        self.packed.clear();
+        Ok(())
+    }
+}
+
+/// Returns the number of identical bytes at the start of the data, up to the size of a packet.
+fn repeat_len(data: &[u8]) -> usize {
+    match data.first() {
+        Some(first) => data
+            .iter()
+            .take(MAX_PACKET_SIZE)
+            .take_while(|byte| *byte == first)
+            .count(),
+        None => 0,
+    }
+}
+
+impl<W> std::io::Write for PackBitsWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let bytes_written = match self.row_size {
+            0 => buf.len(),
+            row_size => buf.len().min(row_size - self.row_len),
+        };
+        self.pending.extend_from_slice(&buf[..bytes_written]);
+        self.row_len += bytes_written;
+
+        let end_of_row = self.row_len == self.row_size && bytes_written != 0;
+        if end_of_row {
+            self.row_len = 0;
+        }
+        self.pack(end_of_row)?;
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use claims::*;
@@ -121,4 +253,71 @@ mod tests {
         assert_ok_eq!(reader.read_to_end(&mut output), 24);
         assert_eq!(output, unpacked_data);
     }
+
+    #[test]
+    fn encode_packbits() {
+        use std::io::Write;
+
+        let packed_data = b"\xFE\xAA\x02\x80\x00\x2A\xFD\xAA\x03\x80\x00\x2A\x22\xF7\xAA";
+        let unpacked_data = b"\xAA\xAA\xAA\x80\x00\x2A\xAA\xAA\xAA\xAA\x80\x00\x2A\x22\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA\xAA";
+
+        let mut writer = PackBitsWriter::new(Vec::new(), 0);
+        assert_ok!(writer.write_all(unpacked_data));
+        assert_ok_eq!(writer.finish(), packed_data);
+    }
+
+    #[test]
+    fn encode_packbits_by_rows() {
+        use std::io::Write;
+
+        let mut writer = PackBitsWriter::new(Vec::new(), 4);
+        assert_ok!(writer.write_all(&[0xAA; 6]));
+        assert_ok!(writer.write_all(&[0xAA, 0xBB]));
+        assert_ok_eq!(writer.finish(), b"\xFD\xAA\xFE\xAA\x00\xBB");
+    }
+
+    #[test]
+    fn round_trip() {
+        use std::io::{Read, Write};
+
+        for row_size in [0, 1, 7, 130, 1000] {
+            // Runs of random lengths, mixed with random bytes.
+            let mut data = Vec::new();
+            while data.len() < 10_000 {
+                let byte = fastrand::u8(..);
+                match fastrand::bool() {
+                    true => data.extend(std::iter::repeat_n(byte, fastrand::usize(1..300))),
+                    false => data.push(byte),
+                }
+            }
+            if let Some(nrows) = data.len().checked_div(row_size) {
+                data.truncate(nrows * row_size);
+            }
+
+            let mut writer = PackBitsWriter::new(Vec::new(), row_size);
+            let mut remaining = &data[..];
+            while !remaining.is_empty() {
+                let size = fastrand::usize(1..=remaining.len().min(500));
+                assert_ok!(writer.write_all(&remaining[..size]));
+                remaining = &remaining[size..];
+            }
+            let encoded = assert_ok!(writer.finish());
+
+            let mut reader = PackBitsReader::new(&encoded[..]);
+            let mut decoded = Vec::new();
+            assert_ok!(reader.read_to_end(&mut decoded));
+            assert_eq!(decoded, data, "Rows of {row_size} bytes");
+        }
+    }
+
+    #[test]
+    fn decode_truncated_packbits() {
+        use std::io::{Cursor, Read};
+
+        let packed_data = b"\xFE\xAA\x04\x80\x00";
+
+        let mut reader = PackBitsReader::new(Cursor::new(packed_data));
+        let mut output = Vec::new();
+        assert_err!(reader.read_to_end(&mut output));
+    }
 }
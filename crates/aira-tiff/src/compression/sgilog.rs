@@ -0,0 +1,161 @@
+/// SGI Log run-length decoder.
+///
+/// Each row of LogL and 32-bit LogLuv pixels is split into planes of bytes, from the most to the
+/// least significant byte, and each plane is run-length encoded. The decoded pixels are returned
+/// as big-endian integers of 2 or 4 bytes.
+///
+/// Unlike other compression algorithms, the width of the rows must be known to decode the data.
+#[derive(Debug)]
+pub struct SgiLogReader<R> {
+    inner: R,
+    /// The number of pixels of a row.
+    width: usize,
+    /// The number of bytes of a pixel.
+    bytesperpixel: usize,
+    /// The decoded row.
+    row: Vec<u8>,
+    /// The number of bytes of the decoded row already returned.
+    position: usize,
+}
+
+impl<R> SgiLogReader<R> {
+    /// Creates a new [`SgiLogReader`] from the given reader, for rows of `width` pixels of
+    /// `bytesperpixel` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytesperpixel` is neither 2 nor 4.
+    pub fn new(reader: R, width: usize, bytesperpixel: usize) -> Self
+    where
+        R: std::io::Read,
+    {
+        assert!(
+            matches!(bytesperpixel, 2 | 4),
+            "SGI Log pixels have 2 or 4 bytes"
+        );
+        Self {
+            inner: reader,
+            width,
+            bytesperpixel,
+            row: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl<R> SgiLogReader<R>
+where
+    R: std::io::Read,
+{
+    /// Decodes the next row, returns `false` at the end of the stream.
+    fn decode_row(&mut self) -> std::io::Result<bool> {
+        use aira_byteorder::ReadBytesExt;
+
+        self.row.clear();
+        self.row.resize(self.width * self.bytesperpixel, 0);
+        self.position = 0;
+
+        for plane in 0..self.bytesperpixel {
+            let mut bytes = self.row[plane..].iter_mut().step_by(self.bytesperpixel);
+            let mut remaining = self.width;
+            while remaining > 0 {
+                let control = match self.inner.read_u8() {
+                    Ok(control) => control,
+                    // The stream ends between rows.
+                    Err(err)
+                        if err.kind() == std::io::ErrorKind::UnexpectedEof
+                            && plane == 0
+                            && remaining == self.width =>
+                    {
+                        return Ok(false)
+                    }
+                    Err(err) => return Err(err),
+                };
+
+                let count = match control {
+                    128.. => {
+                        let count = control as usize - 126;
+                        let value = self.inner.read_u8()?;
+                        bytes.by_ref().take(count).for_each(|byte| *byte = value);
+                        count
+                    }
+                    _ => {
+                        let count = control as usize;
+                        for byte in bytes.by_ref().take(count) {
+                            *byte = self.inner.read_u8()?;
+                        }
+                        count
+                    }
+                };
+                if count > remaining {
+                    return Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "SGI Log run exceeds the row",
+                    ));
+                }
+                remaining -= count;
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+impl<R> std::io::Read for SgiLogReader<R>
+where
+    R: std::io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.width == 0 {
+            return Ok(0);
+        }
+        if self.position == self.row.len() && !self.decode_row()? {
+            return Ok(0);
+        }
+
+        let remaining = &self.row[self.position..];
+        let copied = remaining.len().min(buf.len());
+        buf[..copied].copy_from_slice(&remaining[..copied]);
+        self.position += copied;
+        Ok(copied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use claims::*;
+
+    use super::*;
+
+    #[test]
+    fn decode_sgilog() {
+        // Two rows of 3 pixels of 2 bytes, with runs and literals.
+        let encoded = b"\x81\x12\x03\x34\x56\x78\x02\x9a\xbc\x01\xde\x80\x01\x01\x03";
+        let mut reader = SgiLogReader::new(&encoded[..], 3, 2);
+        let mut decoded = Vec::new();
+        assert_ok_eq!(reader.read_to_end(&mut decoded), 12);
+        assert_eq!(decoded, b"\x12\x34\x12\x56\x12\x78\x9a\x01\xbc\x01\xde\x03");
+    }
+
+    #[test]
+    fn decode_sgilog_32bits() {
+        let encoded = b"\x01\x00\x01\x00\x01\x56\x01\xc2";
+        let mut reader = SgiLogReader::new(&encoded[..], 1, 4);
+        let mut decoded = Vec::new();
+        assert_ok_eq!(reader.read_to_end(&mut decoded), 4);
+        assert_eq!(decoded, b"\x00\x00\x56\xc2");
+    }
+
+    #[test]
+    fn decode_invalid_sgilog() {
+        // The run is longer than the row.
+        let mut reader = SgiLogReader::new(&b"\x82\x12\x02\x34\x56"[..], 3, 2);
+        assert_err!(reader.read_to_end(&mut Vec::new()));
+
+        // The row is truncated.
+        let mut reader = SgiLogReader::new(&b"\x81\x12\x03\x34\x56\x78\x02\x9a"[..], 3, 2);
+        assert_err!(reader.read_to_end(&mut Vec::new()));
+    }
+}
@@ -29,12 +29,18 @@
 //!
 //! [typestate]: https://cliffle.com/blog/rust-typestate/
 
-use crate::{endian::sealed::EndianReader, ByteOrder, DType, Error, Ratio, Tag, Version};
+use crate::{
+    endian::sealed::EndianReader,
+    error::ErrorContext,
+    ByteOrder, DType, EntryPart, Error, Location, Ratio, Tag, Version,
+};
 
 /// TIFF image raw decoder.
 pub struct Decoder<R> {
     reader: EndianReader<R>,
     version: Version,
+    /// The length of the stream, it is computed on demand.
+    stream_len: Option<u64>,
 }
 
 impl<R: std::fmt::Debug> std::fmt::Debug for Decoder<R> {
@@ -71,7 +77,11 @@ impl<R> Decoder<R> {
             }
         }
 
-        Ok(Self { reader, version })
+        Ok(Self {
+            reader,
+            version,
+            stream_len: None,
+        })
     }
 
     /// Get the byte order of the TIFF file.
@@ -91,6 +101,25 @@ impl<R> Decoder<R> {
         self.reader.into_inner()
     }
 
+    /// Returns the length of the underlying stream, the value is computed only once.
+    pub(crate) fn stream_len(&mut self) -> Result<u64, Error>
+    where
+        R: std::io::Seek,
+    {
+        use std::io::Seek;
+
+        if let Some(stream_len) = self.stream_len {
+            return Ok(stream_len);
+        }
+
+        let position = self.reader.stream_position()?;
+        let stream_len = self.reader.seek(std::io::SeekFrom::End(0))?;
+        self.reader.seek(std::io::SeekFrom::Start(position))?;
+
+        self.stream_len = Some(stream_len);
+        Ok(stream_len)
+    }
+
     /// Get an iterator over the directories of the TIFF image.
     pub fn directories(&mut self) -> Directories<'_, R> {
         let next_offset_loc = match self.version {
@@ -100,6 +129,7 @@ impl<R> Decoder<R> {
         Directories {
             decoder: self,
             next_offset_loc: Some(next_offset_loc),
+            index: 0,
         }
     }
 }
@@ -110,6 +140,8 @@ pub struct Directories<'tiff, R> {
     decoder: &'tiff mut Decoder<R>,
     /// The position of the next offset value.
     next_offset_loc: Option<u64>,
+    /// The index of the next directory.
+    index: usize,
 }
 
 impl<R> Directories<'_, R> {
@@ -118,26 +150,79 @@ impl<R> Directories<'_, R> {
     where
         R: std::io::Read + std::io::Seek,
     {
-        use std::io::Seek;
-
         let Some(next_offset_loc) = self.next_offset_loc else {
             return Ok(None);
         };
 
+        let index = self.index;
+
         // Move to `next_offset` and read the offset of the current directory.
-        self.decoder
-            .reader
-            .seek(std::io::SeekFrom::Start(next_offset_loc))?;
-        let offset = match self.decoder.version {
-            Version::Classic => self.decoder.reader.read_u32()? as u64,
-            Version::BigTiff => self.decoder.reader.read_u64()?,
-        };
+        let offset = self.read_offset(next_offset_loc).with_location(|| Location {
+            offset: next_offset_loc,
+            directory: Some(index),
+            entry: None,
+            tag: None,
+            part: None,
+        })?;
 
         if offset == 0 {
             self.next_offset_loc = None;
             return Ok(None);
         }
 
+        let (entries_count, next_offset_loc) =
+            self.read_directory(offset).with_location(|| Location {
+                offset,
+                directory: Some(index),
+                entry: None,
+                tag: None,
+                part: None,
+            })?;
+        self.next_offset_loc = Some(next_offset_loc);
+        self.index += 1;
+
+        let next_offset = self.read_offset(next_offset_loc).with_location(|| Location {
+            offset: next_offset_loc,
+            directory: Some(index),
+            entry: None,
+            tag: None,
+            part: None,
+        })?;
+
+        Ok(Some(Directory {
+            decoder: self.decoder,
+            entries_count,
+            offset,
+            next_offset,
+            index,
+        }))
+    }
+
+    /// Reads the offset of a directory stored at the given position.
+    fn read_offset(&mut self, loc: u64) -> Result<u64, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        use std::io::Seek;
+
+        self.decoder.reader.seek(std::io::SeekFrom::Start(loc))?;
+        let offset = match self.decoder.version {
+            Version::Classic => self.decoder.reader.read_u32()? as u64,
+            Version::BigTiff => self.decoder.reader.read_u64()?,
+        };
+        Ok(offset)
+    }
+
+    /// Reads the number of entries of the directory at the given offset, and computes the
+    /// position of the offset of the next directory.
+    fn read_directory(&mut self, offset: u64) -> Result<(u64, u64), Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        use std::io::Seek;
+
+        let stream_len = self.decoder.stream_len()?;
+
         // Move to the beginning of the next directory.
         self.decoder.reader.seek(std::io::SeekFrom::Start(offset))?;
 
@@ -150,27 +235,21 @@ impl<R> Directories<'_, R> {
             Version::Classic => 12,
             Version::BigTiff => 20,
         };
+        let next_offset_size = match self.decoder.version {
+            Version::Classic => 4,
+            Version::BigTiff => 8,
+        };
         let next_offset_loc = entries_count
             .checked_mul(entry_size)
-            .unwrap()
-            .checked_add(first_entry_offset)
-            .unwrap();
-        self.next_offset_loc = Some(next_offset_loc);
-
-        self.decoder
-            .reader
-            .seek(std::io::SeekFrom::Start(next_offset_loc))?;
-        let next_offset = match self.decoder.version {
-            Version::Classic => self.decoder.reader.read_u32()? as u64,
-            Version::BigTiff => self.decoder.reader.read_u64()?,
-        };
-
-        Ok(Some(Directory {
-            decoder: self.decoder,
-            entries_count,
-            offset,
-            next_offset,
-        }))
+            .and_then(|entries_size| entries_size.checked_add(first_entry_offset))
+            .filter(|&next_offset_loc| {
+                next_offset_loc
+                    .checked_add(next_offset_size)
+                    .is_some_and(|end| end <= stream_len)
+            })
+            .ok_or_else(|| Error::from_static_str("Directory exceeds the end of file"))?;
+
+        Ok((entries_count, next_offset_loc))
     }
 }
 
@@ -184,6 +263,8 @@ pub struct Directory<'tiff, R> {
     pub offset: u64,
     /// The offset of the next directory.
     pub next_offset: u64,
+    /// The index of the directory in the TIFF image.
+    pub index: usize,
 }
 
 impl<'tiff, R> Directory<'tiff, R> {
@@ -193,6 +274,7 @@ impl<'tiff, R> Directory<'tiff, R> {
             decoder,
             entries_count,
             offset,
+            index,
             ..
         } = self;
 
@@ -207,6 +289,27 @@ impl<'tiff, R> Directory<'tiff, R> {
             decoder,
             entries_count,
             entry_offset,
+            directory: index,
+            index: 0,
+        }
+    }
+
+    /// Returns the length of the file.
+    pub(crate) fn stream_len(&mut self) -> Result<u64, Error>
+    where
+        R: std::io::Seek,
+    {
+        self.decoder.stream_len()
+    }
+
+    /// Returns the location of the directory in the file.
+    pub(crate) fn location(&self) -> Location {
+        Location {
+            offset: self.offset,
+            directory: Some(self.index),
+            entry: None,
+            tag: None,
+            part: None,
         }
     }
 }
@@ -219,6 +322,10 @@ pub struct Entries<'tiff, R> {
     entries_count: u64,
     /// The offset of the entry pointed by the iterator.
     entry_offset: u64,
+    /// The index of the directory.
+    directory: usize,
+    /// The index of the entry pointed by the iterator.
+    index: u64,
 }
 
 impl<R> Entries<'_, R> {
@@ -227,18 +334,63 @@ impl<R> Entries<'_, R> {
     where
         R: std::io::Read + std::io::Seek,
     {
-        use std::io::Seek;
-
         if self.entries_count == 0 {
             return Ok(None);
         }
 
+        let header_offset = self.entry_offset;
+        let index = self.index;
+        let mut location = Location {
+            offset: header_offset,
+            directory: Some(self.directory),
+            entry: Some(index),
+            tag: None,
+            part: Some(EntryPart::Header),
+        };
+
+        // Update the iterator, an invalid entry is skipped by the next call.
+        self.entries_count = self.entries_count.checked_sub(1).unwrap();
+        let entry_size = match self.decoder.version {
+            Version::Classic => 12,
+            Version::BigTiff => 20,
+        };
+        self.entry_offset = self.entry_offset.checked_add(entry_size).unwrap();
+        self.index += 1;
+
+        let (tag, dtype, count, offset) = self
+            .read_entry(header_offset, &mut location)
+            .with_location(|| location)?;
+
+        Ok(Some(Entry {
+            decoder: self.decoder,
+            tag,
+            dtype,
+            count,
+            offset,
+            header_offset,
+            directory: self.directory,
+            index,
+        }))
+    }
+
+    /// Reads the entry at the given offset, the location is updated as the entry is read.
+    fn read_entry(
+        &mut self,
+        header_offset: u64,
+        location: &mut Location,
+    ) -> Result<(Tag, DType, u64, u64), Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        use std::io::Seek;
+
         self.decoder
             .reader
-            .seek(std::io::SeekFrom::Start(self.entry_offset))?;
+            .seek(std::io::SeekFrom::Start(header_offset))?;
 
         let tag = self.decoder.reader.read_u16()?;
         let tag = Tag(tag);
+        location.tag = Some(tag);
 
         let dtype = self.decoder.reader.read_u16()?;
         let dtype = DType::try_from_u16(dtype)?;
@@ -248,7 +400,10 @@ impl<R> Entries<'_, R> {
             Version::BigTiff => self.decoder.reader.read_u64()?,
         };
 
-        let data_size = dtype.size().checked_mul(count).unwrap();
+        let data_size = dtype
+            .size()
+            .checked_mul(count)
+            .ok_or_else(|| Error::limit_exceeded("Entry data size overflows"))?;
         let max_data_size = match self.decoder.version {
             Version::Classic => 4,
             Version::BigTiff => 8,
@@ -259,27 +414,24 @@ impl<R> Entries<'_, R> {
             self.decoder.reader.stream_position()?
         } else {
             // The data is stored in a separate offset.
-            match self.decoder.version {
+            let offset = match self.decoder.version {
                 Version::Classic => self.decoder.reader.read_u32()? as u64,
                 Version::BigTiff => self.decoder.reader.read_u64()?,
+            };
+
+            let stream_len = self.decoder.stream_len()?;
+            if offset
+                .checked_add(data_size)
+                .is_none_or(|end| end > stream_len)
+            {
+                location.offset = offset;
+                location.part = Some(EntryPart::Payload);
+                return Err(Error::from_static_str("Entry data exceeds the end of file"));
             }
+            offset
         };
 
-        // Update the iterator
-        self.entries_count = self.entries_count.checked_sub(1).unwrap();
-        let entry_size = match self.decoder.version {
-            Version::Classic => 12,
-            Version::BigTiff => 20,
-        };
-        self.entry_offset = self.entry_offset.checked_add(entry_size).unwrap();
-
-        Ok(Some(Entry {
-            decoder: self.decoder,
-            tag,
-            dtype,
-            count,
-            offset,
-        }))
+        Ok((tag, dtype, count, offset))
     }
 }
 
@@ -293,10 +445,36 @@ pub struct Entry<'tiff, R> {
     pub dtype: DType,
     /// The number of elements in the entry.
     pub count: u64,
+    /// The offset of the entry data.
     offset: u64,
+    /// The offset of the entry header.
+    header_offset: u64,
+    /// The index of the directory.
+    directory: usize,
+    /// The index of the entry within the directory.
+    index: u64,
 }
 
 impl<R> Entry<'_, R> {
+    /// Returns the byte order of the entry data.
+    pub(crate) fn byteorder(&self) -> ByteOrder {
+        self.decoder.byteorder()
+    }
+
+    /// Returns the location of a part of the entry in the file.
+    pub(crate) fn location(&self, part: EntryPart) -> Location {
+        Location {
+            offset: match part {
+                EntryPart::Header => self.header_offset,
+                EntryPart::Payload => self.offset,
+            },
+            directory: Some(self.directory),
+            entry: Some(self.index),
+            tag: Some(self.tag),
+            part: Some(part),
+        }
+    }
+
     /// Decode a single value from the entry.
     pub fn decode<T>(&mut self) -> Result<T, Error>
     where
@@ -308,7 +486,8 @@ impl<R> Entry<'_, R> {
         if self.count != 1 {
             return Err(Error::from_static_str(
                 "Cannot decode entry with count not equal to 1",
-            ));
+            ))
+            .with_location(|| self.location(EntryPart::Header));
         }
 
         if !T::is_dtype_good(self.dtype) {
@@ -316,13 +495,16 @@ impl<R> Entry<'_, R> {
                 "A value of type {} cannot be decoded from a TIFF entry with datatype {:?}",
                 std::any::type_name::<T>(),
                 self.dtype
-            )));
+            )))
+            .with_location(|| self.location(EntryPart::Header));
         }
 
         self.decoder
             .reader
-            .seek(std::io::SeekFrom::Start(self.offset))?;
-        T::decode(&mut self.decoder.reader)
+            .seek(std::io::SeekFrom::Start(self.offset))
+            .map_err(Error::from)
+            .and_then(|_| T::decode(&mut self.decoder.reader))
+            .with_location(|| self.location(EntryPart::Payload))
     }
 
     /// Decode values into the buffer.
@@ -338,7 +520,8 @@ impl<R> Entry<'_, R> {
                 "Cannot decode entry with count {} into a buffer of length {}",
                 self.count,
                 buffer.len()
-            )));
+            )))
+            .with_location(|| self.location(EntryPart::Header));
         }
 
         if !T::is_dtype_good(self.dtype) {
@@ -346,13 +529,16 @@ impl<R> Entry<'_, R> {
                 "A value of type {} cannot be decoded from a TIFF entry with datatype {:?}",
                 std::any::type_name::<T>(),
                 self.dtype
-            )));
+            )))
+            .with_location(|| self.location(EntryPart::Header));
         }
 
         self.decoder
             .reader
-            .seek(std::io::SeekFrom::Start(self.offset))?;
-        T::decode_into(&mut self.decoder.reader, buffer)
+            .seek(std::io::SeekFrom::Start(self.offset))
+            .map_err(Error::from)
+            .and_then(|_| T::decode_into(&mut self.decoder.reader, buffer))
+            .with_location(|| self.location(EntryPart::Payload))
     }
 
     /// Decode values into an uninitialized buffer, returning the initialized slice.
@@ -371,8 +557,10 @@ impl<R> Entry<'_, R> {
 
         self.decoder
             .reader
-            .seek(std::io::SeekFrom::Start(self.offset))?;
-        T::decode_into(&mut self.decoder.reader, buffer)
+            .seek(std::io::SeekFrom::Start(self.offset))
+            .map_err(Error::from)
+            .and_then(|_| T::decode_into(&mut self.decoder.reader, buffer))
+            .with_location(|| self.location(EntryPart::Payload))
     }
 }
 
@@ -1,6 +1,6 @@
 //! The value of an entry in a TIFF directory.
 
-use crate::{decoder, DType, Error, Ratio};
+use crate::{decoder, error::ErrorContext, DType, EntryPart, Error, Ratio};
 
 /// An entry in a TIFF directory.
 #[derive(Clone, Debug)]
@@ -56,6 +56,7 @@ impl Entry {
             }};
         }
 
+        let location = entry.location(EntryPart::Payload);
         let entry = match entry.dtype {
             DType::Byte => Entry::U8(decode_vec!(entry)),
             DType::Short => Entry::U16(decode_vec!(entry)),
@@ -73,9 +74,11 @@ impl Entry {
             DType::Ascii => {
                 let bytes = decode_vec!(entry);
                 let value = std::ffi::CStr::from_bytes_with_nul(&bytes)
-                    .map_err(|err| Error::from_args(format_args!("Invalid string: {err}")))?
+                    .map_err(|err| Error::from_args(format_args!("Invalid string: {err}")))
+                    .with_location(|| location)?
                     .to_str()
-                    .map_err(|err| Error::from_args(format_args!("Invalid UTF-8 stirng: {err}")))?
+                    .map_err(|err| Error::from_args(format_args!("Invalid UTF-8 stirng: {err}")))
+                    .with_location(|| location)?
                     .to_owned();
                 Entry::Ascii(value)
             }
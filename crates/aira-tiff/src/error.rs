@@ -5,22 +5,86 @@ use crate::{
     endian::InvalidSignature,
     metadata::{MissingRequiredTag, UnexpectedDType},
     version::InvalidVersion,
+    Compression, DType, Interpretation, Predictor, Tag,
 };
 
 /// An error that can occur in this crate.
+///
+/// The category of the error is given by [`Error::kind`]. The context attached to the error is
+/// reachable through [`std::error::Error::source`]. The alternate format `{:#}` displays the whole
+/// chain of causes.
 #[derive(Clone)]
 pub struct Error {
     inner: Arc<ErrorInner>,
 }
 
 struct ErrorInner {
-    kind: ErrorKind,
+    repr: Repr,
     cause: Option<Error>,
 }
 
-/// The underlying kind of [`Error`].
+/// The category of an [`Error`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An error that occurred while reading or writing.
+    Io(std::io::ErrorKind),
+    /// The image is corrupt or does not comply with the format.
+    Format,
+    /// The image uses a feature that is not supported.
+    Unsupported(Unsupported),
+    /// A value exceeds a limit of the implementation.
+    LimitExceeded,
+    /// A required tag is missing.
+    MissingRequiredTag(Tag),
+    /// An unexpected datatype was encountered.
+    UnexpectedDType(DType),
+}
+
+/// A feature of the image that is not supported.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Unsupported {
+    /// The compression algorithm is not supported.
+    Compression(Compression),
+    /// The predictor scheme is not supported.
+    Predictor(Predictor),
+    /// The size in bytes of a sample is not supported.
+    SampleSize(u16),
+    /// The size in bits of a sample is not supported.
+    BitsPerSample(u16),
+    /// The color space of the image data is not supported.
+    Interpretation(Interpretation),
+}
+
+/// The location in the file where an [`Error`] occurred.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Location {
+    /// The offset in bytes from the beginning of the file.
+    pub offset: u64,
+    /// The index of the directory, if known.
+    pub directory: Option<usize>,
+    /// The index of the entry within the directory, if known.
+    pub entry: Option<u64>,
+    /// The tag of the entry, if known.
+    pub tag: Option<Tag>,
+    /// The part of the entry, if the error occurred within an entry.
+    pub part: Option<EntryPart>,
+}
+
+/// The part of a directory entry.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EntryPart {
+    /// The entry itself, made of tag, datatype, count and value or offset.
+    Header,
+    /// The data of the entry, either stored in the header or at a separate offset.
+    Payload,
+}
+
+/// The underlying representation of [`Error`].
 #[derive(Debug)]
-enum ErrorKind {
+enum Repr {
     /// An error that is constructed from anything that implements [`std::fmt::Display`].
     AdHoc(Box<str>),
     /// An error that occurred while reading or writing.
@@ -35,32 +99,75 @@ enum ErrorKind {
     UnexpectedDType(UnexpectedDType),
     /// A required tag is missing.
     MissingRequiredTag(MissingRequiredTag),
+    /// A feature of the image is not supported.
+    Unsupported(Unsupported),
+    /// A value exceeds a limit of the implementation.
+    LimitExceeded(&'static str),
+    /// The location where the cause occurred.
+    Location(Location),
 }
 
 impl Error {
     /// Constructs a new [`Error`] value from [`std::fmt::Arguments`].
     #[inline(always)]
     pub(crate) fn from_args(args: std::fmt::Arguments<'_>) -> Self {
-        Error::from(ErrorKind::AdHoc(args.to_string().into_boxed_str()))
+        Error::from(Repr::AdHoc(args.to_string().into_boxed_str()))
     }
 
     /// Constructs a new [`Error`] value from a `&'static str`.
     #[inline(always)]
     pub(crate) fn from_static_str(msg: &'static str) -> Self {
-        Error::from(ErrorKind::AdHoc(msg.into()))
+        Error::from(Repr::AdHoc(msg.into()))
+    }
+
+    /// Constructs a new [`Error`] value of kind [`ErrorKind::LimitExceeded`].
+    #[inline(always)]
+    pub(crate) fn limit_exceeded(msg: &'static str) -> Self {
+        Error::from(Repr::LimitExceeded(msg))
+    }
+
+    /// Returns the category of the error.
+    ///
+    /// An error that only adds context to its cause has the same kind as its cause.
+    pub fn kind(&self) -> ErrorKind {
+        let mut err = self;
+        loop {
+            match (&err.inner.repr, &err.inner.cause) {
+                (Repr::AdHoc(_) | Repr::Location(_), Some(cause)) => err = cause,
+                (repr, _) => return repr.kind(),
+            }
+        }
+    }
+
+    /// Returns the location in the file where the error occurred, if known.
+    pub fn location(&self) -> Option<Location> {
+        let mut err = self;
+        loop {
+            if let Repr::Location(location) = err.inner.repr {
+                return Some(location);
+            }
+            err = err.inner.cause.as_ref()?;
+        }
     }
 }
 
-impl std::error::Error for Error {}
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner
+            .cause
+            .as_ref()
+            .map(|err| err as &(dyn std::error::Error + 'static))
+    }
+}
 
 impl std::fmt::Debug for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         if !f.alternate() {
-            return std::fmt::Display::fmt(self, f);
+            return write!(f, "{self:#}");
         }
 
         f.debug_struct("Error")
-            .field("kind", &self.inner.kind)
+            .field("repr", &self.inner.repr)
             .field("cause", &self.inner.cause)
             .finish()
     }
@@ -68,9 +175,13 @@ impl std::fmt::Debug for Error {
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !f.alternate() {
+            return write!(f, "{}", self.inner.repr);
+        }
+
         let mut err = self;
         loop {
-            write!(f, "{}", err.inner.kind)?;
+            write!(f, "{}", err.inner.repr)?;
             err = match &err.inner.cause {
                 Some(err) => err,
                 None => break,
@@ -81,24 +192,82 @@ impl std::fmt::Display for Error {
     }
 }
 
-impl std::fmt::Display for ErrorKind {
+impl Repr {
+    /// Returns the category of the error representation.
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Repr::AdHoc(_) => ErrorKind::Format,
+            Repr::Io(err) => ErrorKind::Io(err.kind()),
+            Repr::InvalidSignature(_) => ErrorKind::Format,
+            Repr::InvalidVersion(_) => ErrorKind::Format,
+            Repr::UnknownDType(_) => ErrorKind::Format,
+            Repr::UnexpectedDType(err) => ErrorKind::UnexpectedDType(err.0),
+            Repr::MissingRequiredTag(err) => ErrorKind::MissingRequiredTag(err.0),
+            Repr::Unsupported(unsupported) => ErrorKind::Unsupported(*unsupported),
+            Repr::LimitExceeded(_) => ErrorKind::LimitExceeded,
+            Repr::Location(_) => ErrorKind::Format,
+        }
+    }
+}
+
+impl std::fmt::Display for Repr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ErrorKind::AdHoc(msg) => write!(f, "{msg}"),
-            ErrorKind::Io(err) => err.fmt(f),
-            ErrorKind::InvalidSignature(err) => err.fmt(f),
-            ErrorKind::InvalidVersion(err) => err.fmt(f),
-            ErrorKind::UnknownDType(err) => err.fmt(f),
-            ErrorKind::UnexpectedDType(err) => err.fmt(f),
-            ErrorKind::MissingRequiredTag(err) => err.fmt(f),
+            Repr::AdHoc(msg) => write!(f, "{msg}"),
+            Repr::Io(err) => err.fmt(f),
+            Repr::InvalidSignature(err) => err.fmt(f),
+            Repr::InvalidVersion(err) => err.fmt(f),
+            Repr::UnknownDType(err) => err.fmt(f),
+            Repr::UnexpectedDType(err) => err.fmt(f),
+            Repr::MissingRequiredTag(err) => err.fmt(f),
+            Repr::Unsupported(unsupported) => unsupported.fmt(f),
+            Repr::LimitExceeded(msg) => write!(f, "{msg}"),
+            Repr::Location(location) => location.fmt(f),
         }
     }
 }
 
-impl From<ErrorKind> for Error {
-    fn from(kind: ErrorKind) -> Self {
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(directory) = self.directory {
+            write!(f, "IFD {directory}, ")?;
+        }
+        if let Some(entry) = self.entry {
+            write!(f, "entry {entry}, ")?;
+        }
+        if let Some(tag) = self.tag {
+            write!(f, "{tag:?} ")?;
+        }
+        match self.part {
+            Some(EntryPart::Header) => write!(f, "header ")?,
+            Some(EntryPart::Payload) => write!(f, "payload ")?,
+            None => {}
+        }
+        let offset = self.offset;
+        write!(f, "at offset {offset} (0x{offset:x})")
+    }
+}
+
+impl std::fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Unsupported::Compression(compression) => {
+                write!(f, "Unsupported compression algorithm: {compression:?}")
+            }
+            Unsupported::Predictor(predictor) => write!(f, "Unsupported predictor: {predictor:?}"),
+            Unsupported::SampleSize(size) => write!(f, "Unsupported sample size: {size} bytes"),
+            Unsupported::BitsPerSample(bits) => write!(f, "Unsupported sample size: {bits} bits"),
+            Unsupported::Interpretation(interpretation) => {
+                write!(f, "Unsupported photometric interpretation: {interpretation:?}")
+            }
+        }
+    }
+}
+
+impl From<Repr> for Error {
+    fn from(repr: Repr) -> Self {
         Error {
-            inner: Arc::new(ErrorInner { kind, cause: None }),
+            inner: Arc::new(ErrorInner { repr, cause: None }),
         }
     }
 }
@@ -106,42 +275,49 @@ impl From<ErrorKind> for Error {
 impl From<std::io::Error> for Error {
     #[inline(always)]
     fn from(err: std::io::Error) -> Self {
-        Error::from(ErrorKind::Io(err))
+        Error::from(Repr::Io(err))
     }
 }
 
 impl From<InvalidSignature> for Error {
     #[inline(always)]
     fn from(err: InvalidSignature) -> Self {
-        Error::from(ErrorKind::InvalidSignature(err))
+        Error::from(Repr::InvalidSignature(err))
     }
 }
 
 impl From<InvalidVersion> for Error {
     #[inline(always)]
     fn from(err: InvalidVersion) -> Self {
-        Error::from(ErrorKind::InvalidVersion(err))
+        Error::from(Repr::InvalidVersion(err))
     }
 }
 
 impl From<UnknownDType> for Error {
     #[inline(always)]
     fn from(err: UnknownDType) -> Self {
-        Error::from(ErrorKind::UnknownDType(err))
+        Error::from(Repr::UnknownDType(err))
     }
 }
 
 impl From<UnexpectedDType> for Error {
     #[inline(always)]
     fn from(err: UnexpectedDType) -> Self {
-        Error::from(ErrorKind::UnexpectedDType(err))
+        Error::from(Repr::UnexpectedDType(err))
     }
 }
 
 impl From<MissingRequiredTag> for Error {
     #[inline(always)]
     fn from(err: MissingRequiredTag) -> Self {
-        Error::from(ErrorKind::MissingRequiredTag(err))
+        Error::from(Repr::MissingRequiredTag(err))
+    }
+}
+
+impl From<Unsupported> for Error {
+    #[inline(always)]
+    fn from(unsupported: Unsupported) -> Self {
+        Error::from(Repr::Unsupported(unsupported))
     }
 }
 
@@ -174,6 +350,10 @@ impl IntoError for String {
 /// Provides additional context for an error.
 pub(crate) trait ErrorContext {
     fn with_context<E: IntoError>(self, context: impl FnOnce() -> E) -> Self;
+
+    /// Attaches the location where the error occurred, unless a more precise location is already
+    /// known.
+    fn with_location(self, location: impl FnOnce() -> Location) -> Self;
 }
 
 impl ErrorContext for Error {
@@ -182,10 +362,72 @@ impl ErrorContext for Error {
         Arc::get_mut(&mut err.inner).unwrap().cause = Some(self);
         err
     }
+
+    fn with_location(self, location: impl FnOnce() -> Location) -> Self {
+        if self.location().is_some() {
+            return self;
+        }
+
+        Error {
+            inner: Arc::new(ErrorInner {
+                repr: Repr::Location(location()),
+                cause: Some(self),
+            }),
+        }
+    }
 }
 
 impl<T> ErrorContext for Result<T, Error> {
     fn with_context<E: IntoError>(self, context: impl FnOnce() -> E) -> Self {
         self.map_err(|err| err.with_context(context))
     }
+
+    fn with_location(self, location: impl FnOnce() -> Location) -> Self {
+        self.map_err(|err| err.with_location(location))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::error::Error as _;
+
+    use claims::{assert_none, assert_some};
+
+    use super::*;
+
+    #[test]
+    fn context_inherits_kind_of_cause() {
+        let err = Error::from(Unsupported::Compression(Compression::LZW))
+            .with_context(|| "Cannot decode chunk")
+            .with_context(|| "Cannot read image");
+        assert_eq!(
+            err.kind(),
+            ErrorKind::Unsupported(Unsupported::Compression(Compression::LZW))
+        );
+
+        let err = Error::from(std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+            .with_context(|| "Cannot read entry");
+        assert_eq!(err.kind(), ErrorKind::Io(std::io::ErrorKind::UnexpectedEof));
+
+        let err = Error::from_static_str("Image width cannot be zero");
+        assert_eq!(err.kind(), ErrorKind::Format);
+
+        let err = Error::from(MissingRequiredTag(Tag::IMAGE_WIDTH));
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredTag(Tag::IMAGE_WIDTH));
+    }
+
+    #[test]
+    fn cause_chain_is_reachable_from_source() {
+        let err = Error::from_static_str("Rows per strip cannot be zero")
+            .with_context(|| "Invalid metadata");
+        assert_eq!(err.to_string(), "Invalid metadata");
+        assert_eq!(
+            format!("{err:#}"),
+            "Invalid metadata: Rows per strip cannot be zero"
+        );
+
+        let source = assert_some!(err.source());
+        assert_eq!(source.to_string(), "Rows per strip cannot be zero");
+        assert_none!(source.source());
+    }
 }
@@ -0,0 +1,36 @@
+/// The meaning of an extra sample of the pixels.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct ExtraSample(pub u16);
+
+impl Default for ExtraSample {
+    fn default() -> Self {
+        Self::UNSPECIFIED
+    }
+}
+
+impl std::fmt::Debug for ExtraSample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name(), self.0)
+    }
+}
+
+impl ExtraSample {
+    /// The data is not specified.
+    pub const UNSPECIFIED: Self = Self(0);
+    /// The opacity of the pixel, the color samples are premultiplied by the opacity.
+    pub const ASSOCIATED_ALPHA: Self = Self(1);
+    /// The opacity of the pixel, the color samples are not premultiplied.
+    pub const UNASSOCIATED_ALPHA: Self = Self(2);
+}
+
+impl ExtraSample {
+    /// Returns the name of the tag if known, otherwise "Unknown" is returned.
+    fn name(&self) -> &'static str {
+        match self.0 {
+            0 => "Unspecified",
+            1 => "AssociatedAlpha",
+            2 => "UnassociatedAlpha",
+            _ => "Unknown",
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! Index of all the images of a TIFF file.
+//!
+//! The [`TiffFile`] walks through all the directories of a file once, decodes their [`Metadata`]
+//! and keeps them, so that any image can be accessed by index afterwards. The images are
+//! classified according to their [`SubfileType`](crate::SubfileType):
+//!
+//! * [`ImageKind::Main`] and [`ImageKind::Page`] are full resolution images, the pages of the
+//!   file.
+//! * [`ImageKind::Overview`] are reduced resolution versions of the preceding full resolution
+//!   image.
+//! * [`ImageKind::Mask`] are transparency masks of the preceding full resolution image or of its
+//!   overviews.
+//!
+//! The pixels are read from the same reader with a [`PixelReader`](crate::reader::PixelReader).
+//!
+//! The full resolution image and its overviews form the [`Level`]s of a pyramid, the best level to
+//! render an image at a lower resolution is selected with [`TiffFile::select_level`] or
+//! [`TiffFile::select_level_for_size`].
+//!
+//! ```
+//! use aira_tiff::{file::TiffFile, reader::PixelReader};
+//!
+//! let file = std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif")?;
+//! let mut reader = std::io::BufReader::new(file);
+//! let tiff = TiffFile::new(&mut reader)?;
+//!
+//! for page in tiff.pages() {
+//!     let metadata = tiff.metadata(page).unwrap();
+//!     let pixels = PixelReader::new(metadata, tiff.byteorder())?;
+//!     let mut rgb = vec![0u8; pixels.image_len(3)];
+//!     pixels.read_image(&mut reader, &mut rgb)?;
+//! }
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{
+    error::ErrorContext, metadata::MetadataBuilder, reader::Window, ByteOrder, Decoder, Error,
+    Location, Metadata, Version,
+};
+
+/// All the images of a TIFF file, in the order of their directories.
+#[derive(Debug)]
+pub struct TiffFile {
+    byteorder: ByteOrder,
+    version: Version,
+    images: Vec<Image>,
+    /// The problems recovered while decoding in lenient mode.
+    warnings: Vec<Error>,
+}
+
+/// An image of a TIFF file, described by a directory.
+#[derive(Debug)]
+pub struct Image {
+    /// The offset of the directory in the file.
+    offset: u64,
+    kind: ImageKind,
+    /// The index of the full resolution image this image belongs to.
+    parent: Option<usize>,
+    metadata: Metadata,
+}
+
+/// The role of an image in a TIFF file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ImageKind {
+    /// A full resolution image without subfile type.
+    Main,
+    /// A full resolution image marked as a page of a multi-page file.
+    Page,
+    /// A reduced resolution version of a full resolution image.
+    Overview,
+    /// A transparency mask of a full resolution image or of one of its overviews.
+    Mask,
+}
+
+impl TiffFile {
+    /// Decodes the directories of the TIFF file read from `reader`.
+    ///
+    /// Use [`TiffFileBuilder`] to decode malformed files.
+    pub fn new<R>(reader: R) -> Result<Self, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        TiffFileBuilder::default().decode(reader)
+    }
+
+    /// Get the byte order of the TIFF file.
+    pub fn byteorder(&self) -> ByteOrder {
+        self.byteorder
+    }
+
+    /// Get the version of the TIFF file.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the number of images of the file.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns `true` if the file has no image.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// Returns all the images of the file, in the order of their directories.
+    pub fn images(&self) -> &[Image] {
+        &self.images
+    }
+
+    /// Returns the image at the given index, if any.
+    pub fn image(&self, index: usize) -> Option<&Image> {
+        self.images.get(index)
+    }
+
+    /// Returns the metadata of the image at the given index, if any.
+    pub fn metadata(&self, index: usize) -> Option<&Metadata> {
+        self.image(index).map(Image::metadata)
+    }
+
+    /// Returns the indices of the full resolution images, the pages of the file.
+    pub fn pages(&self) -> impl Iterator<Item = usize> + '_ {
+        self.indices(|image| matches!(image.kind, ImageKind::Main | ImageKind::Page))
+    }
+
+    /// Returns the indices of the overviews of the full resolution image at the given index.
+    pub fn overviews(&self, page: usize) -> impl Iterator<Item = usize> + '_ {
+        self.indices(move |image| image.kind == ImageKind::Overview && image.parent == Some(page))
+    }
+
+    /// Returns the indices of the masks of the full resolution image at the given index, and of
+    /// its overviews.
+    pub fn masks(&self, page: usize) -> impl Iterator<Item = usize> + '_ {
+        self.indices(move |image| image.kind == ImageKind::Mask && image.parent == Some(page))
+    }
+
+    /// Returns the index of the mask of the full resolution image or overview at the given index,
+    /// that is the mask of the same page with the same dimensions, if any.
+    pub fn mask(&self, index: usize) -> Option<usize> {
+        let image = self.image(index)?;
+        let page = match image.kind {
+            ImageKind::Main | ImageKind::Page => index,
+            ImageKind::Overview => image.parent?,
+            ImageKind::Mask => return None,
+        };
+        let dimensions = image.metadata.dimensions;
+        self.masks(page)
+            .find(|&mask| self.images[mask].metadata.dimensions == dimensions)
+    }
+
+    /// Returns the levels of the pyramid made of the full resolution image at the given index and
+    /// of its overviews, from the highest to the lowest resolution.
+    ///
+    /// Each level is paired with the mask of the same dimensions, if any. No level is returned if
+    /// the index is not the one of a full resolution image.
+    pub fn levels(&self, page: usize) -> Vec<Level> {
+        let Some(image) = self
+            .image(page)
+            .filter(|image| matches!(image.kind, ImageKind::Main | ImageKind::Page))
+        else {
+            return Vec::new();
+        };
+
+        let full = image.metadata.dimensions;
+        let mut levels = std::iter::once(page)
+            .chain(self.overviews(page))
+            .map(|index| {
+                let dimensions = self.images[index].metadata.dimensions;
+                Level {
+                    image: index,
+                    mask: self.mask(index),
+                    dimensions,
+                    full,
+                }
+            })
+            .collect::<Vec<_>>();
+        levels.sort_by_key(|level| std::cmp::Reverse(level.dimensions));
+        levels
+    }
+
+    /// Selects the level of the pyramid of the full resolution image at the given index, to read
+    /// its pixels downsampled by the given factor.
+    ///
+    /// The selected level is the one with the lowest resolution, which is still at least as high
+    /// as the requested one. A factor of 1 selects the full resolution image.
+    pub fn select_level(&self, page: usize, downsampling: f64) -> Option<Level> {
+        let (width, length) = self.metadata(page)?.dimensions;
+        let downsampling = downsampling.max(1.0);
+        let size = (
+            (width as f64 / downsampling).floor() as u32,
+            (length as f64 / downsampling).floor() as u32,
+        );
+        self.select_level_for_size(page, size)
+    }
+
+    /// Selects the level of the pyramid of the full resolution image at the given index, to read
+    /// the whole image in `size` pixels.
+    ///
+    /// The selected level is the smallest one, which is still at least as large as the requested
+    /// size.
+    pub fn select_level_for_size(&self, page: usize, (width, length): (u32, u32)) -> Option<Level> {
+        let levels = self.levels(page);
+        let first = levels.first().copied();
+        levels
+            .into_iter()
+            .rev()
+            .find(|level| level.dimensions.0 >= width && level.dimensions.1 >= length)
+            .or(first)
+    }
+
+    /// Returns the problems recovered while decoding in lenient mode.
+    ///
+    /// The warnings of each image are held by its [`Metadata`].
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
+
+    /// Returns the indices of the images matching the predicate.
+    fn indices<'a>(
+        &'a self,
+        predicate: impl Fn(&Image) -> bool + 'a,
+    ) -> impl Iterator<Item = usize> + 'a {
+        self.images
+            .iter()
+            .enumerate()
+            .filter(move |(_, image)| predicate(image))
+            .map(|(index, _)| index)
+    }
+}
+
+impl Image {
+    /// Returns the offset of the directory of the image in the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the role of the image in the file.
+    pub fn kind(&self) -> ImageKind {
+        self.kind
+    }
+
+    /// Returns the index of the full resolution image this overview or mask belongs to.
+    ///
+    /// Full resolution images, and overviews or masks preceding any of them, have no parent.
+    pub fn parent(&self) -> Option<usize> {
+        self.parent
+    }
+
+    /// Returns the metadata of the image.
+    pub fn metadata(&self) -> &Metadata {
+        &self.metadata
+    }
+}
+
+/// A level of the pyramid made of a full resolution image and of its overviews.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Level {
+    /// The index of the image, either the full resolution image or one of its overviews.
+    pub image: usize,
+    /// The index of the mask of the image, if any.
+    pub mask: Option<usize>,
+    /// The width and length of the image.
+    pub dimensions: (u32, u32),
+    /// The width and length of the full resolution image.
+    full: (u32, u32),
+}
+
+impl Level {
+    /// Returns the horizontal and vertical downsampling factors of the level, relative to the full
+    /// resolution image.
+    pub fn downsampling(&self) -> (f64, f64) {
+        (
+            self.full.0 as f64 / self.dimensions.0 as f64,
+            self.full.1 as f64 / self.dimensions.1 as f64,
+        )
+    }
+
+    /// Rescales a window of the full resolution image into the pixels of this level.
+    ///
+    /// The rescaled window covers at least the area of the given window, and at least a pixel if
+    /// the given window is not empty.
+    pub fn rescale(&self, window: Window) -> Window {
+        let (x, width) = rescale(
+            window.origin.0,
+            window.size.0,
+            self.full.0,
+            self.dimensions.0,
+        );
+        let (y, length) = rescale(
+            window.origin.1,
+            window.size.1,
+            self.full.1,
+            self.dimensions.1,
+        );
+        Window::new((x, y), (width, length))
+    }
+}
+
+/// Rescales the range of `size` pixels starting at `start` from `from` pixels to `to` pixels.
+fn rescale(start: u32, size: u32, from: u32, to: u32) -> (u32, u32) {
+    if from == 0 || to == 0 {
+        return (0, 0);
+    }
+
+    let (start, end, from, to) = (
+        start as u64,
+        start as u64 + size as u64,
+        from as u64,
+        to as u64,
+    );
+    let first = (start * to / from).min(to);
+    let last = (end * to).div_ceil(from).min(to);
+    match size {
+        0 => (first as u32, 0),
+        // A non-empty range covers at least a pixel.
+        _ if first == last => (first.min(to - 1) as u32, 1),
+        _ => (first as u32, (last - first) as u32),
+    }
+}
+
+impl ImageKind {
+    /// Returns the kind of an image with the given metadata.
+    fn classify(metadata: &Metadata) -> Self {
+        let subfile_type = metadata.subfile_type;
+        if subfile_type.is_mask() {
+            ImageKind::Mask
+        } else if subfile_type.is_reduced_image() {
+            ImageKind::Overview
+        } else if subfile_type.is_page() {
+            ImageKind::Page
+        } else {
+            ImageKind::Main
+        }
+    }
+}
+
+/// A builder of [`TiffFile`] to decode malformed files.
+///
+/// In lenient mode, the metadata of each image is decoded leniently, and the images which cannot
+/// be decoded are skipped. The directories following an invalid or already visited directory
+/// are ignored.
+///
+/// ```
+/// use aira_tiff::file::TiffFileBuilder;
+///
+/// let file = std::fs::File::open("tests/images/minisblack-2c-8b-alpha.tiff")?;
+/// let tiff = TiffFileBuilder::default().lenient(true).decode(file)?;
+/// assert!(tiff.warnings().is_empty());
+/// # Ok::<(), aira_tiff::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct TiffFileBuilder {
+    lenient: bool,
+    defer_chunks: bool,
+}
+
+impl TiffFileBuilder {
+    /// Enables or disables the lenient parsing of malformed files.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Enables or disables the deferred decoding of the strip/tile offsets and byte counts of the
+    /// images, see [`MetadataBuilder::defer_chunks`].
+    pub fn defer_chunks(mut self, defer: bool) -> Self {
+        self.defer_chunks = defer;
+        self
+    }
+
+    /// Decodes the directories of the TIFF file read from `reader`.
+    pub fn decode<R>(self, reader: R) -> Result<TiffFile, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        let lenient = self.lenient;
+        let mut decoder = Decoder::new(reader)?;
+        let byteorder = decoder.byteorder();
+        let version = decoder.version();
+
+        let mut images = Vec::<Image>::new();
+        let mut warnings = Vec::new();
+        let mut visited = HashSet::new();
+        let mut parent = None;
+        let mut directories = decoder.directories();
+        loop {
+            let directory = match directories.next_directory() {
+                Ok(Some(directory)) => directory,
+                Ok(None) => break,
+                Err(err) if lenient => {
+                    warnings.push(err);
+                    break;
+                }
+                Err(err) => return Err(err),
+            };
+
+            // The directories must not form a loop.
+            let offset = directory.offset;
+            if !visited.insert(offset) {
+                let location = Location {
+                    offset,
+                    directory: Some(directory.index),
+                    entry: None,
+                    tag: None,
+                    part: None,
+                };
+                let err = Error::from_static_str("Directory is already visited")
+                    .with_location(|| location);
+                match lenient {
+                    true => {
+                        warnings.push(err);
+                        break;
+                    }
+                    false => return Err(err),
+                }
+            }
+
+            let metadata = match MetadataBuilder::default()
+                .lenient(lenient)
+                .defer_chunks(self.defer_chunks)
+                .decode(directory)
+            {
+                Ok(metadata) => metadata,
+                Err(err) if lenient => {
+                    warnings.push(err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            let kind = ImageKind::classify(&metadata);
+            if matches!(kind, ImageKind::Main | ImageKind::Page) {
+                parent = Some(images.len());
+            }
+            images.push(Image {
+                offset,
+                kind,
+                parent: match kind {
+                    ImageKind::Main | ImageKind::Page => None,
+                    ImageKind::Overview | ImageKind::Mask => parent,
+                },
+                metadata,
+            });
+        }
+
+        Ok(TiffFile {
+            byteorder,
+            version,
+            images,
+            warnings,
+        })
+    }
+}
@@ -0,0 +1,33 @@
+/// The logical order of bits within a byte.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct FillOrder(pub u16);
+
+impl Default for FillOrder {
+    fn default() -> Self {
+        Self::MSB_TO_LSB
+    }
+}
+
+impl std::fmt::Debug for FillOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name(), self.0)
+    }
+}
+
+impl FillOrder {
+    /// Pixels with lower column values are stored in the higher-order bits of the byte.
+    pub const MSB_TO_LSB: Self = Self(1);
+    /// Pixels with lower column values are stored in the lower-order bits of the byte.
+    pub const LSB_TO_MSB: Self = Self(2);
+}
+
+impl FillOrder {
+    /// Returns the name of the tag if known, otherwise "Unknown" is returned.
+    fn name(&self) -> &'static str {
+        match self.0 {
+            1 => "MsbToLsb",
+            2 => "LsbToMsb",
+            _ => "Unknown",
+        }
+    }
+}
@@ -0,0 +1,336 @@
+//! Conversion of floating point samples narrower than 32 bits.
+//!
+//! Besides single and double precision samples, TIFF files may contain half precision samples
+//! (16 bits) and 24-bit samples. The latter is the format written by Photoshop, with 1 sign bit,
+//! 7 exponent bits and 16 mantissa bits. Like GDAL, both formats are widened to `f32`, the
+//! conversion is exact.
+//!
+//! When the `f16` feature is enabled, half precision samples can also be read as the primitive
+//! `f16` type.
+
+use crate::{error::ErrorContext, ByteOrder, Error, Unsupported};
+
+/// The size of the buffer used to read samples before their conversion.
+const BUFFER_SIZE: usize = 4096;
+
+/// Converts the bits of a half precision floating point number to `f32`.
+pub fn f16_to_f32(bits: u16) -> f32 {
+    let sign = (bits as u32 & 0x8000) << 16;
+    let exponent = (bits as u32 >> 10) & 0x1f;
+    let mantissa = bits as u32 & 0x3ff;
+
+    match exponent {
+        0 => {
+            // Zero or subnormal, the value is `mantissa * 2^-24`.
+            let value = mantissa as f32 * f32::from_bits((127 - 24) << 23);
+            f32::from_bits(sign | value.to_bits())
+        }
+        0x1f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 13)),
+        _ => f32::from_bits(sign | ((exponent + 127 - 15) << 23) | (mantissa << 13)),
+    }
+}
+
+/// Converts `value` to the bits of the nearest half precision floating point number.
+///
+/// The values exceeding the range of half precision numbers are converted to infinity.
+pub fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        // The NaN payload is truncated, the quiet bit keeps it non-zero.
+        let payload = match mantissa {
+            0 => 0,
+            _ => 0x200 | (mantissa >> 13) as u16,
+        };
+        return sign | 0x7c00 | payload;
+    }
+
+    let exponent = exponent - 127 + 15;
+    let (bits, shift) = match exponent {
+        0x1f.. => return sign | 0x7c00,
+        // Zero or subnormal, the value is `mantissa * 2^-24`.
+        ..-10 => return sign,
+        -10..=0 => (mantissa | 0x80_0000, (14 - exponent) as u32),
+        _ => (((exponent as u32) << 23) | mantissa, 13),
+    };
+
+    // Rounds to the nearest, ties to even. The carry may propagate to the exponent.
+    let half = 1 << (shift - 1);
+    let remainder = bits & ((1 << shift) - 1);
+    let mut rounded = bits >> shift;
+    if remainder > half || (remainder == half && rounded & 1 == 1) {
+        rounded += 1;
+    }
+    sign | rounded as u16
+}
+
+/// Converts the bits of a 24-bit floating point number to `f32`.
+///
+/// Only the 24 least significant bits are used.
+pub fn f24_to_f32(bits: u32) -> f32 {
+    let sign = (bits & 0x80_0000) << 8;
+    let exponent = (bits >> 16) & 0x7f;
+    let mantissa = bits & 0xffff;
+
+    match exponent {
+        0 => {
+            // Zero or subnormal, the value is `mantissa * 2^-78` and it is normal as `f32`.
+            let value = mantissa as f32 * f32::from_bits((127 - 78) << 23);
+            f32::from_bits(sign | value.to_bits())
+        }
+        0x7f => f32::from_bits(sign | 0x7f80_0000 | (mantissa << 7)),
+        _ => f32::from_bits(sign | ((exponent + 127 - 63) << 23) | (mantissa << 7)),
+    }
+}
+
+/// Converts floating point samples of `bytespersample` bytes stored in `src` to `f32`.
+///
+/// Samples of 2, 3 and 4 bytes are supported.
+///
+/// # Panics
+///
+/// Panics if `src.len()` is not equal to `dst.len() * bytespersample`.
+pub fn widen_into(
+    byteorder: ByteOrder,
+    bytespersample: u16,
+    src: &[u8],
+    dst: &mut [f32],
+) -> Result<(), Error> {
+    check_sample_size(bytespersample)?;
+    assert_eq!(
+        src.len(),
+        dst.len() * bytespersample as usize,
+        "source and destination slices have different lengths"
+    );
+
+    let samples = src.chunks_exact(bytespersample as usize).zip(dst);
+    match (byteorder, bytespersample) {
+        (ByteOrder::BigEndian, 2) => {
+            samples.for_each(|(src, dst)| *dst = f16_to_f32(u16::from_be_bytes([src[0], src[1]])))
+        }
+        (ByteOrder::LittleEndian, 2) => {
+            samples.for_each(|(src, dst)| *dst = f16_to_f32(u16::from_le_bytes([src[0], src[1]])))
+        }
+        (ByteOrder::BigEndian, 3) => samples.for_each(|(src, dst)| {
+            *dst = f24_to_f32(u32::from_be_bytes([0, src[0], src[1], src[2]]))
+        }),
+        (ByteOrder::LittleEndian, 3) => samples.for_each(|(src, dst)| {
+            *dst = f24_to_f32(u32::from_le_bytes([src[0], src[1], src[2], 0]))
+        }),
+        (ByteOrder::BigEndian, _) => samples
+            .for_each(|(src, dst)| *dst = f32::from_be_bytes([src[0], src[1], src[2], src[3]])),
+        (ByteOrder::LittleEndian, _) => samples
+            .for_each(|(src, dst)| *dst = f32::from_le_bytes([src[0], src[1], src[2], src[3]])),
+    }
+
+    Ok(())
+}
+
+/// Reads floating point samples of `bytespersample` bytes and converts them to `f32`.
+///
+/// Samples of 2, 3 and 4 bytes are supported. To read data encoded with the floating point
+/// predictor, use [`FloatPredictorReader::read_samples_f32`] instead.
+///
+/// [`FloatPredictorReader::read_samples_f32`]: crate::predictor::FloatPredictorReader::read_samples_f32
+pub fn read_into<R>(
+    reader: &mut R,
+    byteorder: ByteOrder,
+    bytespersample: u16,
+    dst: &mut [f32],
+) -> Result<(), Error>
+where
+    R: std::io::Read + ?Sized,
+{
+    check_sample_size(bytespersample)?;
+
+    let mut buffer = [0u8; BUFFER_SIZE];
+    let chunk_len = BUFFER_SIZE / bytespersample as usize;
+    for dst in dst.chunks_mut(chunk_len) {
+        let src = &mut buffer[..dst.len() * bytespersample as usize];
+        reader.read_exact(src)?;
+        widen_into(byteorder, bytespersample, src, dst)?;
+    }
+
+    Ok(())
+}
+
+/// Reads half precision floating point samples.
+#[cfg(feature = "f16")]
+#[cfg_attr(docsrs, doc(cfg(feature = "f16")))]
+pub fn read_f16_into<R>(reader: &mut R, byteorder: ByteOrder, dst: &mut [f16]) -> Result<(), Error>
+where
+    R: std::io::Read + ?Sized,
+{
+    use aira_byteorder::ReadBytesExt;
+
+    match byteorder {
+        ByteOrder::BigEndian => reader.read_f16_into::<aira_byteorder::BE>(dst)?,
+        ByteOrder::LittleEndian => reader.read_f16_into::<aira_byteorder::LE>(dst)?,
+    }
+    Ok(())
+}
+
+fn check_sample_size(bytespersample: u16) -> Result<(), Error> {
+    if !(2..=4).contains(&bytespersample) {
+        return Err(
+            Error::from(Unsupported::SampleSize(bytespersample)).with_context(|| {
+                format!(
+                    "Floating point samples with size {} cannot be converted to f32",
+                    bytespersample
+                )
+            }),
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::*;
+
+    use super::*;
+
+    #[test]
+    fn f16_known_values() {
+        assert_eq!(f16_to_f32(0x0000).to_bits(), 0f32.to_bits());
+        assert_eq!(f16_to_f32(0x8000).to_bits(), (-0f32).to_bits());
+        assert_eq!(f16_to_f32(0x3c00), 1.0);
+        assert_eq!(f16_to_f32(0xc000), -2.0);
+        assert_eq!(f16_to_f32(0x3555), 1365.0 / 4096.0);
+        assert_eq!(f16_to_f32(0x7bff), 65504.0);
+        assert_eq!(f16_to_f32(0x0400), 2f32.powi(-14));
+        assert_eq!(f16_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x83ff), -1023.0 * 2f32.powi(-24));
+        assert_eq!(f16_to_f32(0x7c00), f32::INFINITY);
+        assert_eq!(f16_to_f32(0xfc00), f32::NEG_INFINITY);
+        assert!(f16_to_f32(0x7e00).is_nan());
+    }
+
+    #[test]
+    fn f16_from_f32() {
+        assert_eq!(f32_to_f16(0.0), 0x0000);
+        assert_eq!(f32_to_f16(-0.0), 0x8000);
+        assert_eq!(f32_to_f16(1.0), 0x3c00);
+        assert_eq!(f32_to_f16(-2.0), 0xc000);
+        assert_eq!(f32_to_f16(65504.0), 0x7bff);
+        assert_eq!(f32_to_f16(2f32.powi(-14)), 0x0400);
+        assert_eq!(f32_to_f16(2f32.powi(-24)), 0x0001);
+        assert_eq!(f32_to_f16(-1023.0 * 2f32.powi(-24)), 0x83ff);
+        assert_eq!(f32_to_f16(f32::INFINITY), 0x7c00);
+        assert_eq!(f32_to_f16(f32::NEG_INFINITY), 0xfc00);
+        assert!(f16_to_f32(f32_to_f16(f32::NAN)).is_nan());
+
+        // Rounding to the nearest, ties to even.
+        assert_eq!(f32_to_f16(1.0 + 2f32.powi(-11)), 0x3c00);
+        assert_eq!(f32_to_f16(1.0 + 3.0 * 2f32.powi(-11)), 0x3c02);
+        assert_eq!(f32_to_f16(1.0 + 1.5 * 2f32.powi(-10)), 0x3c02);
+        assert_eq!(f32_to_f16(3.0 * 2f32.powi(-25)), 0x0002);
+        assert_eq!(f32_to_f16(2f32.powi(-25)), 0x0000);
+        assert_eq!(f32_to_f16(65520.0), 0x7c00);
+        assert_eq!(f32_to_f16(1e10), 0x7c00);
+        assert_eq!(f32_to_f16(1e-10), 0x0000);
+
+        for bits in (0..0x7c00).step_by(7) {
+            assert_eq!(f32_to_f16(f16_to_f32(bits)), bits);
+        }
+    }
+
+    #[test]
+    fn f24_known_values() {
+        assert_eq!(f24_to_f32(0x00_0000).to_bits(), 0f32.to_bits());
+        assert_eq!(f24_to_f32(0x80_0000).to_bits(), (-0f32).to_bits());
+        assert_eq!(f24_to_f32(0x3f_0000), 1.0);
+        assert_eq!(f24_to_f32(0x40_0000), 2.0);
+        assert_eq!(f24_to_f32(0xbf_8000), -1.5);
+        assert_eq!(f24_to_f32(0x3d_5555), 87381.0 / 262_144.0);
+        assert_eq!(
+            f24_to_f32(0x7e_ffff),
+            (2.0 - 2f32.powi(-16)) * 2f32.powi(63)
+        );
+        assert_eq!(f24_to_f32(0x01_0000), 2f32.powi(-62));
+        assert_eq!(f24_to_f32(0x00_0001), 2f32.powi(-78));
+        assert_eq!(f24_to_f32(0x7f_0000), f32::INFINITY);
+        assert_eq!(f24_to_f32(0xff_0000), f32::NEG_INFINITY);
+        assert!(f24_to_f32(0x7f_8000).is_nan());
+        // Bits above the 24th are ignored.
+        assert_eq!(f24_to_f32(0xff3f_0000), 1.0);
+    }
+
+    #[test]
+    fn widen_f16() {
+        let mut dst = [0f32; 2];
+        assert_ok!(widen_into(
+            ByteOrder::BigEndian,
+            2,
+            &[0x3c, 0x00, 0xc0, 0x00],
+            &mut dst
+        ));
+        assert_eq!(dst, [1.0, -2.0]);
+        assert_ok!(widen_into(
+            ByteOrder::LittleEndian,
+            2,
+            &[0x00, 0x3c, 0x00, 0xc0],
+            &mut dst
+        ));
+        assert_eq!(dst, [1.0, -2.0]);
+    }
+
+    #[test]
+    fn widen_f24() {
+        let mut dst = [0f32; 2];
+        let src = [0x3f, 0x00, 0x00, 0xbf, 0x80, 0x00];
+        assert_ok!(widen_into(ByteOrder::BigEndian, 3, &src, &mut dst));
+        assert_eq!(dst, [1.0, -1.5]);
+        let src = [0x00, 0x00, 0x3f, 0x00, 0x80, 0xbf];
+        assert_ok!(widen_into(ByteOrder::LittleEndian, 3, &src, &mut dst));
+        assert_eq!(dst, [1.0, -1.5]);
+    }
+
+    #[test]
+    fn widen_unsupported() {
+        let err = assert_err!(widen_into(ByteOrder::BigEndian, 8, &[0; 8], &mut [0f32; 1]));
+        assert_eq!(
+            err.kind(),
+            crate::ErrorKind::Unsupported(Unsupported::SampleSize(8))
+        );
+    }
+
+    #[test]
+    fn read_more_than_buffer() {
+        let values = std::iter::repeat_with(|| fastrand::u16(..))
+            .take(BUFFER_SIZE + 7)
+            .collect::<Vec<_>>();
+        let src = values
+            .iter()
+            .flat_map(|value| value.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        let mut dst = vec![0f32; values.len()];
+        assert_ok!(read_into(
+            &mut &src[..],
+            ByteOrder::LittleEndian,
+            2,
+            &mut dst
+        ));
+        for (value, widened) in values.into_iter().zip(dst) {
+            assert_eq!(widened.to_bits(), f16_to_f32(value).to_bits());
+        }
+    }
+
+    #[cfg(feature = "f16")]
+    #[test]
+    fn f16_matches_primitive() {
+        for bits in 0..=u16::MAX {
+            let expected = f16::from_bits(bits) as f32;
+            let actual = f16_to_f32(bits);
+            if expected.is_nan() {
+                assert!(actual.is_nan(), "0x{bits:04x}");
+            } else {
+                assert_eq!(actual.to_bits(), expected.to_bits(), "0x{bits:04x}");
+            }
+        }
+    }
+}
@@ -6,10 +6,13 @@
 //!
 //! * `deflate`: Turns on the support for the Deflate compression algorithm using the [`flate2`]
 //!   crate with `zlib-rs` enabled.
+//! * `lzw`: Turns on the support for the LZW compression algorithm using the [`weezl`] crate.
 //!
 //! ##### Optional features
 //!
 //! * `chrono`: The crate [`chrono`] is used to represent dates and times.
+//! * `f16`: Half precision samples can be read as the primitive type `f16`, this requires a
+//!   nightly compiler.
 //! * `jiff`: The crate [`jiff`] is used to represent dates and times.
 //!
 //! Flags `chrono` and `jiff` are mutually exclusive, if none of them is enabled, then dates and
@@ -18,6 +21,10 @@
 //! [`chrono`]: https://crates.io/crates/chrono
 //! [`jiff`]: https://crates.io/crates/jiff
 //! [`flate2`]: https://crates.io/crates/flate2
+//! [`weezl`]: https://crates.io/crates/weezl
+
+#![cfg_attr(docsrs, feature(doc_cfg))]
+#![cfg_attr(feature = "f16", feature(f16))]
 
 #[cfg(all(feature = "chrono", feature = "jiff"))]
 compile_error!("features 'chrono' and 'jiff' are mutually exclusive");
@@ -25,26 +32,42 @@ compile_error!("features 'chrono' and 'jiff' are mutually exclusive");
 #[doc(inline)]
 pub use self::{
     compression::Compression, decoder::Decoder, dtype::DType, endian::ByteOrder, entry::Entry,
-    error::Error, interpretation::Interpretation, metadata::Metadata,
+    error::{EntryPart, Error, ErrorKind, Location, Unsupported},
+    extra_sample::ExtraSample,
+    file::TiffFile,
+    fill_order::FillOrder,
+    interpretation::Interpretation, metadata::Metadata, orientation::Orientation,
     planar_configuration::PlanarConfiguration, predictor::Predictor, ratio::Ratio,
     resolution_unit::ResolutionUnit, sample_format::SampleFormat, subfile_type::SubfileType,
-    tag::Tag, version::Version,
+    tag::Tag, version::Version, ycbcr_positioning::YCbCrPositioning,
 };
 
 mod dtype;
 mod endian;
 mod error;
+mod extra_sample;
+mod fill_order;
 mod interpretation;
+mod orientation;
 mod planar_configuration;
 mod resolution_unit;
 mod sample_format;
 mod subfile_type;
 mod tag;
 mod version;
+mod ycbcr_positioning;
 
+pub mod color;
 pub mod compression;
 pub mod decoder;
 pub mod entry;
+pub mod file;
+pub mod float;
+pub mod lint;
+pub mod logluv;
+pub mod mask;
 pub mod metadata;
 pub mod predictor;
 pub mod ratio;
+pub mod reader;
+pub mod unpack;
@@ -0,0 +1,598 @@
+//! Conformance checker of TIFF files.
+//!
+//! The checker walks through the raw structure of the file using the [`Decoder`], and reports
+//! every violation of the TIFF 6.0, BigTIFF and GeoTIFF 1.1 specifications it finds instead of
+//! stopping at the first one.
+//!
+//! ```
+//! use aira_tiff::{lint, Decoder};
+//!
+//! let file = std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif")?;
+//! let mut decoder = Decoder::new(file)?;
+//! for violation in lint::check(&mut decoder) {
+//!     println!("{}: {}", violation.location, violation.message);
+//! }
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use std::collections::HashSet;
+
+use crate::{decoder, DType, Decoder, Entry, EntryPart, Error, Location, Tag, Version};
+
+/// The maximum number of directories visited, the chain of directories may contain cycles.
+const MAX_DIRECTORIES: usize = 1 << 16;
+
+/// A rule of the specification.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Rule {
+    /// The structure of the file can be decoded.
+    Structure,
+    /// The entries of a directory are sorted in ascending order by tag, without duplicates.
+    SortedTags,
+    /// The datatype of a well-known tag is one of those defined by the specification.
+    TagDType,
+    /// The directories and the entry data begin on a word boundary.
+    WordAlignedOffset,
+    /// The strips or tiles do not overlap each other.
+    OverlappingChunks,
+    /// The strips or tiles lie within the file.
+    ChunkWithinFile,
+    /// There is a bits per sample and a sample format value for each sample.
+    SampleCount,
+    /// The GeoKey directory is consistent.
+    GeoKeys,
+}
+
+impl Rule {
+    /// Returns the identifier of the rule.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Rule::Structure => "structure",
+            Rule::SortedTags => "sorted-tags",
+            Rule::TagDType => "tag-dtype",
+            Rule::WordAlignedOffset => "word-aligned-offset",
+            Rule::OverlappingChunks => "overlapping-chunks",
+            Rule::ChunkWithinFile => "chunk-within-file",
+            Rule::SampleCount => "sample-count",
+            Rule::GeoKeys => "geo-keys",
+        }
+    }
+}
+
+/// A violation of the specification.
+#[derive(Clone, Debug)]
+pub struct Violation {
+    /// The rule that is violated.
+    pub rule: Rule,
+    /// The location of the violation in the file.
+    pub location: Location,
+    /// A description of the violation.
+    pub message: String,
+}
+
+impl From<Error> for Violation {
+    /// Converts an error that prevents decoding a part of the file into a [`Rule::Structure`]
+    /// violation.
+    fn from(err: Error) -> Self {
+        Violation {
+            rule: Rule::Structure,
+            location: err.location().unwrap_or_else(header_location),
+            message: format!("{err:#}"),
+        }
+    }
+}
+
+/// Checks the conformance of a TIFF file and returns the list of violations.
+pub fn check<R>(decoder: &mut Decoder<R>) -> Vec<Violation>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    let mut linter = Linter {
+        version: decoder.version(),
+        stream_len: 0,
+        chunks: Vec::new(),
+        violations: Vec::new(),
+    };
+
+    match decoder.stream_len() {
+        Ok(stream_len) => linter.stream_len = stream_len,
+        Err(err) => {
+            linter.report_error(err, header_location());
+            return linter.violations;
+        }
+    }
+
+    let mut directories = decoder.directories();
+    let mut visited_offsets = HashSet::new();
+    for _ in 0..MAX_DIRECTORIES {
+        let directory = match directories.next_directory() {
+            Ok(Some(directory)) => directory,
+            Ok(None) => break,
+            Err(err) => {
+                linter.report_error(err, header_location());
+                break;
+            }
+        };
+
+        let location = directory.location();
+        if !visited_offsets.insert(directory.offset) {
+            linter.report(
+                Rule::Structure,
+                location,
+                "Cycle detected in chaining of TIFF directories".into(),
+            );
+            break;
+        }
+
+        linter.check_directory(directory);
+    }
+
+    linter.check_chunks();
+    linter.violations
+}
+
+/// A strip or tile, with the location of the entry that defines it.
+struct ChunkRef {
+    offset: u64,
+    byte_count: u64,
+    index: usize,
+    location: Location,
+}
+
+/// The values of a directory needed by the checks spanning multiple entries.
+#[derive(Default)]
+struct DirectoryValues {
+    samples_per_pixel: Option<u64>,
+    bits_per_sample: Option<(usize, Location)>,
+    sample_format: Option<(usize, Location)>,
+    chunk_offsets: Option<(Vec<u64>, Location)>,
+    chunk_byte_counts: Option<Vec<u64>>,
+    geo_keys: Option<(Vec<u64>, Location)>,
+    geo_doubles: Option<usize>,
+    geo_ascii: Option<String>,
+}
+
+struct Linter {
+    version: Version,
+    stream_len: u64,
+    chunks: Vec<ChunkRef>,
+    violations: Vec<Violation>,
+}
+
+impl Linter {
+    fn report(&mut self, rule: Rule, location: Location, message: String) {
+        self.violations.push(Violation {
+            rule,
+            location,
+            message,
+        });
+    }
+
+    /// Reports an error that prevents decoding a part of the file.
+    fn report_error(&mut self, err: Error, fallback: Location) {
+        let location = err.location().unwrap_or(fallback);
+        self.violations.push(Violation {
+            location,
+            ..err.into()
+        });
+    }
+
+    fn check_directory<R>(&mut self, directory: decoder::Directory<'_, R>)
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        let location = directory.location();
+        if !directory.offset.is_multiple_of(2) {
+            self.report(
+                Rule::WordAlignedOffset,
+                location,
+                format!("Directory offset {} is not word aligned", directory.offset),
+            );
+        }
+
+        let max_inline_size = match self.version {
+            Version::Classic => 4,
+            Version::BigTiff => 8,
+        };
+
+        let mut values = DirectoryValues::default();
+        let mut previous_tag = None;
+        let mut entries = directory.entries();
+        loop {
+            let entry = match entries.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(err) => {
+                    self.report_error(err, location);
+                    continue;
+                }
+            };
+
+            let header = entry.location(EntryPart::Header);
+            let payload = entry.location(EntryPart::Payload);
+            let (tag, dtype, count) = (entry.tag, entry.dtype, entry.count);
+
+            match previous_tag {
+                Some(previous) if previous == tag => {
+                    self.report(Rule::SortedTags, header, format!("Duplicate entry {tag:?}"))
+                }
+                Some(previous) if previous > tag => self.report(
+                    Rule::SortedTags,
+                    header,
+                    format!("Entry {tag:?} is not sorted, it follows {previous:?}"),
+                ),
+                _ => {}
+            }
+            previous_tag = Some(tag);
+
+            if let Some(expected) = expected_dtypes(tag, self.version)
+                && !expected.contains(&dtype)
+            {
+                self.report(
+                    Rule::TagDType,
+                    header,
+                    format!("Entry {tag:?} has datatype {dtype:?}, expected one of {expected:?}"),
+                );
+            }
+
+            let out_of_line = dtype.size().saturating_mul(count) > max_inline_size;
+            if out_of_line && !payload.offset.is_multiple_of(2) {
+                self.report(
+                    Rule::WordAlignedOffset,
+                    header,
+                    format!(
+                        "Data offset {} of {tag:?} is not word aligned",
+                        payload.offset
+                    ),
+                );
+            }
+
+            let value = match Entry::from_decoder(entry) {
+                Ok(value) => value,
+                Err(err) => {
+                    self.report_error(err, payload);
+                    continue;
+                }
+            };
+
+            match tag {
+                Tag::SAMPLES_PER_PIXEL => {
+                    values.samples_per_pixel = integers(&value).first().copied();
+                }
+                Tag::BITS_PER_SAMPLE => values.bits_per_sample = Some((count as usize, header)),
+                Tag::SAMPLE_FORMAT => values.sample_format = Some((count as usize, header)),
+                Tag::STRIP_OFFSETS | Tag::TILE_OFFSETS => {
+                    values.chunk_offsets = Some((integers(&value), header));
+                }
+                Tag::STRIP_BYTE_COUNTS | Tag::TILE_BYTE_COUNTS => {
+                    values.chunk_byte_counts = Some(integers(&value));
+                }
+                Tag::GEO_KEY_DIRECTORY => values.geo_keys = Some((integers(&value), header)),
+                Tag::GEO_DOUBLE_PARAMS => values.geo_doubles = Some(count as usize),
+                Tag::GEO_ASCII_PARAMS => {
+                    if let Entry::Ascii(ascii) = value {
+                        values.geo_ascii = Some(ascii);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.check_samples(&values);
+        self.collect_chunks(&mut values);
+        if let Some((keys, location)) = &values.geo_keys {
+            self.check_geo_keys(keys, location, &values);
+        }
+    }
+
+    /// Checks that the number of values of sample tags matches the number of samples.
+    fn check_samples(&mut self, values: &DirectoryValues) {
+        let samples_per_pixel = values.samples_per_pixel.unwrap_or(1) as usize;
+        for (tag, count) in [
+            (Tag::BITS_PER_SAMPLE, values.bits_per_sample),
+            (Tag::SAMPLE_FORMAT, values.sample_format),
+        ] {
+            if let Some((count, location)) = count
+                && count != samples_per_pixel
+            {
+                self.report(
+                    Rule::SampleCount,
+                    location,
+                    format!(
+                        "Entry {tag:?} has {count} values, expected {samples_per_pixel} \
+                         (SamplesPerPixel)"
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Collects the chunks of the directory, and checks that they lie within the file.
+    fn collect_chunks(&mut self, values: &mut DirectoryValues) {
+        let (Some((offsets, location)), Some(byte_counts)) =
+            (values.chunk_offsets.take(), values.chunk_byte_counts.take())
+        else {
+            return;
+        };
+
+        for (index, (offset, byte_count)) in offsets.into_iter().zip(byte_counts).enumerate() {
+            // Sparse chunks have no data.
+            if offset == 0 && byte_count == 0 {
+                continue;
+            }
+
+            if offset
+                .checked_add(byte_count)
+                .is_none_or(|end| end > self.stream_len)
+            {
+                self.report(
+                    Rule::ChunkWithinFile,
+                    location,
+                    format!(
+                        "Chunk {index} at offset {offset} with {byte_count} bytes exceeds the end \
+                         of file ({} bytes)",
+                        self.stream_len
+                    ),
+                );
+            }
+
+            self.chunks.push(ChunkRef {
+                offset,
+                byte_count,
+                index,
+                location,
+            });
+        }
+    }
+
+    /// Checks that the chunks of all the directories do not overlap each other.
+    fn check_chunks(&mut self) {
+        let mut chunks = std::mem::take(&mut self.chunks);
+        chunks.sort_unstable_by_key(|chunk| chunk.offset);
+
+        let mut previous: Option<&ChunkRef> = None;
+        for chunk in &chunks {
+            if chunk.byte_count == 0 {
+                continue;
+            }
+
+            if let Some(previous) = previous
+                && chunk.offset < previous.offset.saturating_add(previous.byte_count)
+            {
+                self.report(
+                    Rule::OverlappingChunks,
+                    chunk.location,
+                    format!(
+                        "Chunk {} at offset {} overlaps chunk {} of IFD {} at offset {}",
+                        chunk.index,
+                        chunk.offset,
+                        previous.index,
+                        previous.location.directory.unwrap_or_default(),
+                        previous.offset,
+                    ),
+                );
+            }
+
+            // The chunk that ends further is kept to detect all the overlaps.
+            let ends_further = previous.is_none_or(|previous| {
+                chunk.offset.saturating_add(chunk.byte_count)
+                    > previous.offset.saturating_add(previous.byte_count)
+            });
+            if ends_further {
+                previous = Some(chunk);
+            }
+        }
+    }
+
+    /// Checks the consistency of the GeoKey directory, see GeoTIFF 1.1 section 7.1.
+    fn check_geo_keys(&mut self, keys: &[u64], location: &Location, values: &DirectoryValues) {
+        let location = *location;
+
+        let Some((header, keys)) = keys.split_first_chunk::<4>() else {
+            self.report(
+                Rule::GeoKeys,
+                location,
+                "GeoKey directory is shorter than its header".into(),
+            );
+            return;
+        };
+
+        let [version, revision, minor_revision, keys_count] = *header;
+        if version != 1 || revision != 1 || minor_revision > 1 {
+            self.report(
+                Rule::GeoKeys,
+                location,
+                format!(
+                    "Unsupported GeoKey directory version {version}, revision \
+                     {revision}.{minor_revision}, expected version 1, revision 1.0 or 1.1"
+                ),
+            );
+        }
+
+        if keys.len() as u64 != keys_count.saturating_mul(4) {
+            self.report(
+                Rule::GeoKeys,
+                location,
+                format!(
+                    "GeoKey directory declares {keys_count} keys, but contains {} values",
+                    keys.len()
+                ),
+            );
+        }
+
+        let mut previous_id = None;
+        for key in keys.chunks_exact(4) {
+            let [id, tag, count, value] = [key[0], key[1], key[2], key[3]];
+
+            if previous_id.is_some_and(|previous| previous >= id) {
+                self.report(
+                    Rule::GeoKeys,
+                    location,
+                    format!("GeoKey {id} is not sorted in ascending order or is duplicated"),
+                );
+            }
+            previous_id = Some(id);
+
+            let available = match Tag(tag as u16) {
+                _ if tag > u16::MAX as u64 => None,
+                Tag(0) => {
+                    if count != 1 {
+                        self.report(
+                            Rule::GeoKeys,
+                            location,
+                            format!(
+                                "GeoKey {id} stored in the directory has count {count}, expected 1"
+                            ),
+                        );
+                    }
+                    continue;
+                }
+                Tag::GEO_DOUBLE_PARAMS => values.geo_doubles,
+                Tag::GEO_ASCII_PARAMS => values.geo_ascii.as_ref().map(String::len),
+                Tag::GEO_KEY_DIRECTORY => Some(keys.len() + 4),
+                _ => None,
+            };
+
+            let Some(available) = available else {
+                self.report(
+                    Rule::GeoKeys,
+                    location,
+                    format!("GeoKey {id} refers to missing or invalid tag {tag}"),
+                );
+                continue;
+            };
+
+            let end = value.saturating_add(count);
+            if end > available as u64 {
+                self.report(
+                    Rule::GeoKeys,
+                    location,
+                    format!(
+                        "GeoKey {id} refers to values {value}..{end} of tag {tag}, but only \
+                         {available} are available"
+                    ),
+                );
+                continue;
+            }
+
+            // Each string of the ASCII parameters is terminated by a pipe.
+            if tag == Tag::GEO_ASCII_PARAMS.0 as u64 && count > 0 {
+                let ascii = values.geo_ascii.as_deref().unwrap_or_default().as_bytes();
+                if ascii[end as usize - 1] != b'|' {
+                    self.report(
+                        Rule::GeoKeys,
+                        location,
+                        format!("GeoKey {id} string is not terminated by '|'"),
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Returns the location of the file header, for errors outside of any directory.
+fn header_location() -> Location {
+    Location {
+        offset: 0,
+        directory: None,
+        entry: None,
+        tag: None,
+        part: None,
+    }
+}
+
+/// Returns the unsigned integer values of an entry, other values are ignored.
+fn integers(entry: &Entry) -> Vec<u64> {
+    match entry {
+        Entry::U8(values) => values.iter().map(|&x| x as u64).collect(),
+        Entry::U16(values) => values.iter().map(|&x| x as u64).collect(),
+        Entry::U32(values) => values.iter().map(|&x| x as u64).collect(),
+        Entry::U64(values) => values.clone(),
+        _ => Vec::new(),
+    }
+}
+
+/// Returns the datatypes allowed by the specification for a well-known tag.
+fn expected_dtypes(tag: Tag, version: Version) -> Option<&'static [DType]> {
+    use DType::*;
+
+    // Big TIFF allows 64-bit integers for the offsets and byte counts.
+    let big = version == Version::BigTiff;
+
+    let dtypes: &'static [DType] = match tag {
+        Tag::NEW_SUBFILE_TYPE => &[Long],
+        Tag::SUBFILE_TYPE
+        | Tag::BITS_PER_SAMPLE
+        | Tag::COMPRESSION
+        | Tag::PHOTOMETRIC_INTERPRETATION
+        | Tag::THRESHHOLDING
+        | Tag::CELL_WIDTH
+        | Tag::CELL_LENGTH
+        | Tag::FILL_ORDER
+        | Tag::ORIENTATION
+        | Tag::SAMPLES_PER_PIXEL
+        | Tag::PLANAR_CONFIGURATION
+        | Tag::GRAY_RESPONSE_UNIT
+        | Tag::GRAY_RESPONSE_CURVE
+        | Tag::RESOLUTION_UNIT
+        | Tag::PAGE_NUMBER
+        | Tag::TRANSFER_FUNCTION
+        | Tag::PREDICTOR
+        | Tag::COLOR_MAP
+        | Tag::HALFTONE_HINTS
+        | Tag::INK_SET
+        | Tag::NUMBER_OF_INKS
+        | Tag::EXTRA_SAMPLES
+        | Tag::SAMPLE_FORMAT
+        | Tag::TRANSFER_RANGE
+        | Tag::YCBCR_SUB_SAMPLING
+        | Tag::YCBCR_POSITIONING
+        | Tag::GEO_KEY_DIRECTORY => &[Short],
+        Tag::MIN_SAMPLE_VALUE | Tag::MAX_SAMPLE_VALUE => &[Short],
+        Tag::IMAGE_WIDTH
+        | Tag::IMAGE_LENGTH
+        | Tag::ROWS_PER_STRIP
+        | Tag::TILE_WIDTH
+        | Tag::TILE_LENGTH => &[Short, Long],
+        Tag::STRIP_OFFSETS | Tag::STRIP_BYTE_COUNTS | Tag::TILE_BYTE_COUNTS if big => {
+            &[Short, Long, BigLong]
+        }
+        Tag::STRIP_OFFSETS | Tag::STRIP_BYTE_COUNTS | Tag::TILE_BYTE_COUNTS => &[Short, Long],
+        Tag::TILE_OFFSETS if big => &[Long, BigLong],
+        Tag::TILE_OFFSETS | Tag::FREE_OFFSETS | Tag::FREE_BYTE_COUNTS => &[Long],
+        Tag::T4_OPTIONS | Tag::T6_OPTIONS => &[Long],
+        Tag::SUBIFDS if big => &[Long, Ifd, BigLong, BigIfd],
+        Tag::SUBIFDS => &[Long, Ifd],
+        Tag::XRESOLUTION
+        | Tag::YRESOLUTION
+        | Tag::XPOSITION
+        | Tag::YPOSITION
+        | Tag::WHITE_POINT
+        | Tag::PRIMARY_CHROMATICITIES
+        | Tag::YCBCR_COEFFICIENTS
+        | Tag::REFERENCE_BLACK_WHITE => &[Rational],
+        Tag::IMAGE_DESCRIPTION
+        | Tag::MAKE
+        | Tag::MODEL
+        | Tag::SOFTWARE
+        | Tag::DATE_TIME
+        | Tag::ARTIST
+        | Tag::HOST_COMPUTER
+        | Tag::COPYRIGHT
+        | Tag::DOCUMENT_NAME
+        | Tag::PAGE_NAME
+        | Tag::INK_NAMES
+        | Tag::TARGET_PRINTER
+        | Tag::GEO_ASCII_PARAMS
+        | Tag::GDAL_METADATA
+        | Tag::GDAL_NO_DATA => &[Ascii],
+        Tag::DOT_RANGE => &[Byte, Short],
+        Tag::MODEL_PIXEL_SCALE
+        | Tag::MODEL_TIEPOINT
+        | Tag::MODEL_TRANSFORMATION
+        | Tag::GEO_DOUBLE_PARAMS => &[Double],
+        _ => return None,
+    };
+    Some(dtypes)
+}
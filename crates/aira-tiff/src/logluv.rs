@@ -0,0 +1,612 @@
+//! Reading of SGI LogL and LogLuv pixels.
+//!
+//! LogL pixels store the logarithm of the luminance Y in 16 bits, LogLuv pixels additionally
+//! store the chromaticity as CIE (u', v') coordinates, encoded in 32 bits. Both are compressed
+//! with [`Compression::SGILOG`], and decoded to floating-point CIE XYZ values, which can be
+//! converted to linear sRGB.
+//!
+//! The [`LogLuvReader`] is the only way to read these images: the SGI Log compressions depend on
+//! the width of the rows and on the encoding of the pixels, so they are not accepted by
+//! [`DecompressReader`](crate::compression::DecompressReader).
+//!
+//! LogLuv pixels encoded in 24 bits are stored uncompressed with [`Compression::SGILOG24`], the
+//! logarithm of their luminance is coarser, and their chromaticity is the index of a cell in the
+//! table of (u', v') coordinates of libtiff. LogL images with this compression are read as with
+//! [`Compression::SGILOG`], like libtiff does.
+//!
+//! The `BitsPerSample` and `SampleFormat` of these images describe the format requested when they
+//! were written, they are ignored.
+//!
+//! ```
+//! use aira_tiff::{logluv::LogLuvReader, Decoder, Metadata};
+//!
+//! let file = std::fs::File::open("tests/images/logluv-3c-16b.tiff")?;
+//! let mut decoder = Decoder::new(std::io::BufReader::new(file))?;
+//! let mut directories = decoder.directories();
+//! let directory = directories.next_directory()?.unwrap();
+//! let metadata = Metadata::from_decoder(directory)?;
+//!
+//! let pixels = LogLuvReader::new(&metadata)?;
+//! let mut xyz = vec![0.0; pixels.image_len()];
+//! pixels.read_image(&mut decoder.into_inner(), &mut xyz)?;
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use std::io::{Read, Seek};
+
+use crate::{
+    color,
+    compression::{Compression, SgiLogReader},
+    error::ErrorContext,
+    metadata::{Chunk, Layout},
+    reader::Window,
+    Error, Interpretation, Metadata, PlanarConfiguration, Unsupported,
+};
+
+/// Reads the pixel data of LogL and LogLuv images.
+#[derive(Clone, Debug)]
+pub struct LogLuvReader<'tiff> {
+    metadata: &'tiff Metadata,
+    encoding: Encoding,
+}
+
+/// The encoding of the pixels.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Encoding {
+    LogL16,
+    LogLuv24,
+    LogLuv32,
+}
+
+impl Encoding {
+    /// Returns the number of bytes of an encoded pixel.
+    fn bytes_per_pixel(self) -> usize {
+        match self {
+            Encoding::LogL16 => 2,
+            Encoding::LogLuv24 => 3,
+            Encoding::LogLuv32 => 4,
+        }
+    }
+}
+
+impl<'tiff> LogLuvReader<'tiff> {
+    /// Creates a new instance of [`LogLuvReader`] for the image described by `metadata`.
+    pub fn new(metadata: &'tiff Metadata) -> Result<Self, Error> {
+        let encoding = match (metadata.interpretation, metadata.compression) {
+            (Interpretation::LOGL, Compression::SGILOG | Compression::SGILOG24) => Encoding::LogL16,
+            (Interpretation::LOGLUV, Compression::SGILOG) => Encoding::LogLuv32,
+            (Interpretation::LOGLUV, Compression::SGILOG24) => Encoding::LogLuv24,
+            (Interpretation::LOGLUV, compression) => {
+                return Err(
+                    Error::from(Unsupported::Compression(compression)).with_context(|| {
+                        format!("LogLuv pixels cannot be read with {compression:?}")
+                    }),
+                );
+            }
+            (interpretation, _) => return Err(Unsupported::Interpretation(interpretation).into()),
+        };
+        if metadata.configuration == PlanarConfiguration::PLANAR {
+            return Err(Error::from_static_str(
+                "LogLuv pixels stored in separate planes cannot be read",
+            ));
+        }
+
+        Ok(Self { metadata, encoding })
+    }
+
+    /// Returns the number of values of a pixel: 3 for the XYZ values of LogLuv pixels, and 1 for
+    /// the Y value of LogL pixels.
+    pub fn samples_per_pixel(&self) -> usize {
+        match self.encoding {
+            Encoding::LogL16 => 1,
+            Encoding::LogLuv24 | Encoding::LogLuv32 => 3,
+        }
+    }
+
+    /// Returns the number of values of the whole image.
+    pub fn image_len(&self) -> usize {
+        let (width, length) = self.metadata.dimensions;
+        width as usize * length as usize * self.samples_per_pixel()
+    }
+
+    /// Returns the number of values of the decoded `chunk`, including the padding.
+    pub fn chunk_len(&self, chunk: &Chunk) -> usize {
+        let (width, length) = self.decoded_size(chunk);
+        width * length * self.samples_per_pixel()
+    }
+
+    /// Decodes a single chunk into `dst`, the rows include the padding of the chunk.
+    ///
+    /// A [sparse](Chunk::is_sparse) chunk is filled with zeros, without reading from `reader`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`chunk_len`](Self::chunk_len).
+    pub fn read_chunk<R>(&self, reader: &mut R, chunk: &Chunk, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        assert_eq!(
+            dst.len(),
+            self.chunk_len(chunk),
+            "destination slice does not match the size of the chunk"
+        );
+
+        if chunk.is_sparse() {
+            dst.fill(0.0);
+            return Ok(());
+        }
+
+        self.decode_chunk(reader, chunk, dst).with_context(|| {
+            format!(
+                "Failed to read chunk at offset {} with {} bytes",
+                chunk.offset, chunk.byte_count
+            )
+        })
+    }
+
+    /// Reads the XYZ values of the whole image into `dst`, or the Y values of LogL images.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`image_len`](Self::image_len).
+    pub fn read_image<R>(&self, reader: &mut R, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        assert_eq!(
+            dst.len(),
+            self.image_len(),
+            "destination slice does not match the size of the image"
+        );
+
+        let samples = self.samples_per_pixel();
+        let row_len = self.metadata.dimensions.0 as usize * samples;
+        let mut buffer = Vec::new();
+        let image = Window::new((0, 0), self.metadata.dimensions);
+        for chunk in self.metadata.window_chunks(reader, 0, image)? {
+            buffer.resize(self.chunk_len(&chunk), 0.0);
+            self.read_chunk(reader, &chunk, &mut buffer)?;
+
+            let (x, y) = (chunk.origin.0 as usize, chunk.origin.1 as usize);
+            let (width, length) = (chunk.size.0 as usize, chunk.size.1 as usize);
+            let src_rows = buffer.chunks_exact(self.decoded_size(&chunk).0 * samples);
+            let dst_rows = dst[y * row_len..].chunks_exact_mut(row_len);
+            for (src, dst) in src_rows.zip(dst_rows).take(length) {
+                dst[x * samples..(x + width) * samples].copy_from_slice(&src[..width * samples]);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the linear sRGB values of the whole image into `dst`.
+    ///
+    /// The gray levels of LogL images are their luminance.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dst` cannot hold exactly 3 values for each pixel.
+    pub fn read_linear_rgb<R>(&self, reader: &mut R, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        let (width, length) = self.metadata.dimensions;
+        assert_eq!(
+            dst.len(),
+            width as usize * length as usize * 3,
+            "destination slice does not match the size of the image"
+        );
+
+        match self.encoding {
+            Encoding::LogLuv24 | Encoding::LogLuv32 => {
+                self.read_image(reader, dst)?;
+                for pixel in dst.chunks_exact_mut(3) {
+                    let xyz = [pixel[0], pixel[1], pixel[2]];
+                    pixel.copy_from_slice(&color::xyz_to_linear_rgb(xyz));
+                }
+            }
+            Encoding::LogL16 => {
+                let mut luminance = vec![0.0; self.image_len()];
+                self.read_image(reader, &mut luminance)?;
+                for (pixel, y) in dst.chunks_exact_mut(3).zip(luminance) {
+                    pixel.fill(y);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the width and length of the decoded chunk, including the padding.
+    fn decoded_size(&self, chunk: &Chunk) -> (usize, usize) {
+        match self.metadata.layout {
+            Layout::Strips { .. } => (self.metadata.dimensions.0 as usize, chunk.size.1 as usize),
+            Layout::Tiles { width, length } => (width as usize, length as usize),
+        }
+    }
+
+    fn decode_chunk<R>(&self, reader: &mut R, chunk: &Chunk, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        reader.seek(std::io::SeekFrom::Start(chunk.offset))?;
+        let compressed = reader.take(chunk.byte_count);
+        let width = self.decoded_size(chunk).0;
+        match self.encoding {
+            // The 24-bit LogLuv pixels are not compressed.
+            Encoding::LogLuv24 => self.decode_rows(compressed, width, dst),
+            encoding => {
+                let bytesperpixel = encoding.bytes_per_pixel();
+                let decompressed = SgiLogReader::new(compressed, width, bytesperpixel);
+                self.decode_rows(decompressed, width, dst)
+            }
+        }
+    }
+
+    /// Decodes the rows of `width` pixels read from `reader` into `dst`.
+    fn decode_rows<R>(&self, mut reader: R, width: usize, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: Read,
+    {
+        let mut row = vec![0u8; width * self.encoding.bytes_per_pixel()];
+        for dst in dst.chunks_exact_mut(width * self.samples_per_pixel()) {
+            reader.read_exact(&mut row)?;
+            match self.encoding {
+                Encoding::LogL16 => {
+                    for (y, src) in dst.iter_mut().zip(row.chunks_exact(2)) {
+                        *y = logl16_to_y(u16::from_be_bytes([src[0], src[1]]));
+                    }
+                }
+                Encoding::LogLuv24 => {
+                    for (xyz, src) in dst.chunks_exact_mut(3).zip(row.chunks_exact(3)) {
+                        let pixel = u32::from_be_bytes([0, src[0], src[1], src[2]]);
+                        xyz.copy_from_slice(&logluv24_to_xyz(pixel));
+                    }
+                }
+                Encoding::LogLuv32 => {
+                    for (xyz, src) in dst.chunks_exact_mut(3).zip(row.chunks_exact(4)) {
+                        let pixel = u32::from_be_bytes([src[0], src[1], src[2], src[3]]);
+                        xyz.copy_from_slice(&logluv32_to_xyz(pixel));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The scale of the encoded (u', v') coordinates.
+const UV_SCALE: f64 = 410.0;
+
+/// Decodes the luminance Y of a LogL pixel encoded in 16 bits.
+pub fn logl16_to_y(pixel: u16) -> f32 {
+    let exponent = pixel & 0x7fff;
+    if exponent == 0 {
+        return 0.0;
+    }
+
+    let y = (std::f64::consts::LN_2 / 256.0 * (exponent as f64 + 0.5)
+        - std::f64::consts::LN_2 * 64.0)
+        .exp();
+    match pixel & 0x8000 {
+        0 => y as f32,
+        _ => -y as f32,
+    }
+}
+
+/// Decodes the luminance Y of the 10 bits of a LogLuv pixel encoded in 24 bits.
+fn logl10_to_y(exponent: u32) -> f64 {
+    if exponent == 0 {
+        return 0.0;
+    }
+
+    (std::f64::consts::LN_2 / 64.0 * (exponent as f64 + 0.5) - std::f64::consts::LN_2 * 12.0).exp()
+}
+
+/// The size of the cells of the (u', v') table.
+///
+/// The coordinates of the table are single precision, as in libtiff, so that the decoded values
+/// are the same.
+const UV_SQSIZ: f32 = 0.0035;
+
+/// The coordinate v' of the lower side of the first row of cells.
+const UV_VSTART: f32 = 0.01694;
+
+/// The number of cells of the (u', v') table.
+const UV_NDIVS: u32 = 16289;
+
+/// The (u', v') coordinates of the white point E, used for the indices outside of the table.
+const UV_NEUTRAL: (f64, f64) = (0.210526316, 0.473684211);
+
+/// The rows of cells of the (u', v') table of libtiff, from the lowest v', with the coordinate u'
+/// of the left side of their first cell and the index of this cell.
+///
+/// The cells cover the gamut of the visible colors, the values are rounded to 6 decimals as in
+/// libtiff.
+const UV_ROWS: [(f32, u16); 163] = [
+    (0.247663, 0),
+    (0.243779, 4),
+    (0.241684, 10),
+    (0.237874, 17),
+    (0.235906, 26),
+    (0.232153, 36),
+    (0.228352, 48),
+    (0.226259, 62),
+    (0.222371, 77),
+    (0.220410, 94),
+    (0.214710, 112),
+    (0.212714, 133),
+    (0.210721, 155),
+    (0.204976, 178),
+    (0.202986, 204),
+    (0.199245, 231),
+    (0.195525, 260),
+    (0.193560, 291),
+    (0.189878, 323),
+    (0.186216, 357),
+    (0.186216, 393),
+    (0.182592, 429),
+    (0.179003, 467),
+    (0.175466, 507),
+    (0.172001, 549),
+    (0.172001, 593),
+    (0.168612, 637),
+    (0.168612, 683),
+    (0.163575, 729),
+    (0.158642, 778),
+    (0.158642, 830),
+    (0.158642, 882),
+    (0.153815, 934),
+    (0.153815, 989),
+    (0.149097, 1044),
+    (0.149097, 1102),
+    (0.142746, 1160),
+    (0.142746, 1222),
+    (0.142746, 1284),
+    (0.138270, 1346),
+    (0.138270, 1411),
+    (0.138270, 1476),
+    (0.132166, 1541),
+    (0.132166, 1610),
+    (0.126204, 1679),
+    (0.126204, 1752),
+    (0.126204, 1825),
+    (0.120381, 1898),
+    (0.120381, 1975),
+    (0.120381, 2052),
+    (0.120381, 2129),
+    (0.112962, 2206),
+    (0.112962, 2288),
+    (0.112962, 2370),
+    (0.107450, 2452),
+    (0.107450, 2538),
+    (0.107450, 2624),
+    (0.107450, 2710),
+    (0.100343, 2796),
+    (0.100343, 2887),
+    (0.100343, 2978),
+    (0.095126, 3069),
+    (0.095126, 3164),
+    (0.095126, 3259),
+    (0.095126, 3354),
+    (0.088276, 3449),
+    (0.088276, 3549),
+    (0.088276, 3649),
+    (0.088276, 3749),
+    (0.081523, 3849),
+    (0.081523, 3954),
+    (0.081523, 4059),
+    (0.081523, 4164),
+    (0.074861, 4269),
+    (0.074861, 4379),
+    (0.074861, 4489),
+    (0.074861, 4599),
+    (0.068290, 4709),
+    (0.068290, 4824),
+    (0.068290, 4939),
+    (0.068290, 5054),
+    (0.063573, 5169),
+    (0.063573, 5288),
+    (0.063573, 5407),
+    (0.063573, 5526),
+    (0.057219, 5645),
+    (0.057219, 5769),
+    (0.057219, 5893),
+    (0.057219, 6017),
+    (0.050985, 6141),
+    (0.050985, 6270),
+    (0.050985, 6399),
+    (0.050985, 6528),
+    (0.050985, 6657),
+    (0.044859, 6786),
+    (0.044859, 6920),
+    (0.044859, 7054),
+    (0.044859, 7188),
+    (0.040571, 7322),
+    (0.040571, 7460),
+    (0.040571, 7598),
+    (0.040571, 7736),
+    (0.036339, 7874),
+    (0.036339, 8016),
+    (0.036339, 8158),
+    (0.036339, 8300),
+    (0.032139, 8442),
+    (0.032139, 8588),
+    (0.032139, 8734),
+    (0.032139, 8880),
+    (0.027947, 9026),
+    (0.027947, 9176),
+    (0.027947, 9326),
+    (0.023739, 9476),
+    (0.023739, 9630),
+    (0.023739, 9784),
+    (0.023739, 9938),
+    (0.019504, 10092),
+    (0.019504, 10250),
+    (0.019504, 10408),
+    (0.016976, 10566),
+    (0.016976, 10727),
+    (0.016976, 10888),
+    (0.016976, 11049),
+    (0.012639, 11210),
+    (0.012639, 11375),
+    (0.012639, 11540),
+    (0.009991, 11705),
+    (0.009991, 11873),
+    (0.009991, 12041),
+    (0.009016, 12209),
+    (0.009016, 12379),
+    (0.009016, 12549),
+    (0.006217, 12719),
+    (0.006217, 12892),
+    (0.005097, 13065),
+    (0.005097, 13240),
+    (0.005097, 13415),
+    (0.003909, 13590),
+    (0.003909, 13767),
+    (0.002340, 13944),
+    (0.002389, 14121),
+    (0.001068, 14291),
+    (0.001653, 14455),
+    (0.000717, 14612),
+    (0.001614, 14762),
+    (0.000270, 14905),
+    (0.000484, 15041),
+    (0.001103, 15170),
+    (0.001242, 15293),
+    (0.001188, 15408),
+    (0.001011, 15517),
+    (0.000709, 15620),
+    (0.000301, 15717),
+    (0.002416, 15806),
+    (0.003251, 15888),
+    (0.003246, 15964),
+    (0.004141, 16033),
+    (0.005963, 16095),
+    (0.008839, 16150),
+    (0.010490, 16197),
+    (0.016994, 16237),
+    (0.023659, 16268),
+];
+
+/// Returns the (u', v') coordinates of the center of the cell at `index` in the table.
+fn uv_decode(index: u32) -> (f64, f64) {
+    if index >= UV_NDIVS {
+        return UV_NEUTRAL;
+    }
+
+    let row = UV_ROWS.partition_point(|&(_, first)| first as u32 <= index) - 1;
+    let (ustart, first) = UV_ROWS[row];
+    let u = ustart as f64 + ((index - first as u32) as f64 + 0.5) * UV_SQSIZ as f64;
+    let v = UV_VSTART as f64 + (row as f64 + 0.5) * UV_SQSIZ as f64;
+    (u, v)
+}
+
+/// Decodes the XYZ values of a LogLuv pixel encoded in the 24 least significant bits.
+///
+/// The values are the same as those of libtiff.
+pub fn logluv24_to_xyz(pixel: u32) -> [f32; 3] {
+    let luminance = logl10_to_y(pixel >> 14 & 0x3ff);
+    if luminance <= 0.0 {
+        return [0.0; 3];
+    }
+
+    let (u, v) = uv_decode(pixel & 0x3fff);
+    uv_to_xyz(u, v, luminance)
+}
+
+/// Decodes the XYZ values of a LogLuv pixel encoded in 32 bits.
+pub fn logluv32_to_xyz(pixel: u32) -> [f32; 3] {
+    let luminance = logl16_to_y((pixel >> 16) as u16) as f64;
+    if luminance == 0.0 {
+        return [0.0; 3];
+    }
+
+    let u = ((pixel >> 8 & 0xff) as f64 + 0.5) / UV_SCALE;
+    let v = ((pixel & 0xff) as f64 + 0.5) / UV_SCALE;
+    uv_to_xyz(u, v, luminance)
+}
+
+/// Returns the XYZ values of the given (u', v') coordinates and luminance.
+fn uv_to_xyz(u: f64, v: f64, luminance: f64) -> [f32; 3] {
+    let s = 1.0 / (6.0 * u - 16.0 * v + 12.0);
+    let (x, y) = (9.0 * u * s, 4.0 * v * s);
+    [
+        (x / y * luminance) as f32,
+        luminance as f32,
+        ((1.0 - x - y) / y * luminance) as f32,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_logl16() {
+        assert_eq!(logl16_to_y(0), 0.0);
+        assert_eq!(logl16_to_y(0x8000), 0.0);
+
+        // The exponent 16384 is centered on a luminance of 1.
+        let y = logl16_to_y(16384);
+        assert!((y - 1.0).abs() < 2e-3, "{y}");
+        assert_eq!(logl16_to_y(0x8000 | 16384), -y);
+
+        // Each step of 256 doubles the luminance.
+        let ratio = logl16_to_y(16384 + 256) / y;
+        assert!((ratio - 2.0).abs() < 1e-5, "{ratio}");
+    }
+
+    #[test]
+    fn decode_logluv32() {
+        assert_eq!(logluv32_to_xyz(0x0000_56c2), [0.0; 3]);
+
+        // The white point E has the coordinates (u', v') = (4/19, 9/19).
+        let pixel = (16384 << 16) | (86 << 8) | 194;
+        let [x, y, z] = logluv32_to_xyz(pixel);
+        assert!((y - 1.0).abs() < 2e-3, "{y}");
+        assert!((x / y - 1.0).abs() < 1e-2, "{x}");
+        assert!((z / y - 1.0).abs() < 1e-2, "{z}");
+    }
+
+    #[test]
+    fn decode_logluv24() {
+        assert_eq!(logluv24_to_xyz(0x00_0064), [0.0; 3]);
+
+        // The values decoded by libtiff.
+        assert_eq!(
+            logluv24_to_xyz(0x80_0000),
+            [1.8867925, 0.06283937, 9.143449]
+        );
+        assert_eq!(
+            logluv24_to_xyz(0x80_1f40),
+            [0.18030214, 0.06283937, 0.12749408]
+        );
+        assert_eq!(
+            logluv24_to_xyz(0xaf_3fa0),
+            [0.17644586, 0.48140147, -5.6197996e-6]
+        );
+        assert_eq!(logluv24_to_xyz(0xff_f039), [36.793976, 15.91359, 8.952228]);
+
+        // The indices outside of the table are the white point E.
+        assert_eq!(logluv24_to_xyz(0x4b_3fac), [0.0063251373; 3]);
+    }
+
+    #[test]
+    fn uv_table() {
+        // Each row holds at least a cell, the last one ends the table.
+        for rows in UV_ROWS.windows(2) {
+            assert!(rows[0].1 < rows[1].1, "{rows:?}");
+        }
+        assert!((UV_ROWS[162].1 as u32) < UV_NDIVS);
+
+        // The first cell of a row follows the last cell of the previous row.
+        let (u, v) = uv_decode(UV_ROWS[1].1 as u32);
+        let (last_u, last_v) = uv_decode(UV_ROWS[1].1 as u32 - 1);
+        assert!(v > last_v && u < last_u, "{u} {v}");
+
+        assert_eq!(uv_decode(UV_NDIVS), UV_NEUTRAL);
+    }
+}
@@ -0,0 +1,354 @@
+//! Validity masks of the pixels.
+//!
+//! The [`MaskReader`] tells for each pixel of a window whether it holds valid data. The validity
+//! is derived from the first available source, in this order:
+//!
+//! 1. A transparency mask image of the same dimensions, as written by GDAL for internal masks.
+//!    The mask of an overview is the mask overview with the same dimensions.
+//! 2. The nodata value of the `GdalNoData` tag, the pixels whose samples all equal this value are
+//!    invalid.
+//! 3. The first alpha sample of `ExtraSamples`, the fully transparent pixels are invalid.
+//! 4. For floating-point samples, the pixels with a NaN sample are invalid.
+//!
+//! Otherwise all the pixels are valid. The masks hold a byte for each pixel, 255 if the pixel is
+//! valid and 0 otherwise.
+//!
+//! ```
+//! use aira_tiff::{mask::MaskReader, reader::Window, TiffFile};
+//!
+//! let file = std::fs::File::open("tests/images/minisblack-2c-8b-alpha.tiff")?;
+//! let mut reader = std::io::BufReader::new(file);
+//! let tiff = TiffFile::new(&mut reader)?;
+//!
+//! let masks = MaskReader::from_file(&tiff, 0)?;
+//! let window = Window::new((0, 0), (16, 16));
+//! let mut mask = vec![0u8; 16 * 16];
+//! masks.read_mask(&mut reader, window, &mut mask)?;
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use std::io::{Read, Seek};
+
+use crate::{
+    compression::DecompressReader,
+    error::ErrorContext,
+    float,
+    metadata::Layout,
+    reader::{store_value, PixelReader, Window},
+    unpack::Unpacker,
+    ByteOrder, Error, ExtraSample, Metadata, Predictor, SampleFormat, TiffFile, Unsupported,
+};
+
+/// The value of a valid pixel in a mask.
+pub const VALID: u8 = 255;
+
+/// The value of an invalid pixel in a mask.
+pub const INVALID: u8 = 0;
+
+/// The source of the validity of the pixels.
+#[derive(Clone, Copy, Debug)]
+pub enum MaskSource<'tiff> {
+    /// A transparency mask image, the pixels with a non-zero value are valid.
+    Mask(&'tiff Metadata),
+    /// The pixels whose samples all equal the nodata value, cast to the type of the samples, are
+    /// invalid.
+    NoData(f64),
+    /// The pixels whose alpha sample at the given index is zero are invalid.
+    Alpha(u16),
+    /// The pixels with a NaN sample are invalid.
+    Nan,
+    /// All the pixels are valid.
+    AllValid,
+}
+
+/// Reads the validity masks of the pixels of an image.
+#[derive(Clone, Debug)]
+pub struct MaskReader<'tiff> {
+    metadata: &'tiff Metadata,
+    source: MaskSource<'tiff>,
+    /// The reader of the samples of the image, when the validity depends on them.
+    pixels: Option<PixelReader<'tiff>>,
+}
+
+impl<'tiff> MaskReader<'tiff> {
+    /// Creates a new instance of [`MaskReader`] for the image described by `metadata`, stored
+    /// with the given byte order, and with the given transparency mask image if any.
+    pub fn new(
+        metadata: &'tiff Metadata,
+        byteorder: ByteOrder,
+        mask: Option<&'tiff Metadata>,
+    ) -> Result<Self, Error> {
+        if let Some(mask) = mask {
+            check_mask(metadata, mask).with_context(|| "Invalid transparency mask")?;
+            return Ok(Self {
+                metadata,
+                source: MaskSource::Mask(mask),
+                pixels: None,
+            });
+        }
+
+        let samples = metadata.samples();
+        let extra_samples = metadata.extra_samples();
+        let alpha = extra_samples
+            .iter()
+            .position(|&extra| {
+                matches!(
+                    extra,
+                    ExtraSample::ASSOCIATED_ALPHA | ExtraSample::UNASSOCIATED_ALPHA
+                )
+            })
+            .map(|position| (samples.len() - extra_samples.len() + position) as u16);
+        let source = match (metadata.nodata(), alpha) {
+            (Some(nodata), _) => MaskSource::NoData(nodata),
+            (None, Some(alpha)) => MaskSource::Alpha(alpha),
+            _ if samples
+                .iter()
+                .any(|sample| sample.format == SampleFormat::FLOAT) =>
+            {
+                MaskSource::Nan
+            }
+            _ => MaskSource::AllValid,
+        };
+        let pixels = match source {
+            MaskSource::AllValid => None,
+            _ => Some(PixelReader::new(metadata, byteorder)?),
+        };
+
+        Ok(Self {
+            metadata,
+            source,
+            pixels,
+        })
+    }
+
+    /// Creates a new instance of [`MaskReader`] for the image at the given index in `tiff`, with
+    /// its transparency mask if any.
+    pub fn from_file(tiff: &'tiff TiffFile, index: usize) -> Result<Self, Error> {
+        let Some(metadata) = tiff.metadata(index) else {
+            return Err(Error::from_args(format_args!(
+                "Image {index} is out of range, the file has {} images",
+                tiff.len()
+            )));
+        };
+        let mask = tiff.mask(index).and_then(|mask| tiff.metadata(mask));
+        Self::new(metadata, tiff.byteorder(), mask)
+    }
+
+    /// Returns the source of the validity of the pixels.
+    pub fn source(&self) -> MaskSource<'tiff> {
+        self.source
+    }
+
+    /// Reads the mask of `window` into `dst`, with [`VALID`] or [`INVALID`] for each pixel.
+    ///
+    /// The window is given in the image as stored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not the number of pixels of the window.
+    pub fn read_mask<R>(&self, reader: &mut R, window: Window, dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        let (width, length) = self.metadata.dimensions;
+        if window.origin.0 as u64 + window.size.0 as u64 > width as u64
+            || window.origin.1 as u64 + window.size.1 as u64 > length as u64
+        {
+            return Err(Error::from_args(format_args!(
+                "Window {window:?} exceeds the image of {width}x{length} pixels"
+            )));
+        }
+        assert_eq!(
+            dst.len(),
+            window.size.0 as usize * window.size.1 as usize,
+            "destination slice does not match the size of the window"
+        );
+
+        let pixels = match (self.source, &self.pixels) {
+            (MaskSource::Mask(mask), _) => return read_mask_image(mask, reader, window, dst),
+            (_, Some(pixels)) => pixels,
+            _ => {
+                dst.fill(VALID);
+                return Ok(());
+            }
+        };
+
+        let bands = match self.source {
+            MaskSource::Alpha(alpha) => vec![alpha],
+            _ => (0..self.metadata.samples().len() as u16).collect(),
+        };
+        let formats = bands
+            .iter()
+            .map(|&band| self.metadata.samples()[band as usize].format)
+            .collect::<Vec<_>>();
+        let mut samples = vec![0u8; pixels.window_len(window, bands.len())];
+        pixels.read_window(reader, window, &bands, &mut samples)?;
+
+        let bytespersample = pixels.bytes_per_sample();
+        let pixel_len = bands.len() * bytespersample;
+
+        // The nodata value is cast to the type of each sample, as the sparse chunks are filled.
+        let nodata = match self.source {
+            MaskSource::NoData(nodata) => {
+                let mut pixel = vec![0u8; pixel_len];
+                for (dst, &format) in pixel.chunks_exact_mut(bytespersample).zip(&formats) {
+                    store_value(dst, nodata, format);
+                }
+                pixel
+            }
+            _ => Vec::new(),
+        };
+
+        for (dst, pixel) in dst.iter_mut().zip(samples.chunks_exact(pixel_len)) {
+            let mut values = pixel
+                .chunks_exact(bytespersample)
+                .zip(&formats)
+                .map(|(bytes, &format)| sample_value(bytes, format));
+            let valid = match self.source {
+                MaskSource::NoData(nodata) if nodata.is_nan() => !values.all(f64::is_nan),
+                MaskSource::NoData(_) => !pixel
+                    .chunks_exact(bytespersample)
+                    .zip(nodata.chunks_exact(bytespersample))
+                    .zip(&formats)
+                    .all(|((sample, nodata), &format)| same_value(sample, nodata, format)),
+                MaskSource::Alpha(_) => values.any(|value| value != 0.0),
+                _ => !values.any(f64::is_nan),
+            };
+            *dst = match valid {
+                true => VALID,
+                false => INVALID,
+            };
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns an error if `mask` cannot be read as the transparency mask of `metadata`.
+fn check_mask(metadata: &Metadata, mask: &Metadata) -> Result<(), Error> {
+    if mask.dimensions != metadata.dimensions {
+        return Err(Error::from_args(format_args!(
+            "Mask of {}x{} pixels does not match the image of {}x{} pixels",
+            mask.dimensions.0, mask.dimensions.1, metadata.dimensions.0, metadata.dimensions.1
+        )));
+    }
+    let &[sample] = mask.samples() else {
+        return Err(Error::from_args(format_args!(
+            "Mask pixels with {} samples cannot be read",
+            mask.samples().len()
+        )));
+    };
+    if !(1..=8).contains(&sample.bits) {
+        return Err(
+            Error::from(Unsupported::BitsPerSample(sample.bits)).with_context(|| {
+                format!(
+                    "Mask pixels with samples of {} bits cannot be read",
+                    sample.bits
+                )
+            }),
+        );
+    }
+    if mask.predictor != Predictor::NONE {
+        return Err(Error::from(Unsupported::Predictor(mask.predictor))
+            .with_context(|| "Mask pixels cannot be read with a predictor"));
+    }
+    Ok(())
+}
+
+/// Reads `window` of the transparency mask image described by `mask`.
+fn read_mask_image<R>(
+    mask: &Metadata,
+    reader: &mut R,
+    window: Window,
+    dst: &mut [u8],
+) -> Result<(), Error>
+where
+    R: Read + Seek,
+{
+    let bits = mask.samples()[0].bits;
+    let mut packed = Vec::new();
+    let mut values = Vec::new();
+    for chunk in mask.window_chunks(reader, 0, window)? {
+        let Some(overlap) = Window::new(chunk.origin, chunk.size).intersection(&window) else {
+            continue;
+        };
+
+        // The last strip is not padded.
+        let (width, length) = match mask.layout {
+            Layout::Strips { .. } => (mask.dimensions.0, chunk.size.1),
+            Layout::Tiles { width, length } => (width, length),
+        };
+        let unpacker = Unpacker::new(width, 1, bits, mask.fill_order)?;
+        packed.resize(unpacker.row_size() * length as usize, 0);
+        values.resize(unpacker.row_len() * length as usize, 0);
+        if chunk.is_sparse() {
+            // The sparse chunks of a mask hold only invalid pixels.
+            values.fill(0);
+        } else {
+            reader.seek(std::io::SeekFrom::Start(chunk.offset))?;
+            DecompressReader::new(reader.take(chunk.byte_count), mask.compression)?
+                .read_exact(&mut packed)
+                .map_err(Error::from)
+                .and_then(|_| unpacker.unpack_u8(&packed, &mut values))
+                .with_context(|| {
+                    format!(
+                        "Failed to read chunk at offset {} with {} bytes",
+                        chunk.offset, chunk.byte_count
+                    )
+                })?;
+        }
+
+        let (src_x, src_y) = (
+            (overlap.origin.0 - chunk.origin.0) as usize,
+            (overlap.origin.1 - chunk.origin.1) as usize,
+        );
+        let (dst_x, dst_y) = (
+            (overlap.origin.0 - window.origin.0) as usize,
+            (overlap.origin.1 - window.origin.1) as usize,
+        );
+        let src_rows = values.chunks_exact(width as usize).skip(src_y);
+        let dst_rows =
+            dst[dst_y * window.size.0 as usize..].chunks_exact_mut(window.size.0 as usize);
+        for (src, dst) in src_rows.zip(dst_rows).take(overlap.size.1 as usize) {
+            let src = &src[src_x..src_x + overlap.size.0 as usize];
+            let dst = &mut dst[dst_x..dst_x + overlap.size.0 as usize];
+            for (dst, &value) in dst.iter_mut().zip(src) {
+                *dst = match value {
+                    0 => INVALID,
+                    _ => VALID,
+                };
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns whether two samples of the given format stored in native byte order are equal.
+///
+/// Floating-point samples are compared as numbers, so that both zeros are equal.
+fn same_value(a: &[u8], b: &[u8], format: SampleFormat) -> bool {
+    match format {
+        SampleFormat::FLOAT => sample_value(a, format) == sample_value(b, format),
+        _ => a == b,
+    }
+}
+
+/// Returns the value of a sample stored in native byte order.
+fn sample_value(bytes: &[u8], format: SampleFormat) -> f64 {
+    match (format, bytes.len()) {
+        (SampleFormat::FLOAT, 2) => {
+            float::f16_to_f32(u16::from_ne_bytes([bytes[0], bytes[1]])) as f64
+        }
+        (SampleFormat::FLOAT, 4) => f32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+        (SampleFormat::FLOAT, _) => f64::from_ne_bytes(bytes.try_into().unwrap()),
+        (SampleFormat::SIGNED, 1) => bytes[0] as i8 as f64,
+        (SampleFormat::SIGNED, 2) => i16::from_ne_bytes([bytes[0], bytes[1]]) as f64,
+        (SampleFormat::SIGNED, 4) => i32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+        (SampleFormat::SIGNED, _) => i64::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+        (_, 1) => bytes[0] as f64,
+        (_, 2) => u16::from_ne_bytes([bytes[0], bytes[1]]) as f64,
+        (_, 4) => u32::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+        (_, _) => u64::from_ne_bytes(bytes.try_into().unwrap()) as f64,
+    }
+}
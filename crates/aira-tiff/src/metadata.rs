@@ -9,9 +9,10 @@ use chrono::NaiveDateTime as DateTime;
 use jiff::civil::DateTime;
 
 use crate::{
-    decoder, entry::EntryRef, error::ErrorContext, Compression, DType, Entry, Error,
-    Interpretation, PlanarConfiguration, Predictor, Ratio, ResolutionUnit, SampleFormat,
-    SubfileType, Tag,
+    decoder, entry::EntryRef, error::ErrorContext, reader::Window, ByteOrder, Compression, DType,
+    Entry, EntryPart, Error, ExtraSample, FillOrder, Interpretation, Location, Orientation,
+    PlanarConfiguration, Predictor, Ratio, ResolutionUnit, SampleFormat, SubfileType, Tag,
+    YCbCrPositioning,
 };
 
 /// Metadata of TIFF directory.
@@ -33,8 +34,36 @@ pub struct Metadata {
     pub configuration: PlanarConfiguration,
     /// The resolution of the image.
     pub resolution: Option<Resolution>,
+    /// The orientation of the image with respect to the rows and columns.
+    pub orientation: Orientation,
+    /// The logical order of bits within a byte.
+    pub fill_order: FillOrder,
     /// Specify how to interpret the pixel data.
     samples: Vec<Sample>,
+    /// The meaning of the extra samples, the last samples of each pixel.
+    extra_samples: Vec<ExtraSample>,
+    /// The minimum and maximum values of each sample.
+    sample_values: Vec<(u64, u64)>,
+    /// The minimum and maximum values of each sample, whatever its format.
+    sample_ranges: Vec<(f64, f64)>,
+    /// The red, green and blue values of the palette.
+    color_map: Option<Vec<u16>>,
+    /// The names of the inks of separated pixels.
+    ink_names: Vec<String>,
+    /// The name of the document from which the image was scanned.
+    document_name: Option<String>,
+    /// The name of the page from which the image was scanned.
+    page_name: Option<String>,
+    /// The page number and the total number of pages.
+    page_number: Option<(u16, u16)>,
+    /// The offset of the image from the top-left of the page.
+    position: Option<(Ratio<u32>, Ratio<u32>)>,
+    /// The manufacturer of the scanner, camera or other input device.
+    make: Option<String>,
+    /// The model of the scanner, camera or other input device.
+    model: Option<String>,
+    /// The value of the samples of the pixels without data.
+    nodata: Option<f64>,
     /// Person who created the image.
     artist: Option<String>,
     /// Copyright notice.
@@ -46,6 +75,11 @@ pub struct Metadata {
     /// Name and version number of the software package(s) used to create the image.
     software: Option<String>,
 
+    /// The horizontal and vertical subsampling factors of the chroma samples.
+    ycbcr_subsampling: (u16, u16),
+    /// The position of the chroma samples relative to the luma samples.
+    ycbcr_positioning: YCbCrPositioning,
+
     /// Date and time of image creation.
     #[cfg(any(feature = "chrono", feature = "jiff"))]
     datetime: Option<DateTime>,
@@ -54,26 +88,23 @@ pub struct Metadata {
 
     /// All the others entries in the directory.
     entries: BTreeMap<Tag, Entry>,
-    /// The locations of the chunks that make up the image.
+    /// The locations of the chunks that make up the image, empty when they are deferred.
     chunks: Vec<ChunkLoc>,
+    /// The arrays of offsets and byte counts of the chunks, when they are not decoded.
+    deferred_chunks: Option<DeferredChunks>,
+    /// The problems recovered while decoding in lenient mode.
+    warnings: Vec<Error>,
 }
 
 impl Metadata {
-    /// Returns the image width.
+    /// Decodes the metadata of a TIFF directory.
+    ///
+    /// Use [`MetadataBuilder`] to decode malformed images.
     pub fn from_decoder<R>(directory: decoder::Directory<'_, R>) -> Result<Self, Error>
     where
         R: std::io::Read + std::io::Seek,
     {
-        let mut entries = directory.entries();
-        let mut builder = MetadataBuilder::default();
-        while let Some(entry) = entries.next_entry()? {
-            let tag = entry.tag;
-            builder
-                .push_entry(entry)
-                .with_context(|| format!("Invalid {tag:?}"))?;
-        }
-
-        builder.build()
+        MetadataBuilder::default().decode(directory)
     }
 
     /// Returns a slice of samples that make up the pixel data.
@@ -81,6 +112,85 @@ impl Metadata {
         &self.samples
     }
 
+    /// Returns the meaning of the extra samples, which are the last samples of each pixel.
+    pub fn extra_samples(&self) -> &[ExtraSample] {
+        &self.extra_samples
+    }
+
+    /// Returns the minimum and maximum values of each sample, as given by `MinSampleValue` and
+    /// `MaxSampleValue`.
+    ///
+    /// By default the values span the range allowed by the number of bits of the sample.
+    pub fn sample_values(&self) -> &[(u64, u64)] {
+        &self.sample_values
+    }
+
+    /// Returns the minimum and maximum values of each sample, as given by `SMinSampleValue` and
+    /// `SMaxSampleValue`.
+    ///
+    /// Unlike [`Metadata::sample_values`], signed and floating-point values are supported. By
+    /// default the values span the range of the format of the sample.
+    pub fn sample_ranges(&self) -> &[(f64, f64)] {
+        &self.sample_ranges
+    }
+
+    /// Returns the palette of the image, if available.
+    pub fn color_map(&self) -> Option<ColorMap<'_>> {
+        let color_map = self.color_map.as_deref()?;
+        let count = color_map.len() / 3;
+        Some(ColorMap {
+            red: &color_map[..count],
+            green: &color_map[count..2 * count],
+            blue: &color_map[2 * count..],
+        })
+    }
+
+    /// Returns the names of the inks of separated pixels, empty if not available.
+    pub fn ink_names(&self) -> &[String] {
+        &self.ink_names
+    }
+
+    /// Returns the name of the document from which the image was scanned, if available.
+    pub fn document_name(&self) -> Option<&str> {
+        self.document_name.as_deref()
+    }
+
+    /// Returns the name of the page from which the image was scanned, if available.
+    pub fn page_name(&self) -> Option<&str> {
+        self.page_name.as_deref()
+    }
+
+    /// Returns the page number starting from 0, and the total number of pages, if available.
+    ///
+    /// A total of 0 means that the number of pages is unknown.
+    pub fn page_number(&self) -> Option<(u16, u16)> {
+        self.page_number
+    }
+
+    /// Returns the X and Y offsets of the image from the top-left of the page, in units of the
+    /// resolution, if available.
+    pub fn position(&self) -> Option<(Ratio<u32>, Ratio<u32>)> {
+        self.position
+    }
+
+    /// Returns the manufacturer of the input device, if available.
+    pub fn make(&self) -> Option<&str> {
+        self.make.as_deref()
+    }
+
+    /// Returns the model of the input device, if available.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Returns the value of the samples of the pixels without data, as given by the `GdalNoData`
+    /// tag, if available.
+    ///
+    /// A value which is not a number is ignored, the entry is kept as a custom entry.
+    pub fn nodata(&self) -> Option<f64> {
+        self.nodata
+    }
+
     /// Returns a string containing the name of the person who created the image, if available.
     pub fn artist(&self) -> Option<&str> {
         self.artist.as_deref()
@@ -118,6 +228,19 @@ impl Metadata {
         self.datetime.as_deref()
     }
 
+    /// Returns the horizontal and vertical subsampling factors of the chroma samples.
+    ///
+    /// The factors are `(1, 1)` unless the image data is in YCbCr color space, then the chroma
+    /// samples of each block of pixels are stored along with their luma samples in a data unit.
+    pub fn ycbcr_subsampling(&self) -> (u16, u16) {
+        self.ycbcr_subsampling
+    }
+
+    /// Returns the position of the chroma samples relative to the luma samples.
+    pub fn ycbcr_positioning(&self) -> YCbCrPositioning {
+        self.ycbcr_positioning
+    }
+
     /// Returns a tuple with the default width and height of chunks.
     ///
     /// Any chunk in the image will be at most this size, for the size of image data use
@@ -130,17 +253,146 @@ impl Metadata {
     }
 
     /// Returns the number of chunks that make up the image.
+    ///
+    /// With [`PlanarConfiguration::PLANAR`], each plane is made of
+    /// [`chunks_per_plane`](Self::chunks_per_plane) chunks.
     pub fn chunks_count(&self) -> usize {
-        self.chunks.len()
+        match &self.deferred_chunks {
+            Some(deferred) => deferred.len,
+            None => self.chunks.len(),
+        }
+    }
+
+    /// Returns `true` if the offsets and byte counts of the chunks are not decoded, see
+    /// [`MetadataBuilder::defer_chunks`].
+    pub fn has_deferred_chunks(&self) -> bool {
+        self.deferred_chunks.is_some()
+    }
+
+    /// Returns the number of planes of the image data.
+    ///
+    /// Each sample is stored in a separate plane with [`PlanarConfiguration::PLANAR`], otherwise
+    /// all the samples are stored in a single plane.
+    pub fn planes_count(&self) -> usize {
+        match self.configuration {
+            PlanarConfiguration::PLANAR => self.samples.len(),
+            _ => 1,
+        }
+    }
+
+    /// Returns the number of chunks that make up a single plane.
+    pub fn chunks_per_plane(&self) -> usize {
+        let (image_width, image_length) = self.dimensions;
+        self.layout.expected_chunks_count(image_width, image_length)
     }
 
     /// Returns an iterator over the chunks that make up the image.
-    pub fn chunks(&self) -> Chunks<'_> {
-        Chunks {
+    ///
+    /// The chunks are ordered by plane, see [`Chunk::plane`].
+    ///
+    /// Returns `None` if the chunks are deferred, use [`window_chunks`](Self::window_chunks)
+    /// instead.
+    pub fn chunks(&self) -> Option<Chunks<'_>> {
+        if self.has_deferred_chunks() {
+            return None;
+        }
+
+        Some(Chunks {
             image_size: self.dimensions,
             chunk_size: self.chunk_size(),
+            first: 0,
             iter: self.chunks.iter().enumerate(),
+        })
+    }
+
+    /// Returns an iterator over the chunks that make up the given plane.
+    ///
+    /// Returns `None` if the chunks are deferred, use [`window_chunks`](Self::window_chunks)
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `plane` is not less than [`planes_count`](Self::planes_count).
+    pub fn plane_chunks(&self, plane: u16) -> Option<Chunks<'_>> {
+        assert!(
+            (plane as usize) < self.planes_count(),
+            "plane {plane} is out of range, the image has {} planes",
+            self.planes_count()
+        );
+        if self.has_deferred_chunks() {
+            return None;
         }
+
+        let chunks_per_plane = self.chunks_per_plane();
+        let first = plane as usize * chunks_per_plane;
+        let chunks = &self.chunks[first..first + chunks_per_plane];
+        Some(Chunks {
+            image_size: self.dimensions,
+            chunk_size: self.chunk_size(),
+            first,
+            iter: chunks.iter().enumerate(),
+        })
+    }
+
+    /// Returns the chunks of the given plane overlapping `window`, in the order of
+    /// [`plane_chunks`](Self::plane_chunks).
+    ///
+    /// When the chunks are deferred, only the values covering the window are read from the
+    /// offsets and byte counts arrays, a slice for each row of chunks. Otherwise `reader` is not
+    /// used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `plane` is not less than [`planes_count`](Self::planes_count), or if the window
+    /// exceeds the image.
+    pub fn window_chunks<R>(
+        &self,
+        reader: &mut R,
+        plane: u16,
+        window: Window,
+    ) -> Result<Vec<Chunk>, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        assert!(
+            (plane as usize) < self.planes_count(),
+            "plane {plane} is out of range, the image has {} planes",
+            self.planes_count()
+        );
+        let (image_width, image_length) = self.dimensions;
+        assert!(
+            window.origin.0 as u64 + window.size.0 as u64 <= image_width as u64
+                && window.origin.1 as u64 + window.size.1 as u64 <= image_length as u64,
+            "window {window:?} exceeds the image of {image_width}x{image_length} pixels"
+        );
+        if window.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let (chunk_width, chunk_length) = self.chunk_size();
+        let chunks_along_width = image_width.div_ceil(chunk_width) as usize;
+        let first = plane as usize * self.chunks_per_plane();
+        let columns = (window.origin.0 / chunk_width) as usize
+            ..=((window.origin.0 + window.size.0 - 1) / chunk_width) as usize;
+        let rows = (window.origin.1 / chunk_length) as usize
+            ..=((window.origin.1 + window.size.1 - 1) / chunk_length) as usize;
+
+        let mut chunks = Vec::new();
+        for row in rows {
+            let start = first + row * chunks_along_width;
+            let range = start + columns.start()..start + columns.end() + 1;
+            let locs = match &self.deferred_chunks {
+                Some(deferred) => &deferred.read(reader, range.clone())?,
+                None => &self.chunks[range.clone()],
+            };
+            chunks.extend(Chunks {
+                image_size: self.dimensions,
+                chunk_size: self.chunk_size(),
+                first: range.start,
+                iter: locs.iter().enumerate(),
+            });
+        }
+        Ok(chunks)
     }
 
     /// Returns an iterator over the custom entries in the metadata.
@@ -152,6 +404,13 @@ impl Metadata {
     pub fn custom_entry(&self, tag: Tag) -> Option<EntryRef<'_>> {
         self.entries.get(&tag).map(Entry::as_ref)
     }
+
+    /// Returns the problems recovered while decoding the metadata in lenient mode.
+    ///
+    /// See [`MetadataBuilder`] for the list of the recovered problems.
+    pub fn warnings(&self) -> &[Error] {
+        &self.warnings
+    }
 }
 
 /// A single component of a pixel.
@@ -168,6 +427,34 @@ impl Sample {
     pub fn new(format: SampleFormat, bits: u16) -> Self {
         Self { format, bits }
     }
+
+    /// Returns the minimum and maximum values that can be represented by this sample.
+    fn range(&self) -> (f64, f64) {
+        let bits = self.bits.clamp(1, 64) as i32;
+        match self.format {
+            SampleFormat::SIGNED | SampleFormat::COMPLEX_SIGNED => {
+                let max = 2f64.powi(bits - 1);
+                (-max, max - 1.0)
+            }
+            SampleFormat::FLOAT | SampleFormat::COMPLEX_FLOAT => match bits {
+                16 => (-65504.0, 65504.0),
+                32 => (f32::MIN as f64, f32::MAX as f64),
+                _ => (f64::MIN, f64::MAX),
+            },
+            _ => (0.0, 2f64.powi(bits) - 1.0),
+        }
+    }
+}
+
+/// The palette of the image, each sample value indexes the red, green and blue values.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ColorMap<'tiff> {
+    /// The red values, 0 is the minimum intensity and 65535 the maximum.
+    pub red: &'tiff [u16],
+    /// The green values.
+    pub green: &'tiff [u16],
+    /// The blue values.
+    pub blue: &'tiff [u16],
 }
 
 /// The resolution of the image.
@@ -207,11 +494,112 @@ struct ChunkLoc {
     byte_count: u64,
 }
 
+/// The location of an array of offsets or byte counts of the chunks, which is read on demand.
+#[derive(Debug, Clone, Copy)]
+struct DeferredArray {
+    /// The offset of the array from the beginning of the file.
+    offset: u64,
+    /// The datatype of the values, an unsigned integer.
+    dtype: DType,
+    /// The number of values.
+    len: usize,
+    byteorder: ByteOrder,
+}
+
+impl DeferredArray {
+    /// Reads the values at the given indices.
+    fn read<R>(&self, reader: &mut R, indices: std::ops::Range<usize>) -> Result<Vec<u64>, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        use aira_byteorder::{ReadBytesExt, BE, LE};
+
+        macro_rules! read_vec {
+            ($ty:ty, $read_into:ident) => {{
+                let mut values = vec![0 as $ty; indices.len()];
+                match self.byteorder {
+                    ByteOrder::BigEndian => reader.$read_into::<BE>(&mut values)?,
+                    ByteOrder::LittleEndian => reader.$read_into::<LE>(&mut values)?,
+                }
+                values.into_iter().map(|value| value as u64).collect()
+            }};
+        }
+
+        let offset = self.offset + indices.start as u64 * self.dtype.size();
+        reader.seek(std::io::SeekFrom::Start(offset))?;
+        let values = match self.dtype {
+            DType::Short => read_vec!(u16, read_u16_into),
+            DType::Long | DType::Ifd => read_vec!(u32, read_u32_into),
+            _ => read_vec!(u64, read_u64_into),
+        };
+        Ok(values)
+    }
+}
+
+/// The arrays of offsets and byte counts of the chunks, when their decoding is deferred.
+#[derive(Debug, Clone, Copy)]
+struct DeferredChunks {
+    offsets: DeferredArray,
+    byte_counts: DeferredArray,
+    /// The number of chunks of the image, any value beyond is ignored.
+    len: usize,
+}
+
+impl DeferredChunks {
+    /// Reads the locations of the chunks at the given indices.
+    fn read<R>(
+        &self,
+        reader: &mut R,
+        indices: std::ops::Range<usize>,
+    ) -> Result<Vec<ChunkLoc>, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        let context = || {
+            format!(
+                "Failed to read the offsets and byte counts of the chunks {}..{}",
+                indices.start, indices.end
+            )
+        };
+        let offsets = self
+            .offsets
+            .read(reader, indices.clone())
+            .with_context(context)?;
+        let byte_counts = self
+            .byte_counts
+            .read(reader, indices.clone())
+            .with_context(context)?;
+        Ok(offsets
+            .into_iter()
+            .zip(byte_counts)
+            .map(|(offset, byte_count)| ChunkLoc { offset, byte_count })
+            .collect())
+    }
+}
+
+/// The offsets or byte counts of the chunks, decoded or deferred.
+#[derive(Debug)]
+enum ChunkValues {
+    Decoded(Vec<u64>),
+    Deferred(DeferredArray),
+}
+
+impl ChunkValues {
+    fn len(&self) -> usize {
+        match self {
+            Self::Decoded(values) => values.len(),
+            Self::Deferred(array) => array.len,
+        }
+    }
+}
+
 /// An iterator over the chunks that make up the image.
 #[derive(Debug)]
 pub struct Chunks<'tiff> {
     image_size: (u32, u32),
     chunk_size: (u32, u32),
+    /// The index of the first chunk of the iterator in the image.
+    first: usize,
     iter: std::iter::Enumerate<std::slice::Iter<'tiff, ChunkLoc>>,
 }
 
@@ -220,7 +608,14 @@ impl Chunks<'_> {
         let (image_width, image_length) = self.image_size;
         let (chunk_width, chunk_length) = self.chunk_size;
 
+        // The chunks of each plane cover the whole image.
         let chunks_along_width = image_width.div_ceil(chunk_width) as usize;
+        let chunks_along_length = image_length.div_ceil(chunk_length) as usize;
+        let chunks_per_plane = chunks_along_width * chunks_along_length;
+        let index = self.first + index;
+        let plane = (index / chunks_per_plane) as u16;
+        let index = index % chunks_per_plane;
+
         let index_width = index % chunks_along_width;
         let index_length = index / chunks_along_width;
 
@@ -236,6 +631,7 @@ impl Chunks<'_> {
         Chunk {
             origin,
             size,
+            plane,
             offset: loc.offset,
             byte_count: loc.byte_count,
         }
@@ -248,12 +644,25 @@ pub struct Chunk {
     pub origin: (u32, u32),
     /// A tuple with the width and height of the chunk in pixels, the padding is subtracted.
     pub size: (u32, u32),
+    /// The index of the plane holding the chunk, it is the index of its sample with
+    /// [`PlanarConfiguration::PLANAR`] and always zero otherwise.
+    pub plane: u16,
     /// The offset of the chunk from the beginning of the file.
     pub offset: u64,
     /// The number of bytes in the chunk.
     pub byte_count: u64,
 }
 
+impl Chunk {
+    /// Returns `true` if the chunk is not stored in the file, both its offset and byte count are
+    /// zero.
+    ///
+    /// GDAL omits the chunks holding only nodata pixels when writing sparse files.
+    pub fn is_sparse(&self) -> bool {
+        self.offset == 0 && self.byte_count == 0
+    }
+}
+
 impl std::iter::Iterator for Chunks<'_> {
     type Item = Chunk;
 
@@ -312,18 +721,45 @@ impl std::iter::DoubleEndedIterator for CustomEntries<'_> {
 }
 
 /// Builder for [`Metadata`].
-#[derive(Default)]
-struct MetadataBuilder {
+///
+/// By default the builder strictly follows the specification, any violation is reported as an
+/// error. In lenient mode, the builder applies the same fixups as libtiff to recover the metadata
+/// of malformed images, and collects the problems as warnings available from
+/// [`Metadata::warnings`]:
+///
+/// * Integer tags are accepted with any unsigned integer datatype, and with more than one value.
+/// * Missing or zero strip/tile byte counts are estimated.
+/// * Chunks exceeding the end of file are truncated.
+/// * Strings without a NUL terminator or with invalid UTF-8 sequences are accepted.
+/// * Invalid optional entries are skipped.
+///
+/// ```
+/// use aira_tiff::{metadata::MetadataBuilder, Decoder};
+///
+/// let file = std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif")?;
+/// let mut decoder = Decoder::new(file)?;
+/// let mut directories = decoder.directories();
+/// while let Some(directory) = directories.next_directory()? {
+///     let metadata = MetadataBuilder::default().lenient(true).decode(directory)?;
+///     assert!(metadata.warnings().is_empty());
+/// }
+/// # Ok::<(), aira_tiff::Error>(())
+/// ```
+#[derive(Debug, Default)]
+pub struct MetadataBuilder {
+    lenient: bool,
+    defer_chunks: bool,
+    warnings: Vec<Error>,
     image_width: Option<u32>,
     image_length: Option<u32>,
     interpretation: Option<Interpretation>,
     rows_per_strip: Option<u32>,
-    strip_offsets: Option<Vec<u64>>,
-    strip_byte_counts: Option<Vec<u64>>,
+    strip_offsets: Option<ChunkValues>,
+    strip_byte_counts: Option<ChunkValues>,
     tile_width: Option<u32>,
     tile_length: Option<u32>,
-    tile_offsets: Option<Vec<u64>>,
-    tile_byte_counts: Option<Vec<u64>>,
+    tile_offsets: Option<ChunkValues>,
+    tile_byte_counts: Option<ChunkValues>,
     compression: Option<Compression>,
     predictor: Option<Predictor>,
     subfile_type: Option<SubfileType>,
@@ -334,6 +770,25 @@ struct MetadataBuilder {
     samples_per_pixel: Option<u16>,
     bits_per_sample: Option<Vec<u16>>,
     sample_format: Option<Vec<SampleFormat>>,
+    ycbcr_subsampling: Option<Vec<u16>>,
+    ycbcr_positioning: Option<YCbCrPositioning>,
+    orientation: Option<Orientation>,
+    fill_order: Option<FillOrder>,
+    extra_samples: Option<Vec<ExtraSample>>,
+    min_sample_value: Option<Vec<u64>>,
+    max_sample_value: Option<Vec<u64>>,
+    smin_sample_value: Option<Vec<f64>>,
+    smax_sample_value: Option<Vec<f64>>,
+    color_map: Option<Vec<u16>>,
+    ink_names: Option<Vec<String>>,
+    document_name: Option<String>,
+    page_name: Option<String>,
+    page_number: Option<(u16, u16)>,
+    xposition: Option<Ratio<u32>>,
+    yposition: Option<Ratio<u32>>,
+    make: Option<String>,
+    model: Option<String>,
+    nodata: Option<f64>,
     artist: Option<String>,
     copyright: Option<String>,
     host_computer: Option<String>,
@@ -344,14 +799,81 @@ struct MetadataBuilder {
 }
 
 impl MetadataBuilder {
+    /// Enables or disables the lenient parsing of malformed images.
+    pub fn lenient(mut self, lenient: bool) -> Self {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Enables or disables the deferred decoding of the strip/tile offsets and byte counts.
+    ///
+    /// Large images may have millions of chunks. When deferred, the arrays of offsets and byte
+    /// counts are not decoded with the metadata, the values covering a window are read on demand
+    /// by [`Metadata::window_chunks`], and [`Metadata::chunks`] returns `None`. The arrays are
+    /// always decoded in lenient mode, since their values may need to be fixed.
+    pub fn defer_chunks(mut self, defer: bool) -> Self {
+        self.defer_chunks = defer;
+        self
+    }
+
+    /// Decodes the metadata of a TIFF directory.
+    pub fn decode<R>(mut self, mut directory: decoder::Directory<'_, R>) -> Result<Metadata, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        let location = directory.location();
+        let stream_len = directory.stream_len()?;
+
+        let mut entries = directory.entries();
+        loop {
+            let entry = match entries.next_entry() {
+                Ok(Some(entry)) => entry,
+                Ok(None) => break,
+                Err(err) if self.lenient => {
+                    self.warnings.push(err);
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            let tag = entry.tag;
+            let header = entry.location(EntryPart::Header);
+            let result = self
+                .push_entry(entry)
+                .with_location(|| header)
+                .with_context(|| format!("Invalid {tag:?}"));
+            match result {
+                Ok(()) => {}
+                Err(err) if self.lenient => self.warnings.push(err),
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.build(stream_len).with_location(|| location)
+    }
+
+    /// Records a problem that has been recovered in lenient mode.
+    fn warn(&mut self, warning: Error) {
+        debug_assert!(self.lenient);
+        self.warnings.push(warning);
+    }
+
     /// Pushes an entry into the metadata builder.
     fn push_entry<R>(&mut self, mut entry: decoder::Entry<'_, R>) -> Result<(), Error>
     where
         R: std::io::Read + std::io::Seek,
     {
+        let lenient = self.lenient;
+
         macro_rules! decode {
             ($entry:ident into u16) => {{
                 match $entry.dtype {
+                    _ if lenient => {
+                        self.check_dtype(&$entry, &[DType::Short]);
+                        let value = self.decode_lenient_integer(&mut $entry)?;
+                        u16::try_from(value)
+                            .map_err(|_| Error::from_static_str("Value does not fit in 16 bits"))?
+                    }
                     DType::Short => $entry.decode::<u16>()?,
                     dtype => Err(UnexpectedDType(dtype))?,
                 }
@@ -364,6 +886,14 @@ impl MetadataBuilder {
             }};
             ($entry:ident into Vec<u16>) => {{
                 match $entry.dtype {
+                    _ if lenient => {
+                        self.check_dtype(&$entry, &[DType::Short]);
+                        decode_integers(&mut $entry, lenient)?
+                            .into_iter()
+                            .map(u16::try_from)
+                            .collect::<Result<Vec<_>, _>>()
+                            .map_err(|_| Error::from_static_str("Value does not fit in 16 bits"))?
+                    }
                     DType::Short => {
                         let count = entry.count as usize;
                         let mut values = Vec::<u16>::with_capacity(count);
@@ -379,6 +909,10 @@ impl MetadataBuilder {
             }};
             ($entry:ident into Vec<SampleFormat>) => {{
                 match $entry.dtype {
+                    _ if lenient => decode!($entry into Vec<u16>)
+                        .into_iter()
+                        .map(SampleFormat)
+                        .collect::<Vec<_>>(),
                     DType::Short => {
                         let count = entry.count as usize;
                         let mut values = Vec::<SampleFormat>::with_capacity(count);
@@ -398,6 +932,12 @@ impl MetadataBuilder {
             }};
             ($entry:ident into u32) => {{
                 match $entry.dtype {
+                    _ if lenient => {
+                        self.check_dtype(&$entry, &[DType::Long]);
+                        let value = self.decode_lenient_integer(&mut $entry)?;
+                        u32::try_from(value)
+                            .map_err(|_| Error::from_static_str("Value does not fit in 32 bits"))?
+                    }
                     DType::Long => $entry.decode::<u32>()?,
                     dtype => Err(UnexpectedDType(dtype))?,
                 }
@@ -412,56 +952,75 @@ impl MetadataBuilder {
                             entry.unchecked_decode_into(&mut buffer[..count])?;
                             bytes.set_len(count);
                         }
-                        std::ffi::CStr::from_bytes_with_nul(&bytes)
-                            .map_err(|err| Error::from_args(format_args!("Invalid string: {err}")))?
-                            .to_str()
-                            .map_err(|err| {
-                                Error::from_args(format_args!("Invalid UTF-8 stirng: {err}"))
-                            })?
-                            .to_owned()
+                        let location = entry.location(EntryPart::Payload);
+                        if lenient {
+                            self.decode_lenient_string(bytes, location)
+                        } else {
+                            std::ffi::CStr::from_bytes_with_nul(&bytes)
+                                .map_err(|err| {
+                                    Error::from_args(format_args!("Invalid string: {err}"))
+                                })
+                                .with_location(|| location)?
+                                .to_str()
+                                .map_err(|err| {
+                                    Error::from_args(format_args!("Invalid UTF-8 stirng: {err}"))
+                                })
+                                .with_location(|| location)?
+                                .to_owned()
+                        }
+                    }
+                    dtype => Err(UnexpectedDType(dtype))?,
+                }
+            }};
+            ($entry:ident into Vec<String>) => {{
+                match $entry.dtype {
+                    DType::Ascii => {
+                        let count = $entry.count as usize;
+                        let mut bytes = Vec::<u8>::with_capacity(count);
+                        let buffer = bytes.spare_capacity_mut();
+                        unsafe {
+                            entry.unchecked_decode_into(&mut buffer[..count])?;
+                            bytes.set_len(count);
+                        }
+                        let location = entry.location(EntryPart::Payload);
+                        self.decode_strings(bytes, location)?
                     }
                     dtype => Err(UnexpectedDType(dtype))?,
                 }
             }};
+            ($entry:ident into Vec<f64>) => {{
+                match Entry::from_decoder($entry)? {
+                    Entry::U8(values) => values.into_iter().map(f64::from).collect::<Vec<_>>(),
+                    Entry::U16(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::U32(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::U64(values) => values.into_iter().map(|value| value as f64).collect(),
+                    Entry::I8(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::I16(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::I32(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::I64(values) => values.into_iter().map(|value| value as f64).collect(),
+                    Entry::F32(values) => values.into_iter().map(f64::from).collect(),
+                    Entry::F64(values) => values,
+                    _ => Err(Error::from_static_str("Values are not numbers"))?,
+                }
+            }};
             ($entry:ident as u32) => {{
                 match $entry.dtype {
+                    _ if lenient => {
+                        self.check_dtype(&$entry, &[DType::Short, DType::Long]);
+                        let value = self.decode_lenient_integer(&mut $entry)?;
+                        u32::try_from(value)
+                            .map_err(|_| Error::from_static_str("Value does not fit in 32 bits"))?
+                    }
                     DType::Short => $entry.decode::<u16>()? as u32,
                     DType::Long => $entry.decode::<u32>()?,
                     dtype => Err(UnexpectedDType(dtype))?,
                 }
             }};
             ($entry:ident as Vec<u64>) => {{
-                let count = entry.count as usize;
-                match $entry.dtype {
-                    DType::Short => {
-                        let mut values = Vec::<u16>::with_capacity(count);
-                        let buffer = values.spare_capacity_mut();
-                        unsafe {
-                            entry.unchecked_decode_into(&mut buffer[..count])?;
-                            values.set_len(count);
-                        }
-                        values.into_iter().map(|x| x as u64).collect::<Vec<_>>()
-                    }
-                    DType::Long | DType::Ifd => {
-                        let mut values = Vec::<u32>::with_capacity(count);
-                        let buffer = values.spare_capacity_mut();
-                        unsafe {
-                            entry.unchecked_decode_into(&mut buffer[..count])?;
-                            values.set_len(count);
-                        }
-                        values.into_iter().map(|x| x as u64).collect::<Vec<_>>()
-                    }
-                    DType::BigLong | DType::BigIfd => {
-                        let mut values = Vec::<u64>::with_capacity(count);
-                        let buffer = values.spare_capacity_mut();
-                        unsafe {
-                            entry.unchecked_decode_into(&mut buffer[..count])?;
-                            values.set_len(count);
-                        }
-                        values
-                    }
-                    dtype => Err(UnexpectedDType(dtype))?,
+                if lenient {
+                    self.check_dtype(&$entry, &[DType::Short]);
                 }
+                decode_integers(&mut $entry, lenient)?
             }};
         }
 
@@ -481,10 +1040,10 @@ impl MetadataBuilder {
                 self.rows_per_strip = Some(decode!(entry as u32));
             }
             Tag::STRIP_OFFSETS => {
-                self.strip_offsets = Some(decode!(entry as Vec<u64>));
+                self.strip_offsets = Some(self.decode_chunk_values(entry)?);
             }
             Tag::STRIP_BYTE_COUNTS => {
-                self.strip_byte_counts = Some(decode!(entry as Vec<u64>));
+                self.strip_byte_counts = Some(self.decode_chunk_values(entry)?);
             }
             Tag::TILE_WIDTH => {
                 self.tile_width = Some(decode!(entry as u32));
@@ -493,10 +1052,10 @@ impl MetadataBuilder {
                 self.tile_length = Some(decode!(entry as u32));
             }
             Tag::TILE_OFFSETS => {
-                self.tile_offsets = Some(decode!(entry as Vec<u64>));
+                self.tile_offsets = Some(self.decode_chunk_values(entry)?);
             }
             Tag::TILE_BYTE_COUNTS => {
-                self.tile_byte_counts = Some(decode!(entry as Vec<u64>));
+                self.tile_byte_counts = Some(self.decode_chunk_values(entry)?);
             }
             Tag::COMPRESSION => {
                 let compression = decode!(entry into u16);
@@ -544,6 +1103,97 @@ impl MetadataBuilder {
             Tag::SAMPLE_FORMAT => {
                 self.sample_format = Some(decode!(entry into Vec<SampleFormat>));
             }
+            Tag::YCBCR_SUB_SAMPLING => {
+                self.ycbcr_subsampling = Some(decode!(entry into Vec<u16>));
+            }
+            Tag::YCBCR_POSITIONING => {
+                let positioning = decode!(entry into u16);
+                let positioning = YCbCrPositioning(positioning);
+                self.ycbcr_positioning = Some(positioning);
+            }
+            // The orientation and the fill order are advisory, an unknown value is kept as a
+            // custom entry and the default is used.
+            Tag::ORIENTATION => {
+                let orientation = decode!(entry into u16);
+                if !(1..=8).contains(&orientation) {
+                    if lenient {
+                        self.warn(Error::from_args(format_args!(
+                            "Invalid orientation {orientation}, the default is used"
+                        )));
+                    }
+                    self.entries
+                        .insert(Tag::ORIENTATION, Entry::from_decoder(entry)?);
+                    return Ok(());
+                }
+                self.orientation = Some(Orientation(orientation));
+            }
+            Tag::FILL_ORDER => {
+                let fill_order = FillOrder(decode!(entry into u16));
+                if !matches!(fill_order, FillOrder::MSB_TO_LSB | FillOrder::LSB_TO_MSB) {
+                    if lenient {
+                        self.warn(Error::from_args(format_args!(
+                            "Invalid fill order {}, the default is used",
+                            fill_order.0
+                        )));
+                    }
+                    self.entries
+                        .insert(Tag::FILL_ORDER, Entry::from_decoder(entry)?);
+                    return Ok(());
+                }
+                self.fill_order = Some(fill_order);
+            }
+            Tag::EXTRA_SAMPLES => {
+                let extra_samples = decode!(entry into Vec<u16>);
+                self.extra_samples = Some(extra_samples.into_iter().map(ExtraSample).collect());
+            }
+            Tag::MIN_SAMPLE_VALUE => {
+                self.min_sample_value = Some(decode!(entry as Vec<u64>));
+            }
+            Tag::MAX_SAMPLE_VALUE => {
+                self.max_sample_value = Some(decode!(entry as Vec<u64>));
+            }
+            Tag::SMIN_SAMPLE_VALUE => {
+                self.smin_sample_value = Some(decode!(entry into Vec<f64>));
+            }
+            Tag::SMAX_SAMPLE_VALUE => {
+                self.smax_sample_value = Some(decode!(entry into Vec<f64>));
+            }
+            Tag::COLOR_MAP => {
+                self.color_map = Some(decode!(entry into Vec<u16>));
+            }
+            Tag::INK_NAMES => {
+                self.ink_names = Some(decode!(entry into Vec<String>));
+            }
+            Tag::DOCUMENT_NAME => {
+                let document_name = decode!(entry into String);
+                self.document_name = Some(document_name);
+            }
+            Tag::PAGE_NAME => {
+                let page_name = decode!(entry into String);
+                self.page_name = Some(page_name);
+            }
+            Tag::PAGE_NUMBER => {
+                let &[page, total] = decode!(entry into Vec<u16>).as_slice() else {
+                    return Err(Error::from_static_str("Page number must have 2 values"));
+                };
+                self.page_number = Some((page, total));
+            }
+            Tag::XPOSITION => {
+                let xposition = decode!(entry into Ratio<u32>);
+                self.xposition = Some(xposition);
+            }
+            Tag::YPOSITION => {
+                let yposition = decode!(entry into Ratio<u32>);
+                self.yposition = Some(yposition);
+            }
+            Tag::MAKE => {
+                let make = decode!(entry into String);
+                self.make = Some(make);
+            }
+            Tag::MODEL => {
+                let model = decode!(entry into String);
+                self.model = Some(model);
+            }
             Tag::ARTIST => {
                 let artist = decode!(entry into String);
                 self.artist = Some(artist);
@@ -564,6 +1214,21 @@ impl MetadataBuilder {
                 let software = decode!(entry into String);
                 self.software = Some(software);
             }
+            // A nodata value which is not a number is kept as a custom entry.
+            Tag::GDAL_NO_DATA => {
+                let nodata = decode!(entry into String);
+                let Ok(value) = nodata.trim().parse::<f64>() else {
+                    if lenient {
+                        self.warn(Error::from_args(format_args!(
+                            "Invalid nodata value {nodata:?}, the nodata value is ignored"
+                        )));
+                    }
+                    self.entries
+                        .insert(Tag::GDAL_NO_DATA, Entry::from_decoder(entry)?);
+                    return Ok(());
+                };
+                self.nodata = Some(value);
+            }
             tag => {
                 self.entries.insert(tag, Entry::from_decoder(entry)?);
             }
@@ -572,70 +1237,193 @@ impl MetadataBuilder {
         Ok(())
     }
 
+    /// Decodes the first value of an entry with any unsigned integer datatype.
+    fn decode_lenient_integer<R>(&mut self, entry: &mut decoder::Entry<'_, R>) -> Result<u64, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        if entry.count != 1 {
+            let location = entry.location(EntryPart::Header);
+            self.warn(
+                Error::from_args(format_args!(
+                    "Expected a single value for {:?}, found {}",
+                    entry.tag, entry.count
+                ))
+                .with_location(|| location),
+            );
+        }
+
+        let values = decode_integers(entry, true)?;
+        values
+            .first()
+            .copied()
+            .ok_or_else(|| Error::from_static_str("Entry has no values"))
+    }
+
+    /// Records a warning when the datatype of the entry is not one of those given by the
+    /// specification, the value is decoded anyway.
+    fn check_dtype<R>(&mut self, entry: &decoder::Entry<'_, R>, dtypes: &[DType]) {
+        if !dtypes.contains(&entry.dtype) {
+            let location = entry.location(EntryPart::Header);
+            let tag = entry.tag;
+            self.warn(
+                Error::from(UnexpectedDType(entry.dtype))
+                    .with_location(|| location)
+                    .with_context(|| format!("Invalid {tag:?}, the value is decoded anyway")),
+            );
+        }
+    }
+
+    /// Decodes a string that might miss the NUL terminator or contain invalid UTF-8 sequences.
+    fn decode_lenient_string(&mut self, mut bytes: Vec<u8>, location: Location) -> String {
+        match bytes.iter().position(|&byte| byte == 0) {
+            Some(end) => bytes.truncate(end),
+            None => self.warn(
+                Error::from_static_str("String is not NUL terminated").with_location(|| location),
+            ),
+        }
+
+        match String::from_utf8(bytes) {
+            Ok(string) => string,
+            Err(err) => {
+                self.warn(
+                    Error::from_args(format_args!("Invalid UTF-8 stirng: {}", err.utf8_error()))
+                        .with_location(|| location),
+                );
+                String::from_utf8_lossy(err.as_bytes()).into_owned()
+            }
+        }
+    }
+
+    /// Decodes a sequence of NUL terminated strings.
+    fn decode_strings(
+        &mut self,
+        mut bytes: Vec<u8>,
+        location: Location,
+    ) -> Result<Vec<String>, Error> {
+        match bytes.pop() {
+            Some(0) => {}
+            Some(byte) if self.lenient => {
+                bytes.push(byte);
+                self.warn(
+                    Error::from_static_str("String is not NUL terminated")
+                        .with_location(|| location),
+                );
+            }
+            _ => {
+                return Err(Error::from_static_str("String is not NUL terminated")
+                    .with_location(|| location))
+            }
+        }
+
+        let mut strings = Vec::new();
+        for bytes in bytes.split(|&byte| byte == 0) {
+            match std::str::from_utf8(bytes) {
+                Ok(string) => strings.push(string.to_owned()),
+                Err(err) if self.lenient => {
+                    self.warn(
+                        Error::from_args(format_args!("Invalid UTF-8 string: {err}"))
+                            .with_location(|| location),
+                    );
+                    strings.push(String::from_utf8_lossy(bytes).into_owned());
+                }
+                Err(err) => {
+                    return Err(
+                        Error::from_args(format_args!("Invalid UTF-8 string: {err}"))
+                            .with_location(|| location),
+                    )
+                }
+            }
+        }
+        Ok(strings)
+    }
+
+    /// Decodes the strip/tile offsets or byte counts, or records the location of their array when
+    /// they are deferred.
+    fn decode_chunk_values<R>(
+        &mut self,
+        mut entry: decoder::Entry<'_, R>,
+    ) -> Result<ChunkValues, Error>
+    where
+        R: std::io::Read + std::io::Seek,
+    {
+        match entry.dtype {
+            DType::Short | DType::Long | DType::Ifd | DType::BigLong | DType::BigIfd
+                if self.defer_chunks && !self.lenient =>
+            {
+                Ok(ChunkValues::Deferred(DeferredArray {
+                    offset: entry.location(EntryPart::Payload).offset,
+                    dtype: entry.dtype,
+                    len: entry.count as usize,
+                    byteorder: entry.byteorder(),
+                }))
+            }
+            _ => {
+                if self.lenient {
+                    let dtypes = [DType::Short, DType::Long, DType::BigLong];
+                    self.check_dtype(&entry, &dtypes);
+                }
+                decode_integers(&mut entry, self.lenient).map(ChunkValues::Decoded)
+            }
+        }
+    }
+
     /// Validates the collected metadata and returns a new [`Metadata`] instance.
-    fn build(self) -> Result<Metadata, Error> {
-        let Self {
-            image_width,
-            image_length,
-            interpretation,
-            rows_per_strip,
-            tile_width,
-            tile_length,
-            strip_offsets,
-            strip_byte_counts,
-            tile_offsets,
-            tile_byte_counts,
-            compression,
-            predictor,
-            subfile_type,
-            configuration,
-            xresolution,
-            yresolution,
-            resolution_unit,
-            datetime,
-            samples_per_pixel,
-            bits_per_sample,
-            sample_format,
-            artist,
-            copyright,
-            host_computer,
-            description,
-            software,
-            entries,
-        } = self;
+    fn build(mut self, stream_len: u64) -> Result<Metadata, Error> {
+        let lenient = self.lenient;
 
-        let image_width = image_width.ok_or(MissingRequiredTag(Tag::IMAGE_WIDTH))?;
+        let image_width = self
+            .image_width
+            .ok_or(MissingRequiredTag(Tag::IMAGE_WIDTH))?;
         if image_width == 0 {
             return Err(Error::from_static_str("Image width cannot be zero"));
         }
 
-        let image_length = image_length.ok_or(MissingRequiredTag(Tag::IMAGE_LENGTH))?;
+        let image_length = self
+            .image_length
+            .ok_or(MissingRequiredTag(Tag::IMAGE_LENGTH))?;
         if image_length == 0 {
             return Err(Error::from_static_str("Image length cannot be zero"));
         }
 
         let dimensions = (image_width, image_length);
 
-        let interpretation =
-            interpretation.ok_or(MissingRequiredTag(Tag::PHOTOMETRIC_INTERPRETATION))?;
+        let interpretation = self
+            .interpretation
+            .ok_or(MissingRequiredTag(Tag::PHOTOMETRIC_INTERPRETATION))?;
+
+        // The spec defines a default value for rows per strip, that is the whole image.
+        if lenient
+            && self.rows_per_strip.is_none()
+            && self.strip_offsets.is_some()
+            && self.tile_offsets.is_none()
+        {
+            self.rows_per_strip = Some(image_length);
+        }
 
         let (layout, offsets, byte_counts) = match (
-            rows_per_strip,
-            strip_offsets,
-            strip_byte_counts,
-            tile_width,
-            tile_length,
-            tile_offsets,
-            tile_byte_counts,
+            self.rows_per_strip,
+            self.strip_offsets.take(),
+            self.strip_byte_counts.take(),
+            self.tile_width,
+            self.tile_length,
+            self.tile_offsets.take(),
+            self.tile_byte_counts.take(),
         ) {
-            (Some(length), Some(offsets), Some(byte_counts), None, None, None, None) => {
+            (Some(length), Some(offsets), byte_counts, None, None, None, None)
+                if byte_counts.is_some() || lenient =>
+            {
                 if length == 0 {
                     return Err(Error::from_static_str("Rows per strip cannot be zero"));
                 }
 
+                // A strip cannot be longer than the image.
+                let length = if lenient { length.min(image_length) } else { length };
                 (Layout::Strips { length }, offsets, byte_counts)
             }
-            (None, None, None, Some(width), Some(length), Some(offsets), Some(byte_counts)) => {
+            (None, None, None, Some(width), Some(length), Some(offsets), byte_counts)
+                if byte_counts.is_some() || lenient =>
+            {
                 if width == 0 {
                     return Err(Error::from_static_str("Tile width cannot be zero"));
                 }
@@ -652,39 +1440,31 @@ impl MetadataBuilder {
             }
         };
 
-        if offsets.len() != byte_counts.len() {
-            return Err(Error::from_static_str(
-                "Number of strip/tiles offsets does not match number of byte counts",
-            ));
-        }
-
-        let actual_chunks_count = offsets.len();
-        let expected_chunks_count = layout.expected_chunks_count(image_width, image_length);
-        if actual_chunks_count < expected_chunks_count {
-            return Err(Error::from_args(format_args!(
-                "Number of strip/tiles offsets does not match expected chunk counts for the given image dimensions: actual {actual_chunks_count}, expected {expected_chunks_count}",
-            )));
-        }
-
-        let chunks = offsets
-            .into_iter()
-            .zip(byte_counts)
-            .map(|(offset, byte_count)| ChunkLoc { offset, byte_count })
-            .collect();
-
-        let compression = compression.unwrap_or_default();
-        let predictor = predictor.unwrap_or_default();
+        let compression = self.compression.unwrap_or_default();
+        let predictor = self.predictor.unwrap_or_default();
 
-        let subfile_type = subfile_type.unwrap_or_default();
+        let subfile_type = self.subfile_type.unwrap_or_default();
 
-        let configuration = configuration.unwrap_or_default();
+        let configuration = self.configuration.unwrap_or_default();
 
-        let samples_per_pixel = samples_per_pixel.unwrap_or(1);
-        let bits_per_sample =
-            bits_per_sample.unwrap_or_else(|| vec![1; samples_per_pixel as usize]);
-        let sample_format = sample_format
+        let samples_per_pixel = self.samples_per_pixel.unwrap_or(1);
+        let mut bits_per_sample = self
+            .bits_per_sample
+            .take()
+            .unwrap_or_else(|| vec![1; samples_per_pixel as usize]);
+        let mut sample_format = self
+            .sample_format
+            .take()
             .unwrap_or_else(|| vec![SampleFormat::default(); samples_per_pixel as usize]);
 
+        // A single value is commonly written for all the samples.
+        if lenient && bits_per_sample.len() == 1 {
+            bits_per_sample.resize(samples_per_pixel as usize, bits_per_sample[0]);
+        }
+        if lenient && sample_format.len() == 1 {
+            sample_format.resize(samples_per_pixel as usize, sample_format[0]);
+        }
+
         if bits_per_sample.len() != samples_per_pixel as usize {
             return Err(Error::from_args(format_args!(
                 "Number of bits per sample ({}) does not match number of samples per pixel ({})",
@@ -706,31 +1486,262 @@ impl MetadataBuilder {
             .map(|(bits, format)| Sample { bits, format })
             .collect::<Vec<_>>();
 
+        // The chroma samples are subsampled by default, only for the YCbCr color space.
+        let ycbcr_subsampling = match self.ycbcr_subsampling.take().as_deref() {
+            _ if interpretation != Interpretation::YCBCR => (1, 1),
+            None => (2, 2),
+            Some(&[horizontal, vertical])
+                if matches!(horizontal, 1 | 2 | 4)
+                    && matches!(vertical, 1 | 2 | 4)
+                    && vertical <= horizontal =>
+            {
+                (horizontal, vertical)
+            }
+            Some(subsampling) if lenient => {
+                self.warn(Error::from_args(format_args!(
+                    "Invalid YCbCr subsampling {subsampling:?}, the chroma samples are assumed not subsampled"
+                )));
+                (1, 1)
+            }
+            Some(subsampling) => {
+                return Err(Error::from_args(format_args!(
+                    "Invalid YCbCr subsampling {subsampling:?}"
+                )));
+            }
+        };
+        let ycbcr_positioning = self.ycbcr_positioning.unwrap_or_default();
+
+        let orientation = self.orientation.unwrap_or_default();
+        let fill_order = self.fill_order.unwrap_or_default();
+
+        // The extra samples are the last samples of each pixel.
+        let extra_samples = match self.extra_samples.take().unwrap_or_default() {
+            extra_samples if extra_samples.len() <= samples.len() => extra_samples,
+            extra_samples if lenient => {
+                self.warn(Error::from_args(format_args!(
+                    "Number of extra samples ({}) exceeds number of samples per pixel ({}), the extra samples are ignored",
+                    extra_samples.len(),
+                    samples.len()
+                )));
+                Vec::new()
+            }
+            extra_samples => {
+                return Err(Error::from_args(format_args!(
+                    "Number of extra samples ({}) exceeds number of samples per pixel ({})",
+                    extra_samples.len(),
+                    samples.len()
+                )));
+            }
+        };
+
+        // By default the values span the range of the samples.
+        let min_sample_value = self.min_sample_value.take();
+        let min_sample_value =
+            self.sample_values(Tag::MIN_SAMPLE_VALUE, min_sample_value, &samples, |_| 0)?;
+        let max_sample_value = self.max_sample_value.take();
+        let max_sample_value = self.sample_values(
+            Tag::MAX_SAMPLE_VALUE,
+            max_sample_value,
+            &samples,
+            |sample| u64::MAX >> (64 - sample.bits.clamp(1, 64)),
+        )?;
+        let sample_values = min_sample_value.into_iter().zip(max_sample_value).collect();
+        let smin_sample_value = self.smin_sample_value.take();
+        let smin_sample_value = self.sample_values(
+            Tag::SMIN_SAMPLE_VALUE,
+            smin_sample_value,
+            &samples,
+            |sample| sample.range().0,
+        )?;
+        let smax_sample_value = self.smax_sample_value.take();
+        let smax_sample_value = self.sample_values(
+            Tag::SMAX_SAMPLE_VALUE,
+            smax_sample_value,
+            &samples,
+            |sample| sample.range().1,
+        )?;
+        let sample_ranges = smin_sample_value
+            .into_iter()
+            .zip(smax_sample_value)
+            .collect();
+
+        // The color map holds the red, then green, then blue values of each index.
+        let color_map = match (self.color_map.take(), samples.first()) {
+            (Some(color_map), Some(sample)) if color_map.len() == 3 << sample.bits.min(16) => {
+                Some(color_map)
+            }
+            (Some(color_map), Some(sample)) if lenient => {
+                self.warn(Error::from_args(format_args!(
+                    "Number of color map values ({}) does not match the bits per sample ({}), the color map is ignored",
+                    color_map.len(),
+                    sample.bits
+                )));
+                None
+            }
+            (Some(color_map), Some(sample)) => {
+                return Err(Error::from_args(format_args!(
+                    "Number of color map values ({}) does not match the bits per sample ({})",
+                    color_map.len(),
+                    sample.bits
+                )));
+            }
+            (Some(_), None) if lenient => {
+                self.warn(Error::from_static_str(
+                    "Color map without samples per pixel, the color map is ignored",
+                ));
+                None
+            }
+            (Some(_), None) => {
+                return Err(Error::from_static_str(
+                    "Color map without samples per pixel",
+                ));
+            }
+            (None, _) => None,
+        };
+
+        // Each sample is stored in a separate plane when the planar configuration is used.
+        let actual_chunks_count = offsets.len();
+        let expected_chunks_count = match configuration {
+            PlanarConfiguration::PLANAR => {
+                layout.expected_chunks_count(image_width, image_length) * samples.len()
+            }
+            _ => layout.expected_chunks_count(image_width, image_length),
+        };
+        if actual_chunks_count < expected_chunks_count {
+            return Err(Error::from_args(format_args!(
+                "Number of strip/tiles offsets does not match expected chunk counts for the given image dimensions: actual {actual_chunks_count}, expected {expected_chunks_count}",
+            )));
+        }
+
+        let byte_counts = match byte_counts {
+            Some(byte_counts) if byte_counts.len() == offsets.len() => byte_counts,
+            byte_counts if lenient => {
+                self.warn(Error::from_static_str(
+                    "Number of strip/tiles byte counts does not match number of offsets, the byte counts are estimated",
+                ));
+                let byte_counts = match byte_counts {
+                    Some(ChunkValues::Decoded(byte_counts)) => byte_counts,
+                    _ => Vec::new(),
+                };
+                ChunkValues::Decoded(
+                    (0..offsets.len())
+                        .map(|index| byte_counts.get(index).copied().unwrap_or(0))
+                        .collect(),
+                )
+            }
+            _ => {
+                return Err(Error::from_static_str(
+                    "Number of strip/tiles offsets does not match number of byte counts",
+                ));
+            }
+        };
+
+        // Any chunk exceeding the expected count lies outside of the image, it is ignored.
+        let mut deferred_chunks = None;
+        let (offsets, byte_counts) = match (offsets, byte_counts) {
+            (ChunkValues::Decoded(offsets), ChunkValues::Decoded(byte_counts)) => {
+                (offsets, byte_counts)
+            }
+            (ChunkValues::Deferred(offsets), ChunkValues::Deferred(byte_counts)) => {
+                deferred_chunks = Some(DeferredChunks {
+                    offsets,
+                    byte_counts,
+                    len: expected_chunks_count,
+                });
+                (Vec::new(), Vec::new())
+            }
+            _ => {
+                return Err(Error::from_static_str(
+                    "Strip/tile offsets and byte counts must be both deferred or both decoded",
+                ))
+            }
+        };
+        let mut chunks = offsets
+            .into_iter()
+            .zip(byte_counts)
+            .take(expected_chunks_count)
+            .map(|(offset, byte_count)| ChunkLoc { offset, byte_count })
+            .collect::<Vec<_>>();
+
+        if lenient {
+            let chunks_per_plane = layout.expected_chunks_count(image_width, image_length);
+            let uncompressed_size = |index: usize| {
+                let bits_per_pixel = match configuration {
+                    PlanarConfiguration::PLANAR => samples[index / chunks_per_plane].bits as u64,
+                    _ => samples.iter().map(|sample| sample.bits as u64).sum(),
+                };
+                let (width, length) = match layout {
+                    Layout::Strips { length } => {
+                        let origin = (index % chunks_per_plane) as u64 * length as u64;
+                        let length = (length as u64).min(image_length as u64 - origin);
+                        (image_width as u64, length)
+                    }
+                    Layout::Tiles { width, length } => (width as u64, length as u64),
+                };
+
+                // Subsampled pixels are stored in data units of luma and chroma samples.
+                let (horizontal, vertical) =
+                    (ycbcr_subsampling.0 as u64, ycbcr_subsampling.1 as u64);
+                if (horizontal, vertical) != (1, 1) && configuration != PlanarConfiguration::PLANAR
+                {
+                    let units = width.div_ceil(horizontal) * length.div_ceil(vertical);
+                    let bits = samples.first().map_or(0, |sample| sample.bits as u64);
+                    let bits_per_unit = (horizontal * vertical + 2) * bits;
+                    return (units * bits_per_unit).div_ceil(8);
+                }
+                (width * bits_per_pixel).div_ceil(8).saturating_mul(length)
+            };
+            self.fix_chunks(&mut chunks, compression, stream_len, uncompressed_size);
+        }
+
         #[cfg(feature = "chrono")]
-        let datetime = datetime
+        let datetime = self
+            .datetime
+            .take()
             .map(|datetime| {
                 DateTime::parse_from_str(&datetime, "%Y:%m:%d %H:%M:%S")
                     .map_err(|err| Error::from_args(format_args!("{err}")))
                     .with_context(|| "Invalid date and time format, expected 'YYYY:MM:DD HH:MM:SS'")
             })
-            .transpose()?;
+            .transpose();
 
         #[cfg(feature = "jiff")]
-        let datetime = datetime
+        let datetime = self
+            .datetime
+            .take()
             .map(|datetime| {
                 DateTime::strptime("%Y:%m:%d %H:%M:%S", datetime)
                     .map_err(|err| Error::from_args(format_args!("{err}")))
                     .with_context(|| "Invalid date and time format, expected 'YYYY:MM:DD HH:MM:SS'")
             })
-            .transpose()?;
+            .transpose();
+
+        #[cfg(any(feature = "chrono", feature = "jiff"))]
+        let datetime = match datetime {
+            Ok(datetime) => datetime,
+            Err(err) if lenient => {
+                self.warn(err);
+                None
+            }
+            Err(err) => return Err(err),
+        };
 
-        let resolution_unit = resolution_unit.unwrap_or_default();
-        let resolution = match (xresolution, yresolution) {
+        #[cfg(not(any(feature = "chrono", feature = "jiff")))]
+        let datetime = self.datetime.take();
+
+        let resolution_unit = self.resolution_unit.unwrap_or_default();
+        let resolution = match (self.xresolution, self.yresolution) {
             (Some(xresolution), Some(yresolution)) => Some(Resolution {
                 pixels_per_unit: (xresolution, yresolution),
                 unit: resolution_unit,
             }),
             (None, None) => None,
+            _ if lenient => {
+                self.warn(Error::from_static_str(
+                    "X and Y resolution must be both present or both absent, the resolution is ignored",
+                ));
+                None
+            }
             _ => {
                 return Err(Error::from_static_str(
                     "X and Y resolution must be both present or both absent",
@@ -738,31 +1749,218 @@ impl MetadataBuilder {
             }
         };
 
+        let position = match (self.xposition, self.yposition) {
+            (Some(xposition), Some(yposition)) => Some((xposition, yposition)),
+            (None, None) => None,
+            _ if lenient => {
+                self.warn(Error::from_static_str(
+                    "X and Y position must be both present or both absent, the position is ignored",
+                ));
+                None
+            }
+            _ => {
+                return Err(Error::from_static_str(
+                    "X and Y position must be both present or both absent",
+                ))
+            }
+        };
+
+        let Self {
+            warnings,
+            ink_names,
+            document_name,
+            page_name,
+            page_number,
+            make,
+            model,
+            nodata,
+            artist,
+            copyright,
+            host_computer,
+            description,
+            software,
+            entries,
+            ..
+        } = self;
+
         Ok(Metadata {
             dimensions,
             interpretation,
             layout,
             chunks,
+            deferred_chunks,
             compression,
             predictor,
             subfile_type,
             configuration,
             resolution,
+            orientation,
+            fill_order,
+            extra_samples,
+            sample_values,
+            sample_ranges,
+            color_map,
+            ink_names: ink_names.unwrap_or_default(),
+            document_name,
+            page_name,
+            page_number,
+            position,
+            make,
+            model,
+            nodata,
             artist,
             copyright,
             host_computer,
             description,
             software,
+            ycbcr_subsampling,
+            ycbcr_positioning,
             datetime,
             samples,
             entries,
+            warnings,
         })
     }
+
+    /// Returns a value for each sample from the values of `tag`, or the default values.
+    ///
+    /// A single value is commonly written for all the samples, it is used for each of them.
+    fn sample_values<T: Copy>(
+        &mut self,
+        tag: Tag,
+        values: Option<Vec<T>>,
+        samples: &[Sample],
+        default: impl Fn(&Sample) -> T,
+    ) -> Result<Vec<T>, Error> {
+        let values = match values {
+            Some(values) if values.len() == samples.len() => values,
+            Some(values) if values.len() == 1 => {
+                if self.lenient {
+                    self.warn(Error::from_args(format_args!(
+                        "A single value of {tag:?} is given for {} samples, it is used for all of them",
+                        samples.len()
+                    )));
+                }
+                vec![values[0]; samples.len()]
+            }
+            Some(values) if self.lenient => {
+                self.warn(Error::from_args(format_args!(
+                    "Number of values of {tag:?} ({}) does not match number of samples per pixel ({}), the defaults are used",
+                    values.len(),
+                    samples.len()
+                )));
+                samples.iter().map(default).collect()
+            }
+            Some(values) => {
+                return Err(Error::from_args(format_args!(
+                    "Number of values of {tag:?} ({}) does not match number of samples per pixel ({})",
+                    values.len(),
+                    samples.len()
+                )));
+            }
+            None => samples.iter().map(default).collect(),
+        };
+        Ok(values)
+    }
+
+    /// Estimates the missing byte counts and truncates the chunks exceeding the end of file.
+    ///
+    /// The size of uncompressed chunks is computed from their index.
+    fn fix_chunks(
+        &mut self,
+        chunks: &mut [ChunkLoc],
+        compression: Compression,
+        stream_len: u64,
+        uncompressed_size: impl Fn(usize) -> u64,
+    ) {
+        // The offsets sorted in ascending order, the end of a compressed chunk is estimated as the
+        // beginning of the next one.
+        let mut ends = chunks
+            .iter()
+            .map(|chunk| chunk.offset)
+            .chain([stream_len])
+            .collect::<Vec<_>>();
+        ends.sort_unstable();
+
+        let mut estimated = false;
+        for (index, chunk) in chunks.iter_mut().enumerate() {
+            if chunk.byte_count == 0 && chunk.offset != 0 {
+                estimated = true;
+                chunk.byte_count = if compression == Compression::NONE {
+                    uncompressed_size(index)
+                } else {
+                    let next = ends.partition_point(|&end| end <= chunk.offset);
+                    ends.get(next)
+                        .copied()
+                        .unwrap_or(stream_len)
+                        .saturating_sub(chunk.offset)
+                };
+            }
+        }
+        if estimated {
+            self.warn(Error::from_static_str(
+                "Strip/tile byte counts are missing or zero, they are estimated",
+            ));
+        }
+
+        let mut truncated = false;
+        for chunk in chunks.iter_mut() {
+            let end = chunk.offset.saturating_add(chunk.byte_count);
+            if end > stream_len {
+                truncated = true;
+                chunk.byte_count = stream_len.saturating_sub(chunk.offset);
+            }
+        }
+        if truncated {
+            self.warn(Error::from_static_str(
+                "Strip/tile data exceeds the end of file, it is truncated",
+            ));
+        }
+    }
+}
+
+/// Decodes the values of an entry with an unsigned integer datatype.
+///
+/// The datatype Byte is accepted only in lenient mode.
+fn decode_integers<R>(entry: &mut decoder::Entry<'_, R>, lenient: bool) -> Result<Vec<u64>, Error>
+where
+    R: std::io::Read + std::io::Seek,
+{
+    macro_rules! decode_vec {
+        ($ty:ty) => {{
+            let count = entry.count as usize;
+            let mut values = Vec::<$ty>::with_capacity(count);
+            let buffer = values.spare_capacity_mut();
+            unsafe {
+                entry.unchecked_decode_into(&mut buffer[..count])?;
+                values.set_len(count);
+            }
+            values
+        }};
+    }
+
+    let values = match entry.dtype {
+        DType::Byte if lenient => {
+            let values = decode_vec!(u8);
+            values.into_iter().map(|x| x as u64).collect()
+        }
+        DType::Short => {
+            let values = decode_vec!(u16);
+            values.into_iter().map(|x| x as u64).collect()
+        }
+        DType::Long | DType::Ifd => {
+            let values = decode_vec!(u32);
+            values.into_iter().map(|x| x as u64).collect()
+        }
+        DType::BigLong | DType::BigIfd => decode_vec!(u64),
+        dtype => Err(UnexpectedDType(dtype))?,
+    };
+    Ok(values)
 }
 
 /// The entry has an expected datatype.
 #[derive(Debug)]
-pub(crate) struct UnexpectedDType(DType);
+pub(crate) struct UnexpectedDType(pub(crate) DType);
 
 impl std::error::Error for UnexpectedDType {}
 
@@ -774,7 +1972,7 @@ impl std::fmt::Display for UnexpectedDType {
 
 /// A required tag is missing.
 #[derive(Debug)]
-pub(crate) struct MissingRequiredTag(Tag);
+pub(crate) struct MissingRequiredTag(pub(crate) Tag);
 
 impl std::error::Error for MissingRequiredTag {}
 
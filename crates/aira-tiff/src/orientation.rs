@@ -0,0 +1,83 @@
+/// The orientation of the image with respect to the rows and columns.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct Orientation(pub u16);
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Self::TOP_LEFT
+    }
+}
+
+impl std::fmt::Debug for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name(), self.0)
+    }
+}
+
+impl Orientation {
+    /// The 0th row represents the visual top of the image, and the 0th column the visual left.
+    pub const TOP_LEFT: Self = Self(1);
+    /// The 0th row represents the visual top of the image, and the 0th column the visual right.
+    pub const TOP_RIGHT: Self = Self(2);
+    /// The 0th row represents the visual bottom of the image, and the 0th column the visual right.
+    pub const BOTTOM_RIGHT: Self = Self(3);
+    /// The 0th row represents the visual bottom of the image, and the 0th column the visual left.
+    pub const BOTTOM_LEFT: Self = Self(4);
+    /// The 0th row represents the visual left of the image, and the 0th column the visual top.
+    pub const LEFT_TOP: Self = Self(5);
+    /// The 0th row represents the visual right of the image, and the 0th column the visual top.
+    pub const RIGHT_TOP: Self = Self(6);
+    /// The 0th row represents the visual right of the image, and the 0th column the visual bottom.
+    pub const RIGHT_BOTTOM: Self = Self(7);
+    /// The 0th row represents the visual left of the image, and the 0th column the visual bottom.
+    pub const LEFT_BOTTOM: Self = Self(8);
+}
+
+impl Orientation {
+    /// Returns `true` if the rows of the stored image are the columns of the upright image.
+    pub fn is_transposed(&self) -> bool {
+        matches!(self.0, 5..=8)
+    }
+
+    /// Returns the width and length of the upright image, given those of the stored image.
+    pub fn upright_dimensions(&self, (width, length): (u32, u32)) -> (u32, u32) {
+        match self.is_transposed() {
+            true => (length, width),
+            false => (width, length),
+        }
+    }
+
+    /// Returns the position in the stored image of the pixel at `(x, y)` in the upright image,
+    /// given the width and length of the stored image.
+    ///
+    /// Unknown orientations are handled as [`Orientation::TOP_LEFT`].
+    pub fn stored_position(&self, (x, y): (u32, u32), (width, length): (u32, u32)) -> (u32, u32) {
+        match self.0 {
+            2 => (width - 1 - x, y),
+            3 => (width - 1 - x, length - 1 - y),
+            4 => (x, length - 1 - y),
+            5 => (y, x),
+            6 => (y, length - 1 - x),
+            7 => (width - 1 - y, length - 1 - x),
+            8 => (width - 1 - y, x),
+            _ => (x, y),
+        }
+    }
+}
+
+impl Orientation {
+    /// Returns the name of the tag if known, otherwise "Unknown" is returned.
+    fn name(&self) -> &'static str {
+        match self.0 {
+            1 => "TopLeft",
+            2 => "TopRight",
+            3 => "BottomRight",
+            4 => "BottomLeft",
+            5 => "LeftTop",
+            6 => "RightTop",
+            7 => "RightBottom",
+            8 => "LeftBottom",
+            _ => "Unknown",
+        }
+    }
+}
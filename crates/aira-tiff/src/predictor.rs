@@ -80,8 +80,8 @@
 //! ```
 
 pub use self::{
-    float::FloatPredictorReader,
-    int::{IntPredictor, IntPredictorReader},
+    float::{FloatPredictorReader, FloatPredictorWriter},
+    int::{IntPredictor, IntPredictorReader, IntPredictorWriter},
 };
 
 mod float;
@@ -123,3 +123,23 @@ impl Predictor {
         }
     }
 }
+
+/// Writes the bytes of `buf` after the first `written` ones to `inner`.
+///
+/// The number of bytes written is updated as the writes succeed, so that the remaining bytes can
+/// be written again after an error.
+fn write_pending<W>(inner: &mut W, buf: &[u8], written: &mut usize) -> std::io::Result<()>
+where
+    W: std::io::Write,
+{
+    while *written < buf.len() {
+        match inner.write(&buf[*written..]) {
+            Ok(0) => return Err(std::io::ErrorKind::WriteZero.into()),
+            Ok(bytes_written) => *written += bytes_written,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use crate::{ByteOrder, Error};
+
 /// Decode data by rows using the inverse of floating point predictor.
 pub struct FloatPredictorReader<R> {
     /// The inner reader.
@@ -34,6 +36,35 @@ impl<R> FloatPredictorReader<R> {
         }
     }
 
+    /// Reads the decoded samples and converts them to `f32`.
+    ///
+    /// Samples of 2, 3 and 4 bytes are supported, see the [`float`](crate::float) module.
+    pub fn read_samples_f32(&mut self, dst: &mut [f32]) -> Result<(), Error>
+    where
+        R: std::io::Read,
+    {
+        let bytespersample = self.bytespersample;
+        crate::float::read_into(self, ByteOrder::native(), bytespersample, dst)
+    }
+
+    /// Reads the decoded half precision samples.
+    #[cfg(feature = "f16")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "f16")))]
+    pub fn read_samples_f16(&mut self, dst: &mut [f16]) -> Result<(), Error>
+    where
+        R: std::io::Read,
+    {
+        use crate::error::ErrorContext;
+
+        if self.bytespersample != 2 {
+            let err = Error::from(crate::Unsupported::SampleSize(self.bytespersample));
+            return Err(err.with_context(|| {
+                "Samples cannot be read as half precision floating point numbers"
+            }));
+        }
+        crate::float::read_f16_into(self, ByteOrder::native(), dst)
+    }
+
     /// Reads a new row from the inner reader and decodes the data in the inner buffer.
     fn read_another_row(&mut self) -> std::io::Result<()>
     where
@@ -54,6 +85,10 @@ impl<R> FloatPredictorReader<R> {
         let row = self.row.get_mut().deref_mut();
         let buffer = self.buffer.deref_mut();
 
+        if row.is_empty() {
+            return Ok(());
+        }
+
         // Apply the inverse of horizontal differencing.
         buffer[..samples].copy_from_slice(&row[..samples]);
         for col in 1..(row.len() / samples) {
@@ -104,6 +139,146 @@ where
     }
 }
 
+/// Encode data by rows using the floating point predictor.
+///
+/// The data written is expected in native byte order. A row partially written is kept in the
+/// inner buffer until it is completed, and a complete row is kept until it has been fully written
+/// to the inner writer, by the next call to `write`, `flush` or [`finish`](Self::finish).
+pub struct FloatPredictorWriter<W> {
+    /// The inner writer.
+    inner: W,
+    /// The buffer to hold intermediate results.
+    buffer: Box<[u8]>,
+    /// The buffer to hold the row being encoded.
+    row: Box<[u8]>,
+    /// The number of bytes of the row written so far, the row is encoded once it is complete.
+    len: usize,
+    /// The number of bytes of the encoded row written to the inner writer so far.
+    written: usize,
+    /// The number of samples per pixel.
+    samples: u16,
+    /// The number of bytes per sample.
+    bytespersample: u16,
+}
+
+impl<W> FloatPredictorWriter<W> {
+    /// Creates a new instance of [`FloatPredictorWriter`].
+    ///
+    /// This constructor allocates two buffers, each of the same size of a row. An error is
+    /// returned if the row is empty.
+    pub fn new(inner: W, ncols: u32, samples: u16, bytespersample: u16) -> Result<Self, Error> {
+        let row_size = ncols as usize * samples as usize * bytespersample as usize;
+        if row_size == 0 {
+            return Err(Error::from_static_str(
+                "Empty row cannot be encoded using floating point predictor",
+            ));
+        }
+
+        let buffer = vec![0u8; row_size].into_boxed_slice();
+        let row = vec![0u8; row_size].into_boxed_slice();
+
+        Ok(Self {
+            inner,
+            buffer,
+            row,
+            len: 0,
+            written: 0,
+            samples,
+            bytespersample,
+        })
+    }
+
+    /// Flushes the inner writer and returns it.
+    ///
+    /// An error is returned if the last row is incomplete.
+    pub fn finish(mut self) -> std::io::Result<W>
+    where
+        W: std::io::Write,
+    {
+        self.write_row()?;
+
+        if self.len != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Incomplete row cannot be encoded using floating point predictor",
+            ));
+        }
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Writes the rest of the encoded row to the inner writer, if the row is complete.
+    fn write_row(&mut self) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        if self.len != self.row.len() {
+            return Ok(());
+        }
+
+        super::write_pending(&mut self.inner, &self.row, &mut self.written)?;
+        self.len = 0;
+        self.written = 0;
+        Ok(())
+    }
+
+    /// Encodes the data in the inner buffer.
+    fn encode_row(&mut self) {
+        let samples = self.samples as usize;
+        let bytespersample = self.bytespersample as usize;
+
+        let row = &mut *self.row;
+        let buffer = &mut *self.buffer;
+
+        // Reorder the bytes from native endian to big endian
+        let cols = row.len() / bytespersample;
+        for col in 0..cols {
+            for byte in 0..bytespersample {
+                cfg_if::cfg_if! {
+                    if #[cfg(target_endian = "big")] {
+                        buffer[byte * cols + col] = row[col * bytespersample + byte];
+                    } else if #[cfg(target_endian = "little")] {
+                        buffer[(bytespersample - byte - 1) * cols + col] = row[col * bytespersample + byte];
+                    } else {
+                        compile_error!("Unsupported byte order");
+                    }
+                }
+            }
+        }
+
+        // Apply horizontal differencing.
+        row[..samples].copy_from_slice(&buffer[..samples]);
+        for index in samples..row.len() {
+            row[index] = buffer[index].wrapping_sub(buffer[index - samples]);
+        }
+    }
+}
+
+impl<W> std::io::Write for FloatPredictorWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_row()?;
+
+        let bytes_written = buf.len().min(self.row.len() - self.len);
+        self.row[self.len..][..bytes_written].copy_from_slice(&buf[..bytes_written]);
+        self.len += bytes_written;
+
+        if self.len == self.row.len() {
+            self.encode_row();
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_row()?;
+        self.inner.flush()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use aira_byteorder::{NativeEndian, ReadBytesExt};
@@ -139,4 +314,103 @@ mod tests {
 
         assert_eq!(values, [1f64, 2f64, 3f64, 4f64]);
     }
+
+    #[test]
+    fn reader_f24() {
+        use std::io::Write;
+
+        // 1.0, -1.5, 2.0 and 2^-78 as 24-bit floating point numbers.
+        let values = [0x3f_0000u32, 0xbf_8000, 0x40_0000, 0x00_0001];
+        let mut writer = assert_ok!(FloatPredictorWriter::new(Vec::new(), 2, 2, 3));
+        for value in values {
+            let bytes = value.to_ne_bytes();
+            let sample = if cfg!(target_endian = "big") {
+                &bytes[1..]
+            } else {
+                &bytes[..3]
+            };
+            assert_ok!(writer.write_all(sample));
+        }
+        let row = assert_ok!(writer.finish());
+
+        let mut reader = FloatPredictorReader::new(&row[..], 2, 2, 3);
+        let mut decoded = [0f32; 4];
+        assert_ok!(reader.read_samples_f32(&mut decoded));
+
+        assert_eq!(decoded, [1.0, -1.5, 2.0, 2f32.powi(-78)]);
+    }
+
+    #[test]
+    fn reader_unsupported_widening() {
+        let row = [0u8; 8];
+        let mut reader = FloatPredictorReader::new(&row[..], 1, 1, 8);
+
+        let err = assert_err!(reader.read_samples_f32(&mut [0f32; 1]));
+        assert_eq!(
+            err.kind(),
+            crate::ErrorKind::Unsupported(crate::Unsupported::SampleSize(8))
+        );
+    }
+
+    #[test]
+    fn writer_f32() {
+        use aira_byteorder::WriteBytesExt;
+
+        let mut writer = assert_ok!(FloatPredictorWriter::new(Vec::new(), 4, 1, 4));
+        for value in [1f32, 2f32, 3f32, 4f32] {
+            assert_ok!(writer.write_f32::<NativeEndian>(value));
+        }
+
+        let row = assert_ok!(writer.finish());
+        assert_eq!(
+            row,
+            [
+                0x3f, 0x01, 0x00, 0x00, 0x40, 0x80, 0x40, 0x40, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+                0x00, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn writer_empty_row() {
+        assert_some!(FloatPredictorWriter::new(std::io::sink(), 0, 1, 4).err());
+        assert_some!(FloatPredictorWriter::new(std::io::sink(), 4, 0, 4).err());
+    }
+
+    #[test]
+    fn writer_round_trip() {
+        use std::io::{Read, Write};
+
+        for bytespersample in [2, 4, 8] {
+            for samples in 1..=5 {
+                let ncols = fastrand::u32(1..=16);
+                let nrows = fastrand::usize(1..=4);
+                let row_size = ncols as usize * samples as usize * bytespersample as usize;
+                let data = std::iter::repeat_with(|| fastrand::u8(..))
+                    .take(row_size * nrows)
+                    .collect::<Vec<_>>();
+
+                let mut writer = assert_ok!(FloatPredictorWriter::new(
+                    Vec::new(),
+                    ncols,
+                    samples,
+                    bytespersample
+                ));
+                let mut remaining = &data[..];
+                while !remaining.is_empty() {
+                    let size = fastrand::usize(1..=remaining.len());
+                    assert_ok!(writer.write_all(&remaining[..size]));
+                    remaining = &remaining[size..];
+                }
+                let encoded = assert_ok!(writer.finish());
+
+                let mut reader =
+                    FloatPredictorReader::new(&encoded[..], ncols, samples, bytespersample);
+                let mut decoded = vec![0; data.len()];
+                assert_ok!(reader.read_exact(&mut decoded));
+
+                assert_eq!(decoded, data, "{samples} samples of {bytespersample} bytes");
+            }
+        }
+    }
 }
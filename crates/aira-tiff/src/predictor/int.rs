@@ -1,8 +1,11 @@
 use std::marker::PhantomData;
 
-use crate::{ByteOrder, Error};
+use crate::{error::ErrorContext, ByteOrder, Error, Unsupported};
 
 mod fixed;
+mod packed;
+#[cfg(target_arch = "x86_64")]
+mod simd;
 
 /// A trait for decoding a row of data in-place.
 trait Decoder {
@@ -15,6 +18,11 @@ impl Decoder for fn(&mut [u8]) {
     }
 }
 
+/// A trait for encoding a row of data in-place.
+trait Encoder {
+    fn encode(&mut self, row: &mut [u8]);
+}
+
 /// Decode a single row of data in-place.
 pub struct IntPredictor {
     decoder: Box<dyn Decoder>,
@@ -28,6 +36,21 @@ impl IntPredictor {
         Ok(Self { decoder })
     }
 
+    /// Creates a new instance of [`IntPredictor`] for samples of any number of bits.
+    ///
+    /// Samples whose size is not a whole number of bytes are packed in rows of `ncols` pixels,
+    /// see [`IntPredictorReader::with_bits`].
+    pub fn with_bits(
+        byteorder: ByteOrder,
+        ncols: u32,
+        samples: u16,
+        bitspersample: u16,
+    ) -> Result<Self, Error> {
+        let decoder = build_decoder_with_bits(byteorder, ncols, samples, bitspersample)?;
+
+        Ok(Self { decoder })
+    }
+
     /// Decode a row of data in-place.
     ///
     /// This function applies both the fix of the endianness and the computation of cumulated values.
@@ -60,18 +83,41 @@ impl<R> IntPredictorReader<R> {
         bytespersample: u16,
     ) -> Result<Self, Error> {
         let row_size = ncols as usize * samples as usize * bytespersample as usize;
+        let decoder = build_decoder(byteorder, samples, bytespersample)?;
+
+        Ok(Self::with_decoder(inner, row_size, decoder))
+    }
+
+    /// Creates a new instance of [`IntPredictorReader`] for samples of any number of bits.
+    ///
+    /// Samples whose size is not a whole number of bytes are packed starting from the most
+    /// significant bit, and each row is padded to a whole number of bytes. Packed samples are not
+    /// affected by the byte order, and they are decoded in-place.
+    ///
+    /// This constructor allocates a buffer of the same size of a row.
+    pub fn with_bits(
+        inner: R,
+        byteorder: ByteOrder,
+        ncols: u32,
+        samples: u16,
+        bitspersample: u16,
+    ) -> Result<Self, Error> {
+        let row_size = row_size(ncols, samples, bitspersample);
+        let decoder = build_decoder_with_bits(byteorder, ncols, samples, bitspersample)?;
+
+        Ok(Self::with_decoder(inner, row_size, decoder))
+    }
 
+    fn with_decoder(inner: R, row_size: usize, decoder: Box<dyn Decoder>) -> Self {
         let row = vec![0u8; row_size].into_boxed_slice();
         let mut row = std::io::Cursor::new(row);
         row.set_position(row_size as u64);
 
-        let decoder = build_decoder(byteorder, samples, bytespersample)?;
-
-        Ok(Self {
+        Self {
             inner,
             row,
             decoder,
-        })
+        }
     }
 
     /// Reads a new row from the inner reader and decodes the data in the inner buffer.
@@ -107,6 +153,196 @@ where
     }
 }
 
+/// Encode data by rows using the integer predictor.
+///
+/// The data written is expected in native byte order, whole rows are encoded by computing the
+/// differences between subsequent pixels, and written to the inner writer in the given byte order.
+/// A row partially written is kept in the inner buffer until it is completed, and a complete row
+/// is kept until it has been fully written to the inner writer, by the next call to `write`,
+/// `flush` or [`finish`](Self::finish).
+pub struct IntPredictorWriter<W> {
+    /// The inner writer.
+    inner: W,
+    /// The buffer to hold the row being encoded.
+    row: Box<[u8]>,
+    /// The number of bytes of the row written so far, the row is encoded once it is complete.
+    len: usize,
+    /// The number of bytes of the encoded row written to the inner writer so far.
+    written: usize,
+    /// The encoder that will be used to encode the data.
+    encoder: Box<dyn Encoder>,
+}
+
+impl<W> IntPredictorWriter<W> {
+    /// Creates a new instance of [`IntPredictorWriter`].
+    ///
+    /// This constructor allocates a buffer of the same size of a row.
+    pub fn new(
+        inner: W,
+        byteorder: ByteOrder,
+        ncols: u32,
+        samples: u16,
+        bytespersample: u16,
+    ) -> Result<Self, Error> {
+        let row_size = ncols as usize * samples as usize * bytespersample as usize;
+        let encoder = build_encoder(byteorder, samples, bytespersample)?;
+
+        Self::with_encoder(inner, row_size, encoder)
+    }
+
+    /// Creates a new instance of [`IntPredictorWriter`] for samples of any number of bits.
+    ///
+    /// The layout of packed samples is described in [`IntPredictorReader::with_bits`].
+    ///
+    /// This constructor allocates a buffer of the same size of a row.
+    pub fn with_bits(
+        inner: W,
+        byteorder: ByteOrder,
+        ncols: u32,
+        samples: u16,
+        bitspersample: u16,
+    ) -> Result<Self, Error> {
+        let row_size = row_size(ncols, samples, bitspersample);
+        let encoder = build_encoder_with_bits(byteorder, ncols, samples, bitspersample)?;
+
+        Self::with_encoder(inner, row_size, encoder)
+    }
+
+    fn with_encoder(inner: W, row_size: usize, encoder: Box<dyn Encoder>) -> Result<Self, Error> {
+        if row_size == 0 {
+            return Err(Error::from_static_str(
+                "Empty row cannot be encoded using integer predictor",
+            ));
+        }
+
+        let row = vec![0u8; row_size].into_boxed_slice();
+
+        Ok(Self {
+            inner,
+            row,
+            len: 0,
+            written: 0,
+            encoder,
+        })
+    }
+
+    /// Flushes the inner writer and returns it.
+    ///
+    /// An error is returned if the last row is incomplete.
+    pub fn finish(mut self) -> std::io::Result<W>
+    where
+        W: std::io::Write,
+    {
+        self.write_row()?;
+
+        if self.len != 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Incomplete row cannot be encoded using integer predictor",
+            ));
+        }
+
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    /// Writes the rest of the encoded row to the inner writer, if the row is complete.
+    fn write_row(&mut self) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        if self.len != self.row.len() {
+            return Ok(());
+        }
+
+        super::write_pending(&mut self.inner, &self.row, &mut self.written)?;
+        self.len = 0;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl<W> std::io::Write for IntPredictorWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_row()?;
+
+        let bytes_written = buf.len().min(self.row.len() - self.len);
+        self.row[self.len..][..bytes_written].copy_from_slice(&buf[..bytes_written]);
+        self.len += bytes_written;
+
+        if self.len == self.row.len() {
+            self.encoder.encode(&mut self.row);
+        }
+
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.write_row()?;
+        self.inner.flush()
+    }
+}
+
+/// Returns the size in bytes of a row, padded to a whole number of bytes.
+fn row_size(ncols: u32, samples: u16, bitspersample: u16) -> usize {
+    (ncols as usize * samples as usize * bitspersample as usize).div_ceil(8)
+}
+
+/// Builds the appropriate decoder according to the number of bits per sample.
+fn build_decoder_with_bits(
+    byteorder: ByteOrder,
+    ncols: u32,
+    samples: u16,
+    bitspersample: u16,
+) -> Result<Box<dyn Decoder>, Error> {
+    if bitspersample.is_multiple_of(8) {
+        return build_decoder(byteorder, samples, bitspersample / 8);
+    }
+
+    check_packed(samples, bitspersample, "decoded")?;
+    Ok(Box::new(packed::Packed::new(ncols, samples, bitspersample)))
+}
+
+/// Builds the appropriate encoder according to the number of bits per sample.
+fn build_encoder_with_bits(
+    byteorder: ByteOrder,
+    ncols: u32,
+    samples: u16,
+    bitspersample: u16,
+) -> Result<Box<dyn Encoder>, Error> {
+    if bitspersample.is_multiple_of(8) {
+        return build_encoder(byteorder, samples, bitspersample / 8);
+    }
+
+    check_packed(samples, bitspersample, "encoded")?;
+    Ok(Box::new(packed::Packed::new(ncols, samples, bitspersample)))
+}
+
+/// Checks the parameters of packed samples.
+fn check_packed(samples: u16, bitspersample: u16, operation: &str) -> Result<(), Error> {
+    if samples == 0 {
+        return Err(Error::from_args(format_args!(
+            "Pixel without samples cannot be {operation} using integer predictor"
+        )));
+    }
+
+    if bitspersample > packed::MAX_BITS {
+        return Err(
+            Error::from(Unsupported::BitsPerSample(bitspersample)).with_context(|| {
+                format!(
+                    "Pixel with {samples} samples with {bitspersample} bits cannot be {operation} \
+                     using integer predictor"
+                )
+            }),
+        );
+    }
+
+    Ok(())
+}
+
 /// Builds the appropriate decoder according to the parameters.
 fn build_decoder(
     byteorder: ByteOrder,
@@ -115,6 +351,17 @@ fn build_decoder(
 ) -> Result<Box<dyn Decoder>, Error> {
     use aira_byteorder::{BE, LE};
 
+    if samples == 0 {
+        return Err(Error::from_static_str(
+            "Pixel without samples cannot be decoded using integer predictor",
+        ));
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    if let Some(decoder) = simd::build_decoder(byteorder, samples, bytespersample) {
+        return Ok(decoder);
+    }
+
     let decoder: Box<dyn Decoder> = match (byteorder, samples, bytespersample) {
         (_, 1, 1) => Box::new(fixed::decode_u8::<1> as fn(&mut [u8])),
         (_, 2, 1) => Box::new(fixed::decode_u8::<2> as fn(&mut [u8])),
@@ -133,6 +380,8 @@ fn build_decoder(
         (ByteOrder::BigEndian, n, 2) => Box::new(DecodeU16::<BE>::new(n)),
         (ByteOrder::LittleEndian, n, 2) => Box::new(DecodeU16::<LE>::new(n)),
 
+        (byteorder, n, 3) => Box::new(DecodeU24::new(byteorder, n)),
+
         (ByteOrder::BigEndian, 1, 4) => Box::new(fixed::decode_u32::<1, BE> as fn(&mut [u8])),
         (ByteOrder::LittleEndian, 1, 4) => Box::new(fixed::decode_u32::<1, LE> as fn(&mut [u8])),
         (ByteOrder::BigEndian, 2, 4) => Box::new(fixed::decode_u32::<2, BE> as fn(&mut [u8])),
@@ -156,16 +405,54 @@ fn build_decoder(
         (ByteOrder::LittleEndian, n, 8) => Box::new(DecodeU64::<LE>::new(n)),
 
         _ => {
-            return Err(Error::from_args(format_args!(
-                "Pixel with {} samples with size {} cannot be decoded using integer predictor",
-                samples, bytespersample
-            )));
+            return Err(Error::from(Unsupported::SampleSize(bytespersample)).with_context(|| {
+                format!(
+                    "Pixel with {} samples with size {} cannot be decoded using integer predictor",
+                    samples, bytespersample
+                )
+            }));
         }
     };
 
     Ok(decoder)
 }
 
+/// Builds the appropriate encoder according to the parameters.
+fn build_encoder(
+    byteorder: ByteOrder,
+    samples: u16,
+    bytespersample: u16,
+) -> Result<Box<dyn Encoder>, Error> {
+    use aira_byteorder::{BE, LE};
+
+    if samples == 0 {
+        return Err(Error::from_static_str(
+            "Pixel without samples cannot be encoded using integer predictor",
+        ));
+    }
+
+    let encoder: Box<dyn Encoder> = match (byteorder, bytespersample) {
+        (_, 1) => Box::new(EncodeU8::new(samples)),
+        (ByteOrder::BigEndian, 2) => Box::new(EncodeU16::<BE>::new(samples)),
+        (ByteOrder::LittleEndian, 2) => Box::new(EncodeU16::<LE>::new(samples)),
+        (byteorder, 3) => Box::new(EncodeU24::new(byteorder, samples)),
+        (ByteOrder::BigEndian, 4) => Box::new(EncodeU32::<BE>::new(samples)),
+        (ByteOrder::LittleEndian, 4) => Box::new(EncodeU32::<LE>::new(samples)),
+        (ByteOrder::BigEndian, 8) => Box::new(EncodeU64::<BE>::new(samples)),
+        (ByteOrder::LittleEndian, 8) => Box::new(EncodeU64::<LE>::new(samples)),
+        _ => {
+            return Err(Error::from(Unsupported::SampleSize(bytespersample)).with_context(|| {
+                format!(
+                    "Pixel with {} samples with size {} cannot be encoded using integer predictor",
+                    samples, bytespersample
+                )
+            }));
+        }
+    };
+
+    Ok(encoder)
+}
+
 // Decodes a multiple samples per pixel image with 1 byte per sample.
 struct DecodeU8 {
     /// This is the value accumulated so far.
@@ -261,10 +548,440 @@ impl_decode_uint![DecodeU16 using (read_slice_u16, write_slice_u16) -> u16];
 impl_decode_uint![DecodeU32 using (read_slice_u32, write_slice_u32) -> u32];
 impl_decode_uint![DecodeU64 using (read_slice_u64, write_slice_u64) -> u64];
 
+/// Reads a 24-bit sample with the given byte order.
+fn read_u24(byteorder: ByteOrder, sample: &[u8]) -> u32 {
+    let bytes = [sample[0], sample[1], sample[2]];
+    match byteorder {
+        ByteOrder::BigEndian => u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]),
+        ByteOrder::LittleEndian => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]),
+    }
+}
+
+/// Writes a 24-bit sample with the given byte order.
+fn write_u24(byteorder: ByteOrder, value: u32, sample: &mut [u8]) {
+    match byteorder {
+        ByteOrder::BigEndian => sample.copy_from_slice(&value.to_be_bytes()[1..]),
+        ByteOrder::LittleEndian => sample.copy_from_slice(&value.to_le_bytes()[..3]),
+    }
+}
+
+// Decodes an image with 3 bytes per sample, the samples are written in native byte order.
+struct DecodeU24 {
+    byteorder: ByteOrder,
+    /// This is the value accumulated so far.
+    acc: Vec<u32>,
+}
+
+impl DecodeU24 {
+    fn new(byteorder: ByteOrder, samples: u16) -> Self {
+        let samples = samples as usize;
+        Self {
+            byteorder,
+            acc: vec![0; samples],
+        }
+    }
+}
+
+impl Decoder for DecodeU24 {
+    fn decode(&mut self, row: &mut [u8]) {
+        let pixel_size = 3 * self.acc.len();
+
+        let mut pixels = row.chunks_exact_mut(pixel_size);
+        if let Some(first_pixel) = pixels.next() {
+            self.acc
+                .iter_mut()
+                .zip(first_pixel.chunks_exact_mut(3))
+                .for_each(|(acc, sample)| {
+                    *acc = read_u24(self.byteorder, sample);
+                    write_u24(ByteOrder::native(), *acc, sample);
+                });
+
+            for pixel in &mut pixels {
+                self.acc
+                    .iter_mut()
+                    .zip(pixel.chunks_exact_mut(3))
+                    .for_each(|(acc, sample)| {
+                        *acc = acc.wrapping_add(read_u24(self.byteorder, sample)) & 0xff_ffff;
+                        write_u24(ByteOrder::native(), *acc, sample);
+                    });
+            }
+        }
+
+        assert!(pixels.into_remainder().is_empty());
+    }
+}
+
+// Encodes an image with 1 byte per sample.
+struct EncodeU8 {
+    /// This is the previous pixel, before encoding.
+    previous: Vec<u8>,
+}
+
+impl EncodeU8 {
+    fn new(samples: u16) -> Self {
+        let samples = samples as usize;
+        Self {
+            previous: vec![0; samples],
+        }
+    }
+}
+
+impl Encoder for EncodeU8 {
+    fn encode(&mut self, row: &mut [u8]) {
+        let pixel_size = size_of_val(&self.previous[..]);
+
+        let mut pixels = row.chunks_exact_mut(pixel_size);
+        if let Some(first_pixel) = pixels.next() {
+            self.previous.copy_from_slice(first_pixel);
+
+            for pixel in &mut pixels {
+                self.previous
+                    .iter_mut()
+                    .zip(pixel.iter_mut())
+                    .for_each(|(previous, sample)| {
+                        let value = *sample;
+                        *sample = value.wrapping_sub(*previous);
+                        *previous = value;
+                    });
+            }
+        }
+
+        assert!(pixels.into_remainder().is_empty());
+    }
+}
+
+macro_rules! impl_encode_uint {
+    ($name:ident using ($read_into:ident, $write_into:ident) -> $ty:ident) => {
+        struct $name<B> {
+            /// This is the buffer to hold the current pixel, in native byte order.
+            buffer: Vec<$ty>,
+            /// This is the previous pixel, before encoding.
+            previous: Vec<$ty>,
+            _byteorder: PhantomData<B>,
+        }
+
+        impl<B> $name<B> {
+            fn new(samples: u16) -> Self {
+                let samples = samples as usize;
+                Self {
+                    buffer: vec![0; samples],
+                    previous: vec![0; samples],
+                    _byteorder: PhantomData,
+                }
+            }
+        }
+
+        impl<B> Encoder for $name<B>
+        where
+            B: aira_byteorder::ByteOrder,
+        {
+            fn encode(&mut self, row: &mut [u8]) {
+                use aira_byteorder::ByteOrder;
+
+                let pixel_size = size_of_val(&self.previous[..]);
+
+                let mut pixels = row.chunks_exact_mut(pixel_size);
+                if let Some(first_pixel) = pixels.next() {
+                    aira_byteorder::NativeEndian::$read_into(first_pixel, &mut self.previous);
+                    B::$write_into(&self.previous, first_pixel);
+
+                    for pixel in &mut pixels {
+                        aira_byteorder::NativeEndian::$read_into(pixel, &mut self.buffer);
+                        self.previous
+                            .iter_mut()
+                            .zip(self.buffer.iter_mut())
+                            .for_each(|(previous, sample)| {
+                                let value = *sample;
+                                *sample = value.wrapping_sub(*previous);
+                                *previous = value;
+                            });
+                        B::$write_into(&self.buffer, pixel);
+                    }
+                }
+
+                assert!(pixels.into_remainder().is_empty());
+            }
+        }
+    };
+}
+
+impl_encode_uint![EncodeU16 using (read_slice_u16, write_slice_u16) -> u16];
+impl_encode_uint![EncodeU32 using (read_slice_u32, write_slice_u32) -> u32];
+impl_encode_uint![EncodeU64 using (read_slice_u64, write_slice_u64) -> u64];
+
+// Encodes an image with 3 bytes per sample.
+struct EncodeU24 {
+    byteorder: ByteOrder,
+    /// This is the previous pixel, before encoding.
+    previous: Vec<u32>,
+}
+
+impl EncodeU24 {
+    fn new(byteorder: ByteOrder, samples: u16) -> Self {
+        let samples = samples as usize;
+        Self {
+            byteorder,
+            previous: vec![0; samples],
+        }
+    }
+}
+
+impl Encoder for EncodeU24 {
+    fn encode(&mut self, row: &mut [u8]) {
+        let pixel_size = 3 * self.previous.len();
+
+        let mut pixels = row.chunks_exact_mut(pixel_size);
+        if let Some(first_pixel) = pixels.next() {
+            self.previous
+                .iter_mut()
+                .zip(first_pixel.chunks_exact_mut(3))
+                .for_each(|(previous, sample)| {
+                    *previous = read_u24(ByteOrder::native(), sample);
+                    write_u24(self.byteorder, *previous, sample);
+                });
+
+            for pixel in &mut pixels {
+                self.previous
+                    .iter_mut()
+                    .zip(pixel.chunks_exact_mut(3))
+                    .for_each(|(previous, sample)| {
+                        let value = read_u24(ByteOrder::native(), sample);
+                        let diff = value.wrapping_sub(*previous) & 0xff_ffff;
+                        *previous = value;
+                        write_u24(self.byteorder, diff, sample);
+                    });
+            }
+        }
+
+        assert!(pixels.into_remainder().is_empty());
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use aira_byteorder::{BE, LE};
+    use claims::*;
+
+    /// Writes the data in chunks of random sizes.
+    fn write_randomly(writer: &mut impl std::io::Write, mut data: &[u8]) {
+        while !data.is_empty() {
+            let size = fastrand::usize(1..=data.len());
+            assert_ok!(writer.write_all(&data[..size]));
+            data = &data[size..];
+        }
+    }
+
+    #[test]
+    fn writer_round_trip() {
+        use std::io::Read;
+
+        for byteorder in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+            for bytespersample in [1, 2, 4, 8] {
+                for samples in 1..=5 {
+                    let ncols = fastrand::u32(1..=16);
+                    let nrows = fastrand::usize(1..=4);
+                    let row_size = ncols as usize * samples as usize * bytespersample as usize;
+                    let data = std::iter::repeat_with(|| fastrand::u8(..))
+                        .take(row_size * nrows)
+                        .collect::<Vec<_>>();
+
+                    let mut writer = assert_ok!(IntPredictorWriter::new(
+                        Vec::new(),
+                        byteorder,
+                        ncols,
+                        samples,
+                        bytespersample,
+                    ));
+                    write_randomly(&mut writer, &data);
+                    let encoded = assert_ok!(writer.finish());
+                    assert_eq!(encoded.len(), data.len());
+
+                    let mut reader = assert_ok!(IntPredictorReader::new(
+                        &encoded[..],
+                        byteorder,
+                        ncols,
+                        samples,
+                        bytespersample,
+                    ));
+                    let mut decoded = vec![0; data.len()];
+                    assert_ok!(reader.read_exact(&mut decoded));
+
+                    assert_eq!(
+                        decoded, data,
+                        "{byteorder:?} with {samples} samples of {bytespersample} bytes"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn writer_round_trip_with_bits() {
+        use std::io::Read;
+
+        for byteorder in [ByteOrder::BigEndian, ByteOrder::LittleEndian] {
+            for bitspersample in [1, 2, 4, 8, 12, 16, 24] {
+                for samples in 1..=4 {
+                    let ncols = fastrand::u32(1..=16);
+                    let nrows = fastrand::usize(1..=4);
+                    let row_size = row_size(ncols, samples, bitspersample);
+                    let data = std::iter::repeat_with(|| fastrand::u8(..))
+                        .take(row_size * nrows)
+                        .collect::<Vec<_>>();
+
+                    let mut writer = assert_ok!(IntPredictorWriter::with_bits(
+                        Vec::new(),
+                        byteorder,
+                        ncols,
+                        samples,
+                        bitspersample,
+                    ));
+                    write_randomly(&mut writer, &data);
+                    let encoded = assert_ok!(writer.finish());
+
+                    let mut reader = assert_ok!(IntPredictorReader::with_bits(
+                        &encoded[..],
+                        byteorder,
+                        ncols,
+                        samples,
+                        bitspersample,
+                    ));
+                    let mut decoded = vec![0; data.len()];
+                    assert_ok!(reader.read_exact(&mut decoded));
+
+                    assert_eq!(
+                        decoded, data,
+                        "{byteorder:?} with {samples} samples of {bitspersample} bits"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_bits_per_sample() {
+        let err = assert_some!(IntPredictor::with_bits(ByteOrder::LittleEndian, 1, 1, 17).err());
+        assert_eq!(
+            err.kind(),
+            crate::ErrorKind::Unsupported(Unsupported::BitsPerSample(17))
+        );
+    }
+
+    #[test]
+    fn decode_u24_be() {
+        let mut row = [0x00, 0x00, 0x01, 0x00, 0x01, 0x00, 0xff, 0xff, 0xff];
+        DecodeU24::new(ByteOrder::BigEndian, 1).decode(&mut row);
+
+        let values = row
+            .chunks_exact(3)
+            .map(|sample| read_u24(ByteOrder::native(), sample))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [0x000001, 0x000101, 0x000100]);
+    }
+
+    #[test]
+    fn decode_u24_le_x2() {
+        let mut row = [
+            0x01, 0x00, 0x00, 0x02, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00,
+        ];
+        DecodeU24::new(ByteOrder::LittleEndian, 2).decode(&mut row);
+
+        let values = row
+            .chunks_exact(3)
+            .map(|sample| read_u24(ByteOrder::native(), sample))
+            .collect::<Vec<_>>();
+        assert_eq!(values, [1, 2, 2, 3]);
+    }
+
+    #[test]
+    fn writer_incomplete_row() {
+        use std::io::Write;
+
+        let mut writer = assert_ok!(IntPredictorWriter::new(
+            Vec::new(),
+            ByteOrder::LittleEndian,
+            4,
+            1,
+            2
+        ));
+        assert_ok!(writer.write_all(&[0; 12]));
+        assert_eq!(
+            assert_err!(writer.finish()).kind(),
+            std::io::ErrorKind::InvalidInput
+        );
+    }
+
+    #[test]
+    fn writer_empty_row() {
+        let writer = IntPredictorWriter::new(std::io::sink(), ByteOrder::LittleEndian, 0, 1, 2);
+        assert_some!(writer.err());
+
+        let writer =
+            IntPredictorWriter::with_bits(std::io::sink(), ByteOrder::LittleEndian, 0, 1, 4);
+        assert_some!(writer.err());
+    }
+
+    /// A writer accepting at most 3 bytes at once, and failing every other call.
+    #[derive(Default)]
+    struct FlakyWriter {
+        data: Vec<u8>,
+        calls: usize,
+    }
+
+    impl std::io::Write for FlakyWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.calls += 1;
+            match self.calls % 4 {
+                1 => Err(std::io::ErrorKind::Interrupted.into()),
+                3 => Err(std::io::ErrorKind::Other.into()),
+                _ => {
+                    let size = buf.len().min(3);
+                    self.data.extend_from_slice(&buf[..size]);
+                    Ok(size)
+                }
+            }
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writer_failed_writes() {
+        use std::io::Write;
+
+        let data = [1u8, 2, 4, 8, 16, 32, 64, 128, 0, 1, 3, 6, 10, 15, 21, 28];
+        let mut writer = assert_ok!(IntPredictorWriter::new(
+            FlakyWriter::default(),
+            ByteOrder::native(),
+            8,
+            1,
+            1
+        ));
+
+        let mut remaining = &data[..];
+        while !remaining.is_empty() {
+            if let Ok(size) = writer.write(remaining) {
+                remaining = &remaining[size..];
+            }
+        }
+        while writer.flush().is_err() {}
+
+        let inner = assert_ok!(writer.finish());
+        assert_eq!(
+            inner.data,
+            [1, 1, 2, 4, 8, 16, 32, 64, 0, 1, 2, 3, 4, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn encode_u16_x2_be() {
+        let mut row = [1u16, 2, 2, 4, 3, 6, 4, 8];
+        EncodeU16::<BE>::new(2).encode(as_mut_bytes(&mut row));
+        assert_eq!(row.map(u16::from_be), [1, 2, 1, 2, 1, 2, 1, 2]);
+    }
 
     fn as_mut_bytes<T>(values: &mut [T]) -> &mut [u8] {
         unsafe { std::slice::from_raw_parts_mut(values.as_ptr() as *mut u8, size_of_val(values)) }
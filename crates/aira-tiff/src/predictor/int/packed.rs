@@ -0,0 +1,153 @@
+//! Implementation of the integer predictor for samples packed in bits.
+//!
+//! The samples are packed starting from the most significant bit of each byte, without padding
+//! between pixels, and each row is padded to a whole number of bytes. The differences are computed
+//! modulo `2^bits`, the padding bits are left untouched.
+
+use super::{Decoder, Encoder};
+
+/// The maximum number of bits of a packed sample.
+pub const MAX_BITS: u16 = 16;
+
+/// Decodes and encodes a row of packed samples.
+pub struct Packed {
+    /// The number of bits of a sample.
+    bits: u32,
+    /// The number of samples of a pixel.
+    samples: usize,
+    /// The number of pixels of a row.
+    ncols: usize,
+}
+
+impl Packed {
+    pub fn new(ncols: u32, samples: u16, bits: u16) -> Self {
+        debug_assert!((1..=MAX_BITS).contains(&bits));
+        Self {
+            bits: bits as u32,
+            samples: samples as usize,
+            ncols: ncols as usize,
+        }
+    }
+
+    /// Returns the number of samples in the row, ignoring incomplete pixels.
+    fn samples_count(&self, row: &[u8]) -> usize {
+        let pixel_bits = self.samples * self.bits as usize;
+        let ncols = self.ncols.min(row.len() * 8 / pixel_bits);
+        ncols * self.samples
+    }
+
+    fn mask(&self) -> u32 {
+        (1 << self.bits) - 1
+    }
+}
+
+impl Decoder for Packed {
+    fn decode(&mut self, row: &mut [u8]) {
+        let mask = self.mask();
+        for index in self.samples..self.samples_count(row) {
+            let previous = read_bits(row, (index - self.samples) * self.bits as usize, self.bits);
+            let position = index * self.bits as usize;
+            let value = read_bits(row, position, self.bits).wrapping_add(previous) & mask;
+            write_bits(row, position, self.bits, value);
+        }
+    }
+}
+
+impl Encoder for Packed {
+    fn encode(&mut self, row: &mut [u8]) {
+        let mask = self.mask();
+        for index in (self.samples..self.samples_count(row)).rev() {
+            let previous = read_bits(row, (index - self.samples) * self.bits as usize, self.bits);
+            let position = index * self.bits as usize;
+            let value = read_bits(row, position, self.bits).wrapping_sub(previous) & mask;
+            write_bits(row, position, self.bits, value);
+        }
+    }
+}
+
+/// Reads `bits` bits starting at the given bit position.
+fn read_bits(row: &[u8], position: usize, bits: u32) -> u32 {
+    let start = position / 8;
+    let end = (position + bits as usize).div_ceil(8);
+
+    // At most 3 bytes hold a sample of 16 bits.
+    let word = row[start..end]
+        .iter()
+        .fold(0u32, |word, &byte| (word << 8) | byte as u32);
+    let shift = (end * 8 - position) as u32 - bits;
+    (word >> shift) & ((1 << bits) - 1)
+}
+
+/// Writes `bits` bits starting at the given bit position.
+fn write_bits(row: &mut [u8], position: usize, bits: u32, value: u32) {
+    let start = position / 8;
+    let end = (position + bits as usize).div_ceil(8);
+
+    let word = row[start..end]
+        .iter()
+        .fold(0u32, |word, &byte| (word << 8) | byte as u32);
+    let shift = (end * 8 - position) as u32 - bits;
+    let mask = ((1 << bits) - 1) << shift;
+    let mut word = (word & !mask) | (value << shift);
+
+    for byte in row[start..end].iter_mut().rev() {
+        *byte = word as u8;
+        word >>= 8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use super::*;
+
+    #[test]
+    fn decode_1bit() {
+        let mut row = [0b1000_0000, 0b1000_0000];
+        Packed::new(10, 1, 1).decode(&mut row);
+        assert_eq!(row, [0b1111_1111, 0b0000_0000]);
+    }
+
+    #[test]
+    fn decode_4bit_x2() {
+        let mut row = [0x12, 0x11, 0x11];
+        Packed::new(3, 2, 4).decode(&mut row);
+        assert_eq!(row, [0x12, 0x23, 0x34]);
+    }
+
+    #[test]
+    fn decode_12bit() {
+        let mut row = [0x00, 0x1f, 0xff, 0x00];
+        Packed::new(2, 1, 12).decode(&mut row);
+        assert_eq!(row, [0x00, 0x10, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn padding_is_untouched() {
+        let mut row = [0xff, 0xff];
+        Packed::new(3, 1, 4).decode(&mut row);
+        assert_eq!(row, [0xfe, 0xdf]);
+    }
+
+    #[test]
+    fn round_trip() {
+        for bits in 1..=MAX_BITS {
+            for samples in 1..=3 {
+                let ncols = fastrand::u32(1..=32);
+                let row_size = (ncols as usize * samples as usize * bits as usize).div_ceil(8);
+                let row = repeat_with(|| fastrand::u8(..))
+                    .take(row_size)
+                    .collect::<Vec<_>>();
+
+                let mut packed = Packed::new(ncols, samples, bits);
+                let mut encoded = row.clone();
+                packed.encode(&mut encoded);
+                let mut decoded = encoded.clone();
+                packed.decode(&mut decoded);
+
+                assert_eq!(decoded, row, "{samples} samples of {bits} bits");
+            }
+        }
+    }
+}
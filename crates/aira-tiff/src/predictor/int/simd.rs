@@ -0,0 +1,466 @@
+//! Implementation of decode kernels using SIMD instructions.
+//!
+//! Each register is loaded from the row, its samples are converted to native byte order and then
+//! summed up using a logarithmic number of shifts and additions. The last pixel of the register
+//! is broadcast and carried to the next one. The bytes that do not fill a whole register are
+//! decoded one sample at a time.
+//!
+//! The kernels are available for pixels whose size is a power of two of at most 16 bytes. The
+//! SSE2 kernels are always available, while the AVX2 kernels are selected at runtime when
+//! supported by the CPU.
+
+use std::arch::x86_64::*;
+
+use aira_byteorder::{ByteOrder as _, NativeEndian};
+
+use super::Decoder;
+use crate::ByteOrder;
+
+/// A decoder calling a kernel that uses SIMD instructions.
+///
+/// It is only built after checking that the CPU supports the instructions used by the kernel.
+struct SimdDecoder(unsafe fn(&mut [u8]));
+
+impl Decoder for SimdDecoder {
+    fn decode(&mut self, row: &mut [u8]) {
+        // SAFETY: The CPU features required by the kernel are detected by `build_decoder`.
+        unsafe { (self.0)(row) }
+    }
+}
+
+/// Builds a decoder using SIMD instructions, if there is a kernel for the given parameters.
+pub fn build_decoder(
+    byteorder: ByteOrder,
+    samples: u16,
+    bytespersample: u16,
+) -> Option<Box<dyn Decoder>> {
+    let kernel = if std::is_x86_feature_detected!("avx2") {
+        select_kernel::<Avx2>(byteorder, samples, bytespersample)
+    } else {
+        select_kernel::<Sse2>(byteorder, samples, bytespersample)
+    }?;
+
+    Some(Box::new(SimdDecoder(kernel)))
+}
+
+/// Selects the kernel of the given instruction set for the given parameters.
+fn select_kernel<I: Isa>(
+    byteorder: ByteOrder,
+    samples: u16,
+    bytespersample: u16,
+) -> Option<unsafe fn(&mut [u8])> {
+    use aira_byteorder::{BE, LE};
+
+    let pixel_size = samples as usize * bytespersample as usize;
+    let kernel = match (byteorder, bytespersample, pixel_size) {
+        (_, 1, 1) => I::kernel::<1, u8, LE>(),
+        (_, 1, 2) => I::kernel::<2, u8, LE>(),
+        (_, 1, 4) => I::kernel::<4, u8, LE>(),
+        (_, 1, 8) => I::kernel::<8, u8, LE>(),
+        (_, 1, 16) => I::kernel::<16, u8, LE>(),
+
+        (ByteOrder::BigEndian, 2, 2) => I::kernel::<2, u16, BE>(),
+        (ByteOrder::LittleEndian, 2, 2) => I::kernel::<2, u16, LE>(),
+        (ByteOrder::BigEndian, 2, 4) => I::kernel::<4, u16, BE>(),
+        (ByteOrder::LittleEndian, 2, 4) => I::kernel::<4, u16, LE>(),
+        (ByteOrder::BigEndian, 2, 8) => I::kernel::<8, u16, BE>(),
+        (ByteOrder::LittleEndian, 2, 8) => I::kernel::<8, u16, LE>(),
+        (ByteOrder::BigEndian, 2, 16) => I::kernel::<16, u16, BE>(),
+        (ByteOrder::LittleEndian, 2, 16) => I::kernel::<16, u16, LE>(),
+
+        (ByteOrder::BigEndian, 4, 4) => I::kernel::<4, u32, BE>(),
+        (ByteOrder::LittleEndian, 4, 4) => I::kernel::<4, u32, LE>(),
+        (ByteOrder::BigEndian, 4, 8) => I::kernel::<8, u32, BE>(),
+        (ByteOrder::LittleEndian, 4, 8) => I::kernel::<8, u32, LE>(),
+        (ByteOrder::BigEndian, 4, 16) => I::kernel::<16, u32, BE>(),
+        (ByteOrder::LittleEndian, 4, 16) => I::kernel::<16, u32, LE>(),
+
+        (ByteOrder::BigEndian, 8, 8) => I::kernel::<8, u64, BE>(),
+        (ByteOrder::LittleEndian, 8, 8) => I::kernel::<8, u64, LE>(),
+        (ByteOrder::BigEndian, 8, 16) => I::kernel::<16, u64, BE>(),
+        (ByteOrder::LittleEndian, 8, 16) => I::kernel::<16, u64, LE>(),
+
+        _ => return None,
+    };
+
+    Some(kernel)
+}
+
+/// An instruction set providing decode kernels.
+trait Isa {
+    /// Returns the kernel decoding pixels of `P` bytes made of samples of type `T`.
+    fn kernel<const P: usize, T, B>() -> unsafe fn(&mut [u8])
+    where
+        T: Lane,
+        B: aira_byteorder::ByteOrder;
+}
+
+/// The SSE2 instruction set, always available on `x86_64`.
+struct Sse2;
+
+impl Isa for Sse2 {
+    fn kernel<const P: usize, T, B>() -> unsafe fn(&mut [u8])
+    where
+        T: Lane,
+        B: aira_byteorder::ByteOrder,
+    {
+        decode_sse2::<P, T, B>
+    }
+}
+
+/// The AVX2 instruction set.
+struct Avx2;
+
+impl Isa for Avx2 {
+    fn kernel<const P: usize, T, B>() -> unsafe fn(&mut [u8])
+    where
+        T: Lane,
+        B: aira_byteorder::ByteOrder,
+    {
+        decode_avx2::<P, T, B>
+    }
+}
+
+/// The operations on the samples of a given type.
+trait Lane: Copy {
+    /// Reads a sample with the given byte order.
+    fn read<B: aira_byteorder::ByteOrder>(src: &[u8]) -> Self;
+
+    /// Writes a sample in native byte order.
+    fn write(self, dst: &mut [u8]);
+
+    fn wrapping_add(self, other: Self) -> Self;
+
+    /// Adds the samples of two SSE registers.
+    fn sse2_add(a: __m128i, b: __m128i) -> __m128i;
+
+    /// Converts the samples of an SSE register to native byte order.
+    unsafe fn sse2_decode<B: aira_byteorder::ByteOrder>(x: __m128i) -> __m128i;
+
+    /// Adds the samples of two AVX registers.
+    unsafe fn avx2_add(a: __m256i, b: __m256i) -> __m256i;
+
+    /// Converts the samples of an AVX register to native byte order.
+    unsafe fn avx2_decode<B: aira_byteorder::ByteOrder>(x: __m256i) -> __m256i;
+}
+
+impl Lane for u8 {
+    #[inline(always)]
+    fn read<B: aira_byteorder::ByteOrder>(src: &[u8]) -> Self {
+        src[0]
+    }
+
+    #[inline(always)]
+    fn write(self, dst: &mut [u8]) {
+        dst[0] = self;
+    }
+
+    #[inline(always)]
+    fn wrapping_add(self, other: Self) -> Self {
+        self.wrapping_add(other)
+    }
+
+    #[inline(always)]
+    fn sse2_add(a: __m128i, b: __m128i) -> __m128i {
+        // SAFETY: SSE2 is always available on x86_64.
+        unsafe { _mm_add_epi8(a, b) }
+    }
+
+    #[inline(always)]
+    unsafe fn sse2_decode<B: aira_byteorder::ByteOrder>(x: __m128i) -> __m128i {
+        x
+    }
+
+    #[inline(always)]
+    unsafe fn avx2_add(a: __m256i, b: __m256i) -> __m256i {
+        unsafe { _mm256_add_epi8(a, b) }
+    }
+
+    #[inline(always)]
+    unsafe fn avx2_decode<B: aira_byteorder::ByteOrder>(x: __m256i) -> __m256i {
+        x
+    }
+}
+
+macro_rules! impl_lane {
+    ($($ty:ident, $read:ident, $write:ident, $sse2_add:ident, $sse2_decode:ident, $avx2_add:ident, $avx2_decode:ident;)+) => {$(
+        impl Lane for $ty {
+            #[inline(always)]
+            fn read<B: aira_byteorder::ByteOrder>(src: &[u8]) -> Self {
+                B::$read(src)
+            }
+
+            #[inline(always)]
+            fn write(self, dst: &mut [u8]) {
+                NativeEndian::$write(self, dst)
+            }
+
+            #[inline(always)]
+            fn wrapping_add(self, other: Self) -> Self {
+                self.wrapping_add(other)
+            }
+
+            #[inline(always)]
+            fn sse2_add(a: __m128i, b: __m128i) -> __m128i {
+                // SAFETY: SSE2 is always available on x86_64.
+                unsafe { $sse2_add(a, b) }
+            }
+
+            #[inline(always)]
+            unsafe fn sse2_decode<B: aira_byteorder::ByteOrder>(x: __m128i) -> __m128i {
+                unsafe { B::$sse2_decode(x) }
+            }
+
+            #[inline(always)]
+            unsafe fn avx2_add(a: __m256i, b: __m256i) -> __m256i {
+                unsafe { $avx2_add(a, b) }
+            }
+
+            #[inline(always)]
+            unsafe fn avx2_decode<B: aira_byteorder::ByteOrder>(x: __m256i) -> __m256i {
+                unsafe { B::$avx2_decode(x) }
+            }
+        }
+    )+};
+}
+
+impl_lane! {
+    u16, read_u16, write_u16, _mm_add_epi16, sse_decode_u16, _mm256_add_epi16, avx_decode_u16;
+    u32, read_u32, write_u32, _mm_add_epi32, sse_decode_u32, _mm256_add_epi32, avx_decode_u32;
+    u64, read_u64, write_u64, _mm_add_epi64, sse_decode_u64, _mm256_add_epi64, avx_decode_u64;
+}
+
+/// Decodes a row of pixels of `P` bytes using SSE2 instructions.
+fn decode_sse2<const P: usize, T, B>(row: &mut [u8])
+where
+    T: Lane,
+    B: aira_byteorder::ByteOrder,
+{
+    const WIDTH: usize = size_of::<__m128i>();
+    assert!(row.len().is_multiple_of(P));
+
+    // SAFETY: SSE2 is always available on x86_64.
+    let mut carry = unsafe { _mm_setzero_si128() };
+    for register in row.chunks_exact_mut(WIDTH) {
+        // SAFETY: The register is read from and written to a chunk of 16 bytes.
+        unsafe {
+            let x = _mm_loadu_si128(register.as_ptr().cast());
+            let x = T::sse2_add(sse2_prefix_sum::<P, T>(T::sse2_decode::<B>(x)), carry);
+            _mm_storeu_si128(register.as_mut_ptr().cast(), x);
+            carry = sse2_broadcast_last::<P>(x);
+        }
+    }
+
+    let start = row.len() - row.len() % WIDTH;
+    decode_tail::<P, T, B>(row, start);
+}
+
+/// Computes the cumulated sum of the pixels of `P` bytes in an SSE register.
+#[inline(always)]
+fn sse2_prefix_sum<const P: usize, T: Lane>(x: __m128i) -> __m128i {
+    let mut x = x;
+    // SAFETY: SSE2 is always available on x86_64.
+    unsafe {
+        if P <= 1 {
+            x = T::sse2_add(x, _mm_slli_si128::<1>(x));
+        }
+        if P <= 2 {
+            x = T::sse2_add(x, _mm_slli_si128::<2>(x));
+        }
+        if P <= 4 {
+            x = T::sse2_add(x, _mm_slli_si128::<4>(x));
+        }
+        if P <= 8 {
+            x = T::sse2_add(x, _mm_slli_si128::<8>(x));
+        }
+    }
+    x
+}
+
+/// Broadcasts the last pixel of `P` bytes of an SSE register to all the pixels.
+#[inline(always)]
+fn sse2_broadcast_last<const P: usize>(x: __m128i) -> __m128i {
+    // SAFETY: SSE2 is always available on x86_64.
+    unsafe {
+        match P {
+            1 => _mm_shuffle_epi32::<0xff>(_mm_shufflehi_epi16::<0xff>(_mm_unpackhi_epi8(x, x))),
+            2 => _mm_shuffle_epi32::<0xff>(_mm_shufflehi_epi16::<0xff>(x)),
+            4 => _mm_shuffle_epi32::<0xff>(x),
+            8 => _mm_shuffle_epi32::<0xee>(x),
+            _ => x,
+        }
+    }
+}
+
+/// Decodes a row of pixels of `P` bytes using AVX2 instructions.
+#[target_feature(enable = "avx2")]
+unsafe fn decode_avx2<const P: usize, T, B>(row: &mut [u8])
+where
+    T: Lane,
+    B: aira_byteorder::ByteOrder,
+{
+    const WIDTH: usize = size_of::<__m256i>();
+    assert!(row.len().is_multiple_of(P));
+
+    let mut carry = _mm256_setzero_si256();
+    for register in row.chunks_exact_mut(WIDTH) {
+        // SAFETY: The register is read from and written to a chunk of 32 bytes.
+        unsafe {
+            let x = _mm256_loadu_si256(register.as_ptr().cast());
+            let x = avx2_prefix_sum::<P, T>(T::avx2_decode::<B>(x));
+
+            // The shifts and the broadcast work on each 128-bit lane independently, the last
+            // pixel of the low lane is carried to the high lane.
+            let last = avx2_broadcast_last::<P>(x);
+            let x = T::avx2_add(x, _mm256_permute2x128_si256::<0x08>(last, last));
+            let x = T::avx2_add(x, carry);
+            _mm256_storeu_si256(register.as_mut_ptr().cast(), x);
+
+            let last = avx2_broadcast_last::<P>(x);
+            carry = _mm256_permute2x128_si256::<0x11>(last, last);
+        }
+    }
+
+    let start = row.len() - row.len() % WIDTH;
+    decode_tail::<P, T, B>(row, start);
+}
+
+/// Computes the cumulated sum of the pixels of `P` bytes in each lane of an AVX register.
+#[inline(always)]
+unsafe fn avx2_prefix_sum<const P: usize, T: Lane>(x: __m256i) -> __m256i {
+    let mut x = x;
+    unsafe {
+        if P <= 1 {
+            x = T::avx2_add(x, _mm256_slli_si256::<1>(x));
+        }
+        if P <= 2 {
+            x = T::avx2_add(x, _mm256_slli_si256::<2>(x));
+        }
+        if P <= 4 {
+            x = T::avx2_add(x, _mm256_slli_si256::<4>(x));
+        }
+        if P <= 8 {
+            x = T::avx2_add(x, _mm256_slli_si256::<8>(x));
+        }
+    }
+    x
+}
+
+/// Broadcasts the last pixel of `P` bytes of each lane of an AVX register to the pixels of the
+/// same lane.
+#[inline(always)]
+unsafe fn avx2_broadcast_last<const P: usize>(x: __m256i) -> __m256i {
+    unsafe {
+        match P {
+            1 => _mm256_shuffle_epi32::<0xff>(_mm256_shufflehi_epi16::<0xff>(
+                _mm256_unpackhi_epi8(x, x),
+            )),
+            2 => _mm256_shuffle_epi32::<0xff>(_mm256_shufflehi_epi16::<0xff>(x)),
+            4 => _mm256_shuffle_epi32::<0xff>(x),
+            8 => _mm256_shuffle_epi32::<0xee>(x),
+            _ => x,
+        }
+    }
+}
+
+/// Decodes the samples starting from `start`, the bytes before it are already decoded.
+#[inline(always)]
+fn decode_tail<const P: usize, T, B>(row: &mut [u8], start: usize)
+where
+    T: Lane,
+    B: aira_byteorder::ByteOrder,
+{
+    for offset in (start..row.len()).step_by(size_of::<T>()) {
+        let mut value = T::read::<B>(&row[offset..]);
+        if offset >= P {
+            value = value.wrapping_add(T::read::<NativeEndian>(&row[offset - P..]));
+        }
+        value.write(&mut row[offset..]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use aira_byteorder::{BE, LE};
+
+    use super::*;
+    use crate::predictor::int::{DecodeU16, DecodeU32, DecodeU64, DecodeU8};
+
+    /// Checks the kernels of each instruction set against the reference decoder.
+    fn check<const P: usize, T, B>(mut reference: impl Decoder)
+    where
+        T: Lane,
+        B: aira_byteorder::ByteOrder,
+    {
+        let mut kernels = vec![("sse2", Sse2::kernel::<P, T, B>())];
+        if std::is_x86_feature_detected!("avx2") {
+            kernels.push(("avx2", Avx2::kernel::<P, T, B>()));
+        }
+
+        for (isa, kernel) in kernels {
+            // Rows not filling the last register are decoded too.
+            for npixels in [0, 1, 2, 3, 15, 16, 17, 63, 64, 65, 100] {
+                let row = repeat_with(|| fastrand::u8(..))
+                    .take(npixels * P)
+                    .collect::<Vec<_>>();
+
+                let mut expected = row.clone();
+                reference.decode(&mut expected);
+
+                let mut actual = row.clone();
+                // SAFETY: The AVX2 kernels are only tested when supported.
+                unsafe { kernel(&mut actual) };
+
+                assert_eq!(actual, expected, "{isa}: {npixels} pixels of {P} bytes");
+            }
+        }
+    }
+
+    macro_rules! test_case {
+        ($($name:ident ($reference:expr) for <$bytes:literal, $ty:ident, $byteorder:ident>;)+) => {$(
+            #[test]
+            fn $name() {
+                check::<$bytes, $ty, $byteorder>($reference);
+            }
+        )+};
+    }
+
+    test_case!(
+        simd_decode_u8x1 (DecodeU8::new(1)) for <1, u8, LE>;
+        simd_decode_u8x2 (DecodeU8::new(2)) for <2, u8, LE>;
+        simd_decode_u8x4 (DecodeU8::new(4)) for <4, u8, LE>;
+        simd_decode_u8x8 (DecodeU8::new(8)) for <8, u8, LE>;
+        simd_decode_u8x16 (DecodeU8::new(16)) for <16, u8, LE>;
+
+        simd_decode_u16x1_be (DecodeU16::<BE>::new(1)) for <2, u16, BE>;
+        simd_decode_u16x2_be (DecodeU16::<BE>::new(2)) for <4, u16, BE>;
+        simd_decode_u16x4_be (DecodeU16::<BE>::new(4)) for <8, u16, BE>;
+        simd_decode_u16x8_be (DecodeU16::<BE>::new(8)) for <16, u16, BE>;
+        simd_decode_u16x1_le (DecodeU16::<LE>::new(1)) for <2, u16, LE>;
+        simd_decode_u16x2_le (DecodeU16::<LE>::new(2)) for <4, u16, LE>;
+        simd_decode_u16x4_le (DecodeU16::<LE>::new(4)) for <8, u16, LE>;
+        simd_decode_u16x8_le (DecodeU16::<LE>::new(8)) for <16, u16, LE>;
+
+        simd_decode_u32x1_be (DecodeU32::<BE>::new(1)) for <4, u32, BE>;
+        simd_decode_u32x2_be (DecodeU32::<BE>::new(2)) for <8, u32, BE>;
+        simd_decode_u32x4_be (DecodeU32::<BE>::new(4)) for <16, u32, BE>;
+        simd_decode_u32x1_le (DecodeU32::<LE>::new(1)) for <4, u32, LE>;
+        simd_decode_u32x2_le (DecodeU32::<LE>::new(2)) for <8, u32, LE>;
+        simd_decode_u32x4_le (DecodeU32::<LE>::new(4)) for <16, u32, LE>;
+
+        simd_decode_u64x1_be (DecodeU64::<BE>::new(1)) for <8, u64, BE>;
+        simd_decode_u64x2_be (DecodeU64::<BE>::new(2)) for <16, u64, BE>;
+        simd_decode_u64x1_le (DecodeU64::<LE>::new(1)) for <8, u64, LE>;
+        simd_decode_u64x2_le (DecodeU64::<LE>::new(2)) for <16, u64, LE>;
+    );
+
+    #[test]
+    fn unsupported_pixel_size() {
+        assert!(select_kernel::<Sse2>(ByteOrder::LittleEndian, 3, 1).is_none());
+        assert!(select_kernel::<Sse2>(ByteOrder::LittleEndian, 3, 2).is_none());
+        assert!(select_kernel::<Sse2>(ByteOrder::LittleEndian, 4, 8).is_none());
+        assert!(select_kernel::<Sse2>(ByteOrder::LittleEndian, 1, 3).is_none());
+    }
+}
@@ -0,0 +1,779 @@
+//! Reading of the pixel data.
+//!
+//! The [`PixelReader`] decompresses the chunks of an image, inverts the predictor and converts the
+//! samples to the native byte order. The pixels are returned with their samples interleaved,
+//! whatever the planar configuration of the image. A subset of the bands can be read, and with
+//! [`PlanarConfiguration::PLANAR`] only the chunks of the requested bands are decoded.
+//!
+//! Subsampled YCbCr pixels are stored in data units, each holding the luma samples of a block of
+//! pixels followed by their two chroma samples. The chroma samples are upsampled to full
+//! resolution by bilinear interpolation, according to their
+//! [`YCbCrPositioning`](crate::YCbCrPositioning).
+//!
+//! A [`Window`] of the image can be read, only the chunks it overlaps are decoded. With
+//! [`PixelReader::oriented`], the pixels are read upright according to the
+//! [`Orientation`](crate::Orientation) of the image, and the windows are given in the upright
+//! image.
+//!
+//! ```
+//! use aira_tiff::{reader::PixelReader, Decoder, Metadata};
+//!
+//! let file = std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif")?;
+//! let mut decoder = Decoder::new(std::io::BufReader::new(file))?;
+//! let byteorder = decoder.byteorder();
+//! let mut directories = decoder.directories();
+//! let directory = directories.next_directory()?.unwrap();
+//! let metadata = Metadata::from_decoder(directory)?;
+//!
+//! // Reads the green band only.
+//! let pixels = PixelReader::new(&metadata, byteorder)?;
+//! let mut green = vec![0u8; pixels.image_len(1)];
+//! pixels.read_bands(&mut decoder.into_inner(), &[1], &mut green)?;
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+//!
+//! ```
+//! use aira_tiff::{
+//!     reader::{PixelReader, Window},
+//!     Decoder, Metadata,
+//! };
+//!
+//! let file = std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif")?;
+//! let mut decoder = Decoder::new(std::io::BufReader::new(file))?;
+//! let byteorder = decoder.byteorder();
+//! let mut directories = decoder.directories();
+//! let directory = directories.next_directory()?.unwrap();
+//! let metadata = Metadata::from_decoder(directory)?;
+//!
+//! // Reads a window of 64x64 upright pixels.
+//! let pixels = PixelReader::new(&metadata, byteorder)?.oriented(true);
+//! let window = Window::new((100, 100), (64, 64));
+//! let mut rgb = vec![0u8; pixels.window_len(window, 3)];
+//! pixels.read_window(&mut decoder.into_inner(), window, &[0, 1, 2], &mut rgb)?;
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+use std::io::{Read, Seek};
+
+use aira_byteorder::{ByteOrder as _, BE, LE};
+
+use crate::{
+    compression::DecompressReader,
+    error::ErrorContext,
+    float,
+    metadata::{Chunk, Layout},
+    predictor::{FloatPredictorReader, IntPredictorReader},
+    ByteOrder, Error, Metadata, Orientation, PlanarConfiguration, Predictor, SampleFormat,
+    Unsupported, YCbCrPositioning,
+};
+
+/// A rectangular region of an image.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Window {
+    /// The position of the top-left pixel of the window.
+    pub origin: (u32, u32),
+    /// The width and length of the window in pixels.
+    pub size: (u32, u32),
+}
+
+impl Window {
+    /// Creates a new [`Window`] with the given origin and size.
+    pub fn new(origin: (u32, u32), size: (u32, u32)) -> Self {
+        Self { origin, size }
+    }
+
+    /// Returns `true` if the window holds no pixel.
+    pub fn is_empty(&self) -> bool {
+        self.size.0 == 0 || self.size.1 == 0
+    }
+
+    /// Returns the region shared by both windows, if any.
+    pub fn intersection(&self, other: &Window) -> Option<Window> {
+        // The windows may extend beyond the range of u32.
+        let end = |origin: u32, size: u32| origin as u64 + size as u64;
+        let (x0, y0) = (
+            self.origin.0.max(other.origin.0),
+            self.origin.1.max(other.origin.1),
+        );
+        let (x1, y1) = (
+            end(self.origin.0, self.size.0).min(end(other.origin.0, other.size.0)),
+            end(self.origin.1, self.size.1).min(end(other.origin.1, other.size.1)),
+        );
+        // The size of the intersection is bounded by the size of both windows.
+        ((x0 as u64) < x1 && (y0 as u64) < y1).then(|| {
+            let size = (x1 - x0 as u64, y1 - y0 as u64);
+            Window::new((x0, y0), (size.0 as u32, size.1 as u32))
+        })
+    }
+}
+
+/// Reads the pixel data of an image.
+///
+/// Samples of 8, 16, 32 and 64 bits are supported, all the samples of a pixel must have the same
+/// size. Subsampled YCbCr pixels must be stored contiguously, with samples of 8 or 16 bits and
+/// without predictor.
+#[derive(Clone, Debug)]
+pub struct PixelReader<'tiff> {
+    metadata: &'tiff Metadata,
+    byteorder: ByteOrder,
+    /// The number of bytes of each sample.
+    bytespersample: u16,
+    /// The orientation applied to the stored pixels.
+    orientation: Orientation,
+}
+
+impl<'tiff> PixelReader<'tiff> {
+    /// Creates a new instance of [`PixelReader`] for the image described by `metadata`, stored
+    /// with the given byte order.
+    pub fn new(metadata: &'tiff Metadata, byteorder: ByteOrder) -> Result<Self, Error> {
+        let samples = metadata.samples();
+        let Some(first) = samples.first() else {
+            return Err(Error::from_static_str(
+                "Pixels without samples cannot be read",
+            ));
+        };
+        if samples.iter().any(|sample| sample.bits != first.bits) {
+            return Err(Error::from_static_str(
+                "Pixels with samples of different sizes cannot be read",
+            ));
+        }
+        if !matches!(first.bits, 8 | 16 | 32 | 64) {
+            return Err(
+                Error::from(Unsupported::BitsPerSample(first.bits)).with_context(|| {
+                    format!("Pixels with samples of {} bits cannot be read", first.bits)
+                }),
+            );
+        }
+
+        if metadata.ycbcr_subsampling() != (1, 1) {
+            if metadata.configuration == PlanarConfiguration::PLANAR {
+                return Err(Error::from_static_str(
+                    "Subsampled YCbCr pixels stored in separate planes cannot be read",
+                ));
+            }
+            if samples.len() != 3 {
+                return Err(Error::from_args(format_args!(
+                    "Subsampled YCbCr pixels with {} samples cannot be read",
+                    samples.len()
+                )));
+            }
+            if !matches!(first.bits, 8 | 16) {
+                return Err(
+                    Error::from(Unsupported::BitsPerSample(first.bits)).with_context(|| {
+                        format!(
+                            "Subsampled YCbCr pixels with samples of {} bits cannot be read",
+                            first.bits
+                        )
+                    }),
+                );
+            }
+            if metadata.predictor != Predictor::NONE {
+                return Err(Error::from(Unsupported::Predictor(metadata.predictor))
+                    .with_context(|| "Subsampled YCbCr pixels cannot be read with a predictor"));
+            }
+        }
+
+        Ok(Self {
+            metadata,
+            byteorder,
+            bytespersample: first.bits / 8,
+            orientation: Orientation::TOP_LEFT,
+        })
+    }
+
+    /// Sets whether the pixels are read upright according to the orientation of the image.
+    ///
+    /// The pixels are read in the order they are stored by default. When oriented, the image and
+    /// the windows have the [`dimensions`](Self::dimensions) of the upright image. The chunks are
+    /// always read as stored.
+    pub fn oriented(mut self, oriented: bool) -> Self {
+        self.orientation = match oriented {
+            true => self.metadata.orientation,
+            false => Orientation::TOP_LEFT,
+        };
+        self
+    }
+
+    /// Returns the width and length of the image as read, the stored dimensions are swapped when
+    /// an orientation transposing the image is applied.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.orientation
+            .upright_dimensions(self.metadata.dimensions)
+    }
+
+    /// Returns the number of bytes of a sample.
+    pub fn bytes_per_sample(&self) -> usize {
+        self.bytespersample as usize
+    }
+
+    /// Returns the size in bytes of the buffer holding the whole image, with `bands` samples for
+    /// each pixel.
+    pub fn image_len(&self, bands: usize) -> usize {
+        let (width, length) = self.metadata.dimensions;
+        width as usize * length as usize * bands * self.bytes_per_sample()
+    }
+
+    /// Returns the size in bytes of the buffer holding `window`, with `bands` samples for each
+    /// pixel.
+    pub fn window_len(&self, window: Window, bands: usize) -> usize {
+        let (width, length) = window.size;
+        width as usize * length as usize * bands * self.bytes_per_sample()
+    }
+
+    /// Returns the size in bytes of the buffer holding the decoded `chunk`, including the padding.
+    pub fn chunk_len(&self, chunk: &Chunk) -> usize {
+        let (width, length) = self.decoded_size(chunk);
+        match self.metadata.ycbcr_subsampling() {
+            (1, 1) => width * length * self.chunk_samples() as usize * self.bytes_per_sample(),
+            (horizontal, vertical) => {
+                let (horizontal, vertical) = (horizontal as usize, vertical as usize);
+                let units = width.div_ceil(horizontal) * length.div_ceil(vertical);
+                units * (horizontal * vertical + 2) * self.bytes_per_sample()
+            }
+        }
+    }
+
+    /// Decodes a single chunk into `dst`.
+    ///
+    /// The samples are stored in native byte order, and the rows include the padding of the
+    /// chunk. With [`PlanarConfiguration::PLANAR`], the chunk holds the samples of a single band.
+    /// Subsampled YCbCr pixels are returned as data units, the chroma samples are not upsampled.
+    ///
+    /// A [sparse](Chunk::is_sparse) chunk is filled with the nodata value of the image, or zero
+    /// if there is none, without reading from `reader`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`chunk_len`](Self::chunk_len).
+    pub fn read_chunk<R>(&self, reader: &mut R, chunk: &Chunk, dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        assert_eq!(
+            dst.len(),
+            self.chunk_len(chunk),
+            "destination slice does not match the size of the chunk"
+        );
+
+        if chunk.is_sparse() {
+            self.fill_sparse(chunk, dst);
+            return Ok(());
+        }
+
+        self.decode_chunk(reader, chunk, dst).with_context(|| {
+            format!(
+                "Failed to read chunk at offset {} with {} bytes",
+                chunk.offset, chunk.byte_count
+            )
+        })
+    }
+
+    /// Reads the whole image into `dst`, with all the samples of each pixel interleaved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`image_len`](Self::image_len) for all the samples.
+    pub fn read_image<R>(&self, reader: &mut R, dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        let bands = (0..self.metadata.samples().len() as u16).collect::<Vec<_>>();
+        self.read_bands(reader, &bands, dst)
+    }
+
+    /// Reads the samples of the given bands of the whole image into `dst`, interleaved in the
+    /// order of `bands`.
+    ///
+    /// The bands are the indices of the samples of a pixel, they can be reordered or repeated.
+    /// With [`PlanarConfiguration::PLANAR`], only the chunks of the requested bands are decoded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`image_len`](Self::image_len) for the given bands.
+    pub fn read_bands<R>(&self, reader: &mut R, bands: &[u16], dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        self.check_bands(bands)?;
+        assert_eq!(
+            dst.len(),
+            self.image_len(bands.len()),
+            "destination slice does not match the size of the image"
+        );
+
+        let window = Window::new((0, 0), self.dimensions());
+        self.read_oriented(reader, window, bands, dst)
+    }
+
+    /// Reads the samples of the given bands of `window` into `dst`, interleaved in the order of
+    /// `bands`.
+    ///
+    /// Only the chunks overlapping the window are decoded, except for subsampled YCbCr pixels
+    /// which are upsampled from the whole image.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `dst` is not [`window_len`](Self::window_len) for the given bands.
+    pub fn read_window<R>(
+        &self,
+        reader: &mut R,
+        window: Window,
+        bands: &[u16],
+        dst: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        self.check_bands(bands)?;
+        let (width, length) = self.dimensions();
+        if window.origin.0 as u64 + window.size.0 as u64 > width as u64
+            || window.origin.1 as u64 + window.size.1 as u64 > length as u64
+        {
+            return Err(Error::from_args(format_args!(
+                "Window {window:?} exceeds the image of {width}x{length} pixels"
+            )));
+        }
+        assert_eq!(
+            dst.len(),
+            self.window_len(window, bands.len()),
+            "destination slice does not match the size of the window"
+        );
+
+        self.read_oriented(reader, window, bands, dst)
+    }
+
+    /// Returns an error if a band is not a sample of the pixels.
+    fn check_bands(&self, bands: &[u16]) -> Result<(), Error> {
+        let samples = self.metadata.samples().len();
+        match bands.iter().find(|&&band| band as usize >= samples) {
+            Some(band) => Err(Error::from_args(format_args!(
+                "Band {band} is out of range, the pixels have {samples} samples"
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Reads the pixels of `window` of the upright image.
+    fn read_oriented<R>(
+        &self,
+        reader: &mut R,
+        window: Window,
+        bands: &[u16],
+        dst: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        if window.is_empty() || bands.is_empty() {
+            return Ok(());
+        }
+        if self.orientation == Orientation::TOP_LEFT {
+            return self.read_stored(reader, window, bands, dst);
+        }
+
+        // The stored window is read first, then its pixels are moved to their upright position.
+        let dimensions = self.metadata.dimensions;
+        let (x0, y0) = self.orientation.stored_position(window.origin, dimensions);
+        let (x1, y1) = self.orientation.stored_position(
+            (
+                window.origin.0 + window.size.0 - 1,
+                window.origin.1 + window.size.1 - 1,
+            ),
+            dimensions,
+        );
+        let stored = Window::new(
+            (x0.min(x1), y0.min(y1)),
+            (x0.abs_diff(x1) + 1, y0.abs_diff(y1) + 1),
+        );
+        let mut buffer = vec![0u8; self.window_len(stored, bands.len())];
+        self.read_stored(reader, stored, bands, &mut buffer)?;
+
+        let pixel_len = bands.len() * self.bytes_per_sample();
+        let width = window.size.0 as usize;
+        for (index, dst) in dst.chunks_exact_mut(pixel_len).enumerate() {
+            let position = (
+                window.origin.0 + (index % width) as u32,
+                window.origin.1 + (index / width) as u32,
+            );
+            let (x, y) = self.orientation.stored_position(position, dimensions);
+            let (x, y) = (
+                (x - stored.origin.0) as usize,
+                (y - stored.origin.1) as usize,
+            );
+            let start = (y * stored.size.0 as usize + x) * pixel_len;
+            dst.copy_from_slice(&buffer[start..start + pixel_len]);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the pixels of `window` of the stored image.
+    fn read_stored<R>(
+        &self,
+        reader: &mut R,
+        window: Window,
+        bands: &[u16],
+        dst: &mut [u8],
+    ) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        if self.metadata.ycbcr_subsampling() != (1, 1) {
+            let image = Window::new((0, 0), self.metadata.dimensions);
+            if window == image {
+                return self.read_subsampled(reader, bands, dst);
+            }
+
+            let mut buffer = vec![0u8; self.image_len(bands.len())];
+            self.read_subsampled(reader, bands, &mut buffer)?;
+            self.copy_window(image, &buffer, bands.len(), window, dst);
+            return Ok(());
+        }
+
+        let planar = self.metadata.configuration == PlanarConfiguration::PLANAR;
+        let mut buffer = Vec::new();
+        for plane in 0..self.metadata.planes_count() as u16 {
+            if planar && !bands.contains(&plane) {
+                continue;
+            }
+
+            for chunk in self.metadata.window_chunks(reader, plane, window)? {
+                let Some(overlap) = Window::new(chunk.origin, chunk.size).intersection(&window)
+                else {
+                    continue;
+                };
+                buffer.resize(self.chunk_len(&chunk), 0);
+                self.read_chunk(reader, &chunk, &mut buffer)?;
+                self.copy_chunk(&chunk, &buffer, bands, overlap, window, dst);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads the data units of subsampled YCbCr pixels, and upsamples the chroma samples.
+    fn read_subsampled<R>(&self, reader: &mut R, bands: &[u16], dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        let bytespersample = self.bytes_per_sample();
+        let (width, length) = self.metadata.dimensions;
+        let (width, length) = (width as usize, length as usize);
+        let (horizontal, vertical) = self.metadata.ycbcr_subsampling();
+        let (horizontal, vertical) = (horizontal as usize, vertical as usize);
+        let unit_len = (horizontal * vertical + 2) * bytespersample;
+
+        // The luma samples at full resolution, and the pairs of chroma samples of the image.
+        let (chroma_width, chroma_length) = (width.div_ceil(horizontal), length.div_ceil(vertical));
+        let mut luma = vec![0u8; width * length * bytespersample];
+        let mut chroma = vec![0u8; chroma_width * chroma_length * 2 * bytespersample];
+
+        let image = Window::new((0, 0), self.metadata.dimensions);
+        let mut buffer = Vec::new();
+        for chunk in self.metadata.window_chunks(reader, 0, image)? {
+            buffer.resize(self.chunk_len(&chunk), 0);
+            self.read_chunk(reader, &chunk, &mut buffer)?;
+
+            // The chunks are made of whole data units.
+            let units_per_row = self.decoded_size(&chunk).0.div_ceil(horizontal);
+            let (x, y) = (chunk.origin.0 as usize, chunk.origin.1 as usize);
+            let units_x = (chunk.size.0 as usize).div_ceil(horizontal);
+            let units_y = (chunk.size.1 as usize).div_ceil(vertical);
+            for unit_y in 0..units_y {
+                for unit_x in 0..units_x {
+                    let unit = &buffer[(unit_y * units_per_row + unit_x) * unit_len..][..unit_len];
+                    let (luma_samples, chroma_samples) =
+                        unit.split_at(horizontal * vertical * bytespersample);
+
+                    let rows = luma_samples.chunks_exact(horizontal * bytespersample);
+                    for (row, src) in rows.enumerate() {
+                        let (x, y) = (x + unit_x * horizontal, y + unit_y * vertical + row);
+                        if y >= length {
+                            break;
+                        }
+                        let count = horizontal.min(width - x) * bytespersample;
+                        let start = (y * width + x) * bytespersample;
+                        luma[start..start + count].copy_from_slice(&src[..count]);
+                    }
+
+                    let (x, y) = (x / horizontal + unit_x, y / vertical + unit_y);
+                    if x < chroma_width && y < chroma_length {
+                        let start = (y * chroma_width + x) * 2 * bytespersample;
+                        chroma[start..start + chroma_samples.len()].copy_from_slice(chroma_samples);
+                    }
+                }
+            }
+        }
+
+        let positioning = self.metadata.ycbcr_positioning();
+        let columns = chroma_weights(width, horizontal, positioning);
+        let rows = chroma_weights(length, vertical, positioning);
+        let chroma_sample = |x: usize, y: usize, index: usize| {
+            load(&chroma, (y * chroma_width + x) * 2 + index, bytespersample)
+        };
+
+        let dst_pixels = dst.chunks_exact_mut(bands.len() * bytespersample);
+        for (pixel, dst) in dst_pixels.enumerate() {
+            let (x0, x1, weight_x) = columns[pixel % width];
+            let (y0, y1, weight_y) = rows[pixel / width];
+            for (dst, &band) in dst.chunks_exact_mut(bytespersample).zip(bands) {
+                let value = match band {
+                    0 => load(&luma, pixel, bytespersample),
+                    band => {
+                        let index = band as usize - 1;
+                        let top = interpolate(
+                            chroma_sample(x0, y0, index),
+                            chroma_sample(x1, y0, index),
+                            weight_x,
+                        );
+                        let bottom = interpolate(
+                            chroma_sample(x0, y1, index),
+                            chroma_sample(x1, y1, index),
+                            weight_x,
+                        );
+                        interpolate(top, bottom, weight_y)
+                    }
+                };
+                store(dst, value);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the number of samples of a pixel stored in each chunk.
+    fn chunk_samples(&self) -> u16 {
+        match self.metadata.configuration {
+            PlanarConfiguration::PLANAR => 1,
+            _ => self.metadata.samples().len() as u16,
+        }
+    }
+
+    /// Returns the width and length of the decoded chunk, including the padding.
+    fn decoded_size(&self, chunk: &Chunk) -> (usize, usize) {
+        match self.metadata.layout {
+            // The last strip is not padded.
+            Layout::Strips { .. } => (self.metadata.dimensions.0 as usize, chunk.size.1 as usize),
+            Layout::Tiles { width, length } => (width as usize, length as usize),
+        }
+    }
+
+    /// Fills the decoded sparse `chunk` with the nodata value of the image, or zero.
+    fn fill_sparse(&self, chunk: &Chunk, dst: &mut [u8]) {
+        let nodata = self.metadata.nodata().unwrap_or(0.0);
+        let samples = self.metadata.samples();
+        let samples = match self.metadata.configuration {
+            PlanarConfiguration::PLANAR => &samples[chunk.plane as usize..][..1],
+            // The luma and chroma samples have the same format.
+            _ if self.metadata.ycbcr_subsampling() != (1, 1) => &samples[..1],
+            _ => samples,
+        };
+
+        let bytespersample = self.bytes_per_sample();
+        let mut pixel = vec![0u8; samples.len() * bytespersample];
+        for (dst, sample) in pixel.chunks_exact_mut(bytespersample).zip(samples) {
+            store_value(dst, nodata, sample.format);
+        }
+        for dst in dst.chunks_exact_mut(pixel.len()) {
+            dst.copy_from_slice(&pixel);
+        }
+    }
+
+    fn decode_chunk<R>(&self, reader: &mut R, chunk: &Chunk, dst: &mut [u8]) -> Result<(), Error>
+    where
+        R: Read + Seek,
+    {
+        reader.seek(std::io::SeekFrom::Start(chunk.offset))?;
+        let compressed = reader.take(chunk.byte_count);
+        let decompressed = DecompressReader::new(compressed, self.metadata.compression)?;
+
+        let ncols = self.decoded_size(chunk).0 as u32;
+        let samples = self.chunk_samples();
+        match self.metadata.predictor {
+            Predictor::NONE => {
+                let mut decompressed = decompressed;
+                decompressed.read_exact(dst)?;
+                to_native(self.byteorder, self.bytespersample, dst);
+            }
+            Predictor::HORIZONTAL => {
+                IntPredictorReader::new(
+                    decompressed,
+                    self.byteorder,
+                    ncols,
+                    samples,
+                    self.bytespersample,
+                )?
+                .read_exact(dst)?;
+            }
+            Predictor::FLOAT => {
+                FloatPredictorReader::new(decompressed, ncols, samples, self.bytespersample)
+                    .read_exact(dst)?;
+            }
+            predictor => return Err(Unsupported::Predictor(predictor).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Copies the pixels of `window` from `src` holding the pixels of `image`, to `dst`.
+    fn copy_window(&self, image: Window, src: &[u8], bands: usize, window: Window, dst: &mut [u8]) {
+        let pixel_len = bands * self.bytes_per_sample();
+        let src_row_len = image.size.0 as usize * pixel_len;
+        let dst_row_len = window.size.0 as usize * pixel_len;
+        let x = (window.origin.0 - image.origin.0) as usize * pixel_len;
+        let y = (window.origin.1 - image.origin.1) as usize;
+        let src_rows = src.chunks_exact(src_row_len).skip(y);
+        for (src, dst) in src_rows.zip(dst.chunks_exact_mut(dst_row_len)) {
+            dst.copy_from_slice(&src[x..x + dst_row_len]);
+        }
+    }
+
+    /// Copies the requested bands of the `overlap` of the decoded chunk `src` with `window`, to
+    /// their place in `dst` holding the pixels of the window.
+    fn copy_chunk(
+        &self,
+        chunk: &Chunk,
+        src: &[u8],
+        bands: &[u16],
+        overlap: Window,
+        window: Window,
+        dst: &mut [u8],
+    ) {
+        let bytespersample = self.bytes_per_sample();
+        let src_pixel = self.chunk_samples() as usize * bytespersample;
+        let dst_pixel = bands.len() * bytespersample;
+
+        // The offsets of the requested samples in the source and destination pixels.
+        let planar = self.metadata.configuration == PlanarConfiguration::PLANAR;
+        let offsets = bands
+            .iter()
+            .enumerate()
+            .filter_map(|(index, &band)| match planar {
+                true => (band == chunk.plane).then_some((0, index * bytespersample)),
+                false => Some((band as usize * bytespersample, index * bytespersample)),
+            })
+            .collect::<Vec<_>>();
+        let identity = src_pixel == dst_pixel && offsets.iter().all(|(src, dst)| src == dst);
+
+        let src_row_len = self.decoded_size(chunk).0 * src_pixel;
+        let dst_row_len = window.size.0 as usize * dst_pixel;
+        let (width, length) = (overlap.size.0 as usize, overlap.size.1 as usize);
+        let (src_x, src_y) = (
+            (overlap.origin.0 - chunk.origin.0) as usize,
+            (overlap.origin.1 - chunk.origin.1) as usize,
+        );
+        let (dst_x, dst_y) = (
+            (overlap.origin.0 - window.origin.0) as usize,
+            (overlap.origin.1 - window.origin.1) as usize,
+        );
+
+        let src_rows = src.chunks_exact(src_row_len).skip(src_y);
+        let dst_rows = dst[dst_y * dst_row_len..].chunks_exact_mut(dst_row_len);
+        for (src, dst) in src_rows.zip(dst_rows).take(length) {
+            let src = &src[src_x * src_pixel..(src_x + width) * src_pixel];
+            let dst = &mut dst[dst_x * dst_pixel..(dst_x + width) * dst_pixel];
+            if identity {
+                dst.copy_from_slice(src);
+                continue;
+            }
+
+            let pixels = src
+                .chunks_exact(src_pixel)
+                .zip(dst.chunks_exact_mut(dst_pixel));
+            for (src, dst) in pixels {
+                for &(src_offset, dst_offset) in &offsets {
+                    dst[dst_offset..dst_offset + bytespersample]
+                        .copy_from_slice(&src[src_offset..src_offset + bytespersample]);
+                }
+            }
+        }
+    }
+}
+
+/// The precision in bits of the interpolation weights of the chroma samples.
+const WEIGHT_BITS: u32 = 8;
+
+/// Returns for each luma sample along a direction, the two nearest chroma samples and the weight of
+/// the second one.
+fn chroma_weights(
+    len: usize,
+    subsampling: usize,
+    positioning: YCbCrPositioning,
+) -> Vec<(usize, usize, u32)> {
+    let last = len.div_ceil(subsampling) - 1;
+
+    // The position of the first chroma sample relative to the luma samples it covers.
+    let offset = match positioning {
+        YCbCrPositioning::COSITED => 0.0,
+        _ => (subsampling - 1) as f32 / 2.0,
+    };
+
+    (0..len)
+        .map(|position| {
+            let position =
+                ((position as f32 - offset) / subsampling as f32).clamp(0.0, last as f32);
+            let first = position as usize;
+            let weight = (position - first as f32) * (1 << WEIGHT_BITS) as f32;
+            (first, (first + 1).min(last), weight.round() as u32)
+        })
+        .collect()
+}
+
+/// Interpolates linearly between two samples, `weight` is the weight of the second one.
+#[inline(always)]
+fn interpolate(first: u32, second: u32, weight: u32) -> u32 {
+    let sum = first * ((1 << WEIGHT_BITS) - weight) + second * weight;
+    (sum + (1 << (WEIGHT_BITS - 1))) >> WEIGHT_BITS
+}
+
+/// Loads the native sample of 1 or 2 bytes at the given index.
+#[inline(always)]
+fn load(bytes: &[u8], index: usize, bytespersample: usize) -> u32 {
+    match bytespersample {
+        1 => bytes[index] as u32,
+        _ => u16::from_ne_bytes([bytes[2 * index], bytes[2 * index + 1]]) as u32,
+    }
+}
+
+/// Stores a native sample of 1 or 2 bytes.
+#[inline(always)]
+fn store(dst: &mut [u8], value: u32) {
+    match dst {
+        [byte] => *byte = value as u8,
+        _ => dst.copy_from_slice(&(value as u16).to_ne_bytes()),
+    }
+}
+
+/// Stores `value` as a native sample of the given format, with the size of `dst`.
+///
+/// Integer values are rounded and saturated to the range of the sample.
+pub(crate) fn store_value(dst: &mut [u8], value: f64, format: SampleFormat) {
+    match (format, dst.len()) {
+        (SampleFormat::FLOAT, 2) => {
+            dst.copy_from_slice(&float::f32_to_f16(value as f32).to_ne_bytes())
+        }
+        (SampleFormat::FLOAT, 4) => dst.copy_from_slice(&(value as f32).to_ne_bytes()),
+        (SampleFormat::FLOAT, _) => dst.copy_from_slice(&value.to_ne_bytes()),
+        (SampleFormat::SIGNED, 1) => dst.copy_from_slice(&(value.round() as i8).to_ne_bytes()),
+        (SampleFormat::SIGNED, 2) => dst.copy_from_slice(&(value.round() as i16).to_ne_bytes()),
+        (SampleFormat::SIGNED, 4) => dst.copy_from_slice(&(value.round() as i32).to_ne_bytes()),
+        (SampleFormat::SIGNED, _) => dst.copy_from_slice(&(value.round() as i64).to_ne_bytes()),
+        (_, 1) => dst.copy_from_slice(&(value.round() as u8).to_ne_bytes()),
+        (_, 2) => dst.copy_from_slice(&(value.round() as u16).to_ne_bytes()),
+        (_, 4) => dst.copy_from_slice(&(value.round() as u32).to_ne_bytes()),
+        (_, _) => dst.copy_from_slice(&(value.round() as u64).to_ne_bytes()),
+    }
+}
+
+/// Converts the samples stored in `bytes` from the given byte order to the native byte order.
+fn to_native(byteorder: ByteOrder, bytespersample: u16, bytes: &mut [u8]) {
+    match (byteorder, bytespersample) {
+        (_, 1) => {}
+        (ByteOrder::BigEndian, 2) => _ = BE::to_native_in_place_u16(bytes),
+        (ByteOrder::LittleEndian, 2) => _ = LE::to_native_in_place_u16(bytes),
+        (ByteOrder::BigEndian, 4) => _ = BE::to_native_in_place_u32(bytes),
+        (ByteOrder::LittleEndian, 4) => _ = LE::to_native_in_place_u32(bytes),
+        (ByteOrder::BigEndian, _) => _ = BE::to_native_in_place_u64(bytes),
+        (ByteOrder::LittleEndian, _) => _ = LE::to_native_in_place_u64(bytes),
+    }
+}
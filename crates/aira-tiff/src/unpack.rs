@@ -0,0 +1,362 @@
+//! Expansion of samples packed in bits.
+//!
+//! Samples whose size is not a whole number of bytes are packed without padding between pixels,
+//! and each row is padded to a whole number of bytes. With the default fill order, the samples
+//! are packed starting from the most significant bit of each byte; with
+//! [`FillOrder::LSB_TO_MSB`] the bits of each byte are reversed. Packed samples are not affected
+//! by the byte order.
+//!
+//! The [`Unpacker`] expands each sample to a whole byte or a pair of bytes, the values are not
+//! scaled. 1-bit and 4-bit samples are expanded using SIMD instructions when available.
+//!
+//! ```
+//! use aira_tiff::{unpack::Unpacker, FillOrder};
+//!
+//! // Two rows of 3 pixels of 4 bits.
+//! let packed = [0x12, 0x30, 0x45, 0x60];
+//! let unpacker = Unpacker::new(3, 1, 4, FillOrder::MSB_TO_LSB)?;
+//! let mut samples = [0u8; 6];
+//! unpacker.unpack_u8(&packed, &mut samples)?;
+//! assert_eq!(samples, [1, 2, 3, 4, 5, 6]);
+//! # Ok::<(), aira_tiff::Error>(())
+//! ```
+
+#[cfg(target_arch = "x86_64")]
+mod simd;
+
+use crate::{error::ErrorContext, Error, FillOrder, Unsupported};
+
+/// The maximum number of bits of a packed sample.
+///
+/// Samples of 16 bits and more are stored in whole bytes, which are affected by the byte order.
+pub const MAX_BITS: u16 = 15;
+
+/// Expands rows of packed samples.
+#[derive(Clone, Debug)]
+pub struct Unpacker {
+    /// The number of bits of a sample.
+    bits: u16,
+    /// Whether the bits of each byte are reversed.
+    reversed: bool,
+    /// The number of samples of a row.
+    row_len: usize,
+}
+
+impl Unpacker {
+    /// Creates a new instance of [`Unpacker`] for rows of `ncols` pixels, made of `samples`
+    /// samples of `bitspersample` bits.
+    ///
+    /// Samples of 1 to [`MAX_BITS`] bits are supported.
+    pub fn new(
+        ncols: u32,
+        samples: u16,
+        bitspersample: u16,
+        fill_order: FillOrder,
+    ) -> Result<Self, Error> {
+        if !(1..=MAX_BITS).contains(&bitspersample) {
+            return Err(Error::from(Unsupported::BitsPerSample(bitspersample))
+                .with_context(|| format!("Samples with {bitspersample} bits cannot be unpacked")));
+        }
+
+        let reversed = match fill_order {
+            FillOrder::MSB_TO_LSB => false,
+            FillOrder::LSB_TO_MSB => true,
+            _ => {
+                return Err(Error::from_args(format_args!(
+                    "Samples with fill order {} cannot be unpacked",
+                    fill_order.0
+                )))
+            }
+        };
+
+        Ok(Self {
+            bits: bitspersample,
+            reversed,
+            row_len: ncols as usize * samples as usize,
+        })
+    }
+
+    /// Returns the number of bytes of a packed row, including the padding.
+    pub fn row_size(&self) -> usize {
+        (self.row_len * self.bits as usize).div_ceil(8)
+    }
+
+    /// Returns the number of samples of a row.
+    pub fn row_len(&self) -> usize {
+        self.row_len
+    }
+
+    /// Expands the rows of packed samples in `src` to bytes in `dst`.
+    ///
+    /// Samples of at most 8 bits can be expanded to bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dst` do not hold the same number of whole rows.
+    pub fn unpack_u8(&self, src: &[u8], dst: &mut [u8]) -> Result<(), Error> {
+        if self.bits > 8 {
+            return Err(
+                Error::from(Unsupported::BitsPerSample(self.bits)).with_context(|| {
+                    format!("Samples with {} bits cannot be unpacked to u8", self.bits)
+                }),
+            );
+        }
+
+        let bits = self.bits as u32;
+        self.unpack_rows(src, dst, |src, dst| match bits {
+            1 => unpack_1bit(src, dst),
+            2 => unpack_2bit(src, dst),
+            4 => unpack_4bit(src, dst),
+            8 => dst.copy_from_slice(src),
+            _ => unpack_bits(bits, src, dst),
+        });
+        Ok(())
+    }
+
+    /// Expands the rows of packed samples in `src` to unsigned 16-bit integers in `dst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` and `dst` do not hold the same number of whole rows.
+    pub fn unpack_u16(&self, src: &[u8], dst: &mut [u16]) -> Result<(), Error> {
+        let bits = self.bits as u32;
+        self.unpack_rows(src, dst, |src, dst| match bits {
+            12 => unpack_12bit(src, dst),
+            _ => unpack_bits(bits, src, dst),
+        });
+        Ok(())
+    }
+
+    /// Expands each row with the given function, after reversing the bits of the bytes if needed.
+    fn unpack_rows<T, F>(&self, src: &[u8], dst: &mut [T], unpack_row: F)
+    where
+        F: Fn(&[u8], &mut [T]),
+    {
+        let row_size = self.row_size();
+        let rows = src.len().checked_div(row_size).unwrap_or(0);
+        assert!(
+            src.len() == rows * row_size && dst.len() == rows * self.row_len,
+            "source and destination slices have different numbers of rows"
+        );
+
+        if self.row_len == 0 {
+            return;
+        }
+
+        let rows = src
+            .chunks_exact(row_size)
+            .zip(dst.chunks_exact_mut(self.row_len));
+        if self.reversed {
+            let mut buffer = vec![0u8; row_size];
+            for (src, dst) in rows {
+                buffer
+                    .iter_mut()
+                    .zip(src)
+                    .for_each(|(reversed, byte)| *reversed = byte.reverse_bits());
+                unpack_row(&buffer, dst);
+            }
+        } else {
+            rows.for_each(|(src, dst)| unpack_row(src, dst));
+        }
+    }
+}
+
+/// Expands a row of 1-bit samples.
+fn unpack_1bit(src: &[u8], dst: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    let (src, dst) = {
+        let consumed = simd::unpack_1bit(src, dst);
+        (&src[consumed..], &mut dst[consumed * 8..])
+    };
+
+    for (dst, &byte) in dst.chunks_mut(8).zip(src) {
+        for (index, dst) in dst.iter_mut().enumerate() {
+            *dst = (byte >> (7 - index)) & 1;
+        }
+    }
+}
+
+/// Expands a row of 2-bit samples.
+fn unpack_2bit(src: &[u8], dst: &mut [u8]) {
+    for (dst, &byte) in dst.chunks_mut(4).zip(src) {
+        for (index, dst) in dst.iter_mut().enumerate() {
+            *dst = (byte >> (6 - 2 * index)) & 0b11;
+        }
+    }
+}
+
+/// Expands a row of 4-bit samples.
+fn unpack_4bit(src: &[u8], dst: &mut [u8]) {
+    #[cfg(target_arch = "x86_64")]
+    let (src, dst) = {
+        let consumed = simd::unpack_4bit(src, dst);
+        (&src[consumed..], &mut dst[consumed * 2..])
+    };
+
+    for (dst, &byte) in dst.chunks_mut(2).zip(src) {
+        dst[0] = byte >> 4;
+        if let Some(dst) = dst.get_mut(1) {
+            *dst = byte & 0x0f;
+        }
+    }
+}
+
+/// Expands a row of 12-bit samples.
+fn unpack_12bit(src: &[u8], dst: &mut [u16]) {
+    for (dst, src) in dst.chunks_mut(2).zip(src.chunks(3)) {
+        dst[0] = (src[0] as u16) << 4 | (src[1] as u16) >> 4;
+        if let Some(dst) = dst.get_mut(1) {
+            *dst = (src[1] as u16 & 0x0f) << 8 | src[2] as u16;
+        }
+    }
+}
+
+/// Expands a row of samples of any number of bits.
+fn unpack_bits<T: Unpacked>(bits: u32, src: &[u8], dst: &mut [T]) {
+    let mask = (1 << bits) - 1;
+    let mut bytes = src.iter();
+    // The bits not consumed yet are the least significant bits of the word.
+    let mut word = 0u32;
+    let mut available = 0;
+    for dst in dst {
+        while available < bits {
+            word = (word << 8) | *bytes.next().expect("the row is too short") as u32;
+            available += 8;
+        }
+        available -= bits;
+        *dst = T::truncate((word >> available) & mask);
+    }
+}
+
+/// An unsigned integer holding an unpacked sample.
+trait Unpacked {
+    /// Converts the value, which fits in the type.
+    fn truncate(value: u32) -> Self;
+}
+
+impl Unpacked for u8 {
+    fn truncate(value: u32) -> Self {
+        value as u8
+    }
+}
+
+impl Unpacked for u16 {
+    fn truncate(value: u32) -> Self {
+        value as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use claims::*;
+
+    use super::*;
+
+    /// Reads the sample at the given index, one bit at a time.
+    fn read_sample(row: &[u8], bits: usize, index: usize) -> u16 {
+        (index * bits..(index + 1) * bits).fold(0, |sample, position| {
+            (sample << 1) | ((row[position / 8] >> (7 - position % 8)) & 1) as u16
+        })
+    }
+
+    fn random_rows(unpacker: &Unpacker, rows: usize) -> Vec<u8> {
+        repeat_with(|| fastrand::u8(..))
+            .take(rows * unpacker.row_size())
+            .collect()
+    }
+
+    #[test]
+    fn unpack_1bit_rows() {
+        // The second row starts on a byte boundary.
+        let unpacker = assert_ok!(Unpacker::new(10, 1, 1, FillOrder::MSB_TO_LSB));
+        let mut dst = [0u8; 20];
+        assert_ok!(unpacker.unpack_u8(&[0b1010_0000, 0b0100_0000, 0xff, 0xc0], &mut dst));
+        assert_eq!(dst[..10], [1, 0, 1, 0, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(dst[10..], [1; 10]);
+    }
+
+    #[test]
+    fn unpack_12bit_rows() {
+        let unpacker = assert_ok!(Unpacker::new(3, 1, 12, FillOrder::MSB_TO_LSB));
+        let mut dst = [0u16; 6];
+        // Each row of 36 bits is padded to 5 bytes.
+        let src = [0x12, 0x34, 0x56, 0x78, 0x90, 0xab, 0xcd, 0xef, 0x01, 0x20];
+        assert_ok!(unpacker.unpack_u16(&src, &mut dst));
+        assert_eq!(dst, [0x123, 0x456, 0x789, 0xabc, 0xdef, 0x012]);
+    }
+
+    #[test]
+    fn unpack_lsb_to_msb() {
+        let unpacker = assert_ok!(Unpacker::new(4, 1, 2, FillOrder::LSB_TO_MSB));
+        let mut dst = [0u8; 4];
+        assert_ok!(unpacker.unpack_u8(&[0b1110_0100], &mut dst));
+        assert_eq!(dst, [0, 2, 1, 3]);
+    }
+
+    #[test]
+    fn unpack_matches_bit_by_bit() {
+        for bits in 1..=MAX_BITS {
+            for fill_order in [FillOrder::MSB_TO_LSB, FillOrder::LSB_TO_MSB] {
+                // Rows long enough to use the SIMD kernels, with an incomplete last byte.
+                let ncols = fastrand::u32(1..=300);
+                let samples = fastrand::u16(1..=3);
+                let unpacker = assert_ok!(Unpacker::new(ncols, samples, bits, fill_order));
+                let src = random_rows(&unpacker, 3);
+
+                let reversed = match fill_order {
+                    FillOrder::LSB_TO_MSB => src.iter().map(|byte| byte.reverse_bits()).collect(),
+                    _ => src.clone(),
+                };
+                let expected = reversed
+                    .chunks_exact(unpacker.row_size())
+                    .flat_map(|row| {
+                        (0..unpacker.row_len()).map(|index| read_sample(row, bits as usize, index))
+                    })
+                    .collect::<Vec<_>>();
+
+                let mut actual = vec![0u16; expected.len()];
+                assert_ok!(unpacker.unpack_u16(&src, &mut actual));
+                assert_eq!(actual, expected, "{samples} samples of {bits} bits");
+
+                if bits <= 8 {
+                    let mut actual = vec![0u8; expected.len()];
+                    assert_ok!(unpacker.unpack_u8(&src, &mut actual));
+                    let actual = actual.into_iter().map(u16::from).collect::<Vec<_>>();
+                    assert_eq!(actual, expected, "{samples} samples of {bits} bits");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn unsupported_bits() {
+        for bits in [0, 16, 32] {
+            let err = assert_err!(Unpacker::new(1, 1, bits, FillOrder::MSB_TO_LSB));
+            assert_eq!(
+                err.kind(),
+                crate::ErrorKind::Unsupported(Unsupported::BitsPerSample(bits))
+            );
+        }
+
+        let unpacker = assert_ok!(Unpacker::new(1, 1, 12, FillOrder::MSB_TO_LSB));
+        let err = assert_err!(unpacker.unpack_u8(&[0; 2], &mut [0; 1]));
+        assert_eq!(
+            err.kind(),
+            crate::ErrorKind::Unsupported(Unsupported::BitsPerSample(12))
+        );
+    }
+
+    #[test]
+    fn unknown_fill_order() {
+        assert_err!(Unpacker::new(1, 1, 1, FillOrder(3)));
+    }
+
+    #[test]
+    #[should_panic = "different numbers of rows"]
+    fn incomplete_row() {
+        let unpacker = Unpacker::new(10, 1, 1, FillOrder::MSB_TO_LSB).unwrap();
+        let _ = unpacker.unpack_u8(&[0; 3], &mut [0; 10]);
+    }
+}
@@ -0,0 +1,129 @@
+//! Implementation of unpack kernels using SIMD instructions.
+//!
+//! Each kernel expands whole registers of packed bytes, as long as the destination can hold all
+//! their samples, and returns the number of bytes consumed. The remaining bytes are expanded by
+//! the caller. Only SSE2 instructions are used, which are always available on x86_64.
+
+use std::arch::x86_64::*;
+
+/// The number of bytes of a register.
+const WIDTH: usize = size_of::<__m128i>();
+
+/// Expands 1-bit samples, 8 samples for each byte of `src`.
+pub fn unpack_1bit(src: &[u8], dst: &mut [u8]) -> usize {
+    let count = (src.len() / WIDTH).min(dst.len() / (8 * WIDTH));
+    let src = src[..count * WIDTH].chunks_exact(WIDTH);
+    let dst = dst[..count * 8 * WIDTH].chunks_exact_mut(8 * WIDTH);
+
+    // SAFETY: SSE2 is always available on x86_64.
+    let (mask, ones) = unsafe {
+        let mask = _mm_set_epi8(1, 2, 4, 8, 16, 32, 64, -128, 1, 2, 4, 8, 16, 32, 64, -128);
+        (mask, _mm_set1_epi8(1))
+    };
+
+    for (src, dst) in src.zip(dst) {
+        // SAFETY: SSE2 is always available on x86_64, the register is read from a chunk of 16
+        // bytes and written to chunks of 16 bytes.
+        unsafe {
+            let bytes = _mm_loadu_si128(src.as_ptr().cast());
+
+            // Each byte is repeated 8 times, so that each copy holds one of its samples.
+            let lo = _mm_unpacklo_epi8(bytes, bytes);
+            let hi = _mm_unpackhi_epi8(bytes, bytes);
+            let words = [
+                _mm_unpacklo_epi16(lo, lo),
+                _mm_unpackhi_epi16(lo, lo),
+                _mm_unpacklo_epi16(hi, hi),
+                _mm_unpackhi_epi16(hi, hi),
+            ];
+
+            for (words, dst) in words.into_iter().zip(dst.chunks_exact_mut(2 * WIDTH)) {
+                let repeated = [
+                    _mm_unpacklo_epi32(words, words),
+                    _mm_unpackhi_epi32(words, words),
+                ];
+                for (repeated, dst) in repeated.into_iter().zip(dst.chunks_exact_mut(WIDTH)) {
+                    let bits = _mm_cmpeq_epi8(_mm_and_si128(repeated, mask), mask);
+                    _mm_storeu_si128(dst.as_mut_ptr().cast(), _mm_and_si128(bits, ones));
+                }
+            }
+        }
+    }
+
+    count * WIDTH
+}
+
+/// Expands 4-bit samples, 2 samples for each byte of `src`.
+pub fn unpack_4bit(src: &[u8], dst: &mut [u8]) -> usize {
+    let count = (src.len() / WIDTH).min(dst.len() / (2 * WIDTH));
+    let src = src[..count * WIDTH].chunks_exact(WIDTH);
+    let dst = dst[..count * 2 * WIDTH].chunks_exact_mut(2 * WIDTH);
+
+    // SAFETY: SSE2 is always available on x86_64.
+    let mask = unsafe { _mm_set1_epi8(0x0f) };
+
+    for (src, dst) in src.zip(dst) {
+        // SAFETY: SSE2 is always available on x86_64, the register is read from a chunk of 16
+        // bytes and written to a chunk of 32 bytes.
+        unsafe {
+            let bytes = _mm_loadu_si128(src.as_ptr().cast());
+            let hi = _mm_and_si128(_mm_srli_epi16::<4>(bytes), mask);
+            let lo = _mm_and_si128(bytes, mask);
+
+            // The sample in the high nibble comes first.
+            let dst = dst.as_mut_ptr().cast::<__m128i>();
+            _mm_storeu_si128(dst, _mm_unpacklo_epi8(hi, lo));
+            _mm_storeu_si128(dst.add(1), _mm_unpackhi_epi8(hi, lo));
+        }
+    }
+
+    count * WIDTH
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter::repeat_with;
+
+    use super::*;
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        repeat_with(|| fastrand::u8(..)).take(len).collect()
+    }
+
+    #[test]
+    fn unpack_1bit_matches_scalar() {
+        let src = random_bytes(3 * WIDTH + 5);
+        let mut dst = vec![0xff; src.len() * 8];
+        assert_eq!(unpack_1bit(&src, &mut dst), 3 * WIDTH);
+
+        for (index, &sample) in dst[..3 * WIDTH * 8].iter().enumerate() {
+            assert_eq!(
+                sample,
+                (src[index / 8] >> (7 - index % 8)) & 1,
+                "sample {index}"
+            );
+        }
+        assert!(dst[3 * WIDTH * 8..].iter().all(|&sample| sample == 0xff));
+    }
+
+    #[test]
+    fn unpack_4bit_matches_scalar() {
+        let src = random_bytes(3 * WIDTH + 5);
+        let mut dst = vec![0xff; src.len() * 2];
+        assert_eq!(unpack_4bit(&src, &mut dst), 3 * WIDTH);
+
+        for (index, &sample) in dst[..3 * WIDTH * 2].iter().enumerate() {
+            let expected = (src[index / 2] >> (4 - 4 * (index % 2))) & 0x0f;
+            assert_eq!(sample, expected, "sample {index}");
+        }
+        assert!(dst[3 * WIDTH * 2..].iter().all(|&sample| sample == 0xff));
+    }
+
+    #[test]
+    fn short_destination() {
+        let src = random_bytes(2 * WIDTH);
+        // The last register cannot be expanded entirely.
+        assert_eq!(unpack_1bit(&src, &mut [0; 16 * WIDTH - 1]), WIDTH);
+        assert_eq!(unpack_4bit(&src, &mut [0; 4 * WIDTH - 1]), WIDTH);
+    }
+}
@@ -0,0 +1,33 @@
+/// The position of the chroma samples relative to the luma samples of subsampled YCbCr pixels.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub struct YCbCrPositioning(pub u16);
+
+impl Default for YCbCrPositioning {
+    fn default() -> Self {
+        Self::CENTERED
+    }
+}
+
+impl std::fmt::Debug for YCbCrPositioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}({})", self.name(), self.0)
+    }
+}
+
+impl YCbCrPositioning {
+    /// The chroma samples are located at the center of the luma samples they cover.
+    pub const CENTERED: Self = Self(1);
+    /// The chroma samples are located at the top-left luma sample they cover.
+    pub const COSITED: Self = Self(2);
+}
+
+impl YCbCrPositioning {
+    /// Returns the name of the tag if known, otherwise "Unknown" is returned.
+    fn name(&self) -> &'static str {
+        match self.0 {
+            1 => "Centered",
+            2 => "Cosited",
+            _ => "Unknown",
+        }
+    }
+}
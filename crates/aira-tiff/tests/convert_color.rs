@@ -0,0 +1,217 @@
+use aira_tiff::{
+    color::{ColorConverter, ColorFormat},
+    reader::PixelReader,
+    ByteOrder, Interpretation,
+};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Builds a little-endian TIFF file storing the 8-bit samples of a single row in a single strip.
+fn build_row(interpretation: u16, samples: u16, pixels: &[u8], extra: &[Entry]) -> Vec<u8> {
+    let width = (pixels.len() / samples as usize) as u16;
+    let mut tiff = TiffBuilder::default();
+    let mut entries = vec![
+        short(256, width),
+        short(257, 1),
+        shorts(258, &vec![8; samples as usize]),
+        short(259, 1),
+        short(262, interpretation),
+        short(277, samples),
+        short(278, 1),
+    ];
+    entries.extend(tiff.strips(&[pixels]));
+    entries.extend_from_slice(extra);
+    entries.sort_by_key(|entry| entry.0);
+    tiff.directory(&entries);
+    tiff.finish()
+}
+
+/// Reads the pixels of the only directory of the given file and converts them.
+fn convert(bytes: &[u8], format: ColorFormat) -> Result<Vec<u8>, aira_tiff::Error> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+    let samples = metadata.samples().len();
+    let mut src = vec![0u8; pixels.image_len(samples)];
+    assert_ok!(pixels.read_image(&mut reader, &mut src));
+
+    let converter = ColorConverter::new(&metadata, format)?;
+    assert_eq!(converter.samples_per_pixel(), samples);
+    let mut dst = vec![0u8; src.len() / samples * format.channels()];
+    converter.convert_u8(&src, &mut dst);
+    Ok(dst)
+}
+
+#[test]
+fn convert_gray() {
+    let bytes = build_row(1, 1, &[0, 100, 255], &[]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 100, 100, 100, 255, 255, 255]
+    );
+
+    let bytes = build_row(0, 1, &[0, 100, 255], &[]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgba8)),
+        [255, 255, 255, 255, 155, 155, 155, 255, 0, 0, 0, 255]
+    );
+}
+
+#[test]
+fn convert_palette() {
+    let mut map = vec![0u16; 3 * 256];
+    map[1] = 0xffff;
+    map[256 + 2] = 0x8080;
+    map[512 + 2] = 0x4040;
+    let bytes = build_row(3, 1, &[0, 1, 2], &[shorts(320, &map)]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 255, 0, 0, 0, 128, 64]
+    );
+
+    // Color maps holding 8-bit values are detected.
+    let mut map = vec![0u16; 3 * 256];
+    map[1] = 255;
+    map[256 + 2] = 128;
+    let bytes = build_row(3, 1, &[1, 2], &[shorts(320, &map)]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [255, 0, 0, 0, 128, 0]
+    );
+
+    let bytes = build_row(3, 1, &[1, 2], &[]);
+    assert_err!(convert(&bytes, ColorFormat::Rgb8));
+}
+
+#[test]
+fn convert_cmyk() {
+    let pixels = [0, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 255, 0, 255, 255, 128];
+    let bytes = build_row(5, 4, &pixels, &[]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [255, 255, 255, 0, 255, 255, 0, 0, 0, 127, 0, 0]
+    );
+
+    let bytes = build_row(5, 4, &pixels, &[shorts(332, &[2])]);
+    assert_err!(convert(&bytes, ColorFormat::Rgb8));
+}
+
+#[test]
+fn convert_ycbcr() {
+    let pixels = [0, 128, 128, 255, 128, 128, 76, 85, 255];
+    let bytes = build_row(6, 3, &pixels, &[shorts(530, &[1, 1])]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 255, 255, 255, 254, 0, 0]
+    );
+
+    // The luma has a reduced range with the reference black and white.
+    let reference = rationals(
+        532,
+        &[(16, 1), (235, 1), (128, 1), (240, 1), (128, 1), (240, 1)],
+    );
+    let bytes = build_row(
+        6,
+        3,
+        &[16, 128, 128, 235, 128, 128],
+        &[shorts(530, &[1, 1]), reference],
+    );
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 255, 255, 255]
+    );
+}
+
+#[test]
+fn convert_cielab() {
+    let bytes = build_row(8, 3, &[0, 0, 0, 255, 0, 0], &[]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 255, 255, 255]
+    );
+
+    let bytes = build_row(9, 3, &[0, 128, 128, 255, 128, 128], &[]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [0, 0, 0, 255, 255, 255]
+    );
+}
+
+#[test]
+fn convert_extra_samples() {
+    // Unassociated alpha is kept as is, and composited over black.
+    let pixels = [200, 100, 50, 128, 200, 100, 50, 0];
+    let bytes = build_row(2, 4, &pixels, &[shorts(338, &[2])]);
+    assert_eq!(assert_ok!(convert(&bytes, ColorFormat::Rgba8)), pixels);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [100, 50, 25, 0, 0, 0]
+    );
+
+    // Associated alpha is divided out.
+    let pixels = [100, 50, 25, 128];
+    let bytes = build_row(2, 4, &pixels, &[shorts(338, &[1])]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgba8)),
+        [199, 100, 50, 128]
+    );
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgb8)),
+        [100, 50, 25]
+    );
+
+    // Unspecified extra samples are ignored.
+    let pixels = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+    let bytes = build_row(2, 5, &pixels, &[shorts(338, &[0, 2])]);
+    assert_eq!(
+        assert_ok!(convert(&bytes, ColorFormat::Rgba8)),
+        [1, 2, 3, 5, 6, 7, 8, 10]
+    );
+
+    let bytes = build_row(2, 3, &[1, 2, 3], &[shorts(338, &[2])]);
+    assert_err!(convert(&bytes, ColorFormat::Rgb8));
+}
+
+#[test]
+fn convert_associated_alpha_image() {
+    let bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    assert_eq!(metadata.interpretation, Interpretation::BLACK_IS_ZERO);
+
+    let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+    let mut samples = vec![0u8; pixels.image_len(2)];
+    assert_ok!(pixels.read_image(&mut reader, &mut samples));
+
+    let converter = assert_ok!(ColorConverter::new(&metadata, ColorFormat::Rgba8));
+    assert!(converter.has_alpha());
+    let mut rgba = vec![0u8; samples.len() * 2];
+    converter.convert_u8(&samples, &mut rgba);
+
+    for (gray_alpha, rgba) in samples.chunks_exact(2).zip(rgba.chunks_exact(4)) {
+        let &[gray, alpha] = gray_alpha else {
+            unreachable!()
+        };
+        assert_eq!(rgba[0], rgba[1]);
+        assert_eq!(rgba[0], rgba[2]);
+        assert_eq!(rgba[3], alpha);
+        if alpha > 0 {
+            let expected = (gray as u32 * 255 + alpha as u32 / 2) / alpha as u32;
+            assert_eq!(rgba[0] as u32, expected.min(255));
+        }
+    }
+}
+
+#[test]
+fn unsupported_interpretation() {
+    let bytes = build_row(32844, 1, &[0, 1], &[]);
+    let err = assert_err!(convert(&bytes, ColorFormat::Rgb8));
+    assert_eq!(
+        err.kind(),
+        aira_tiff::ErrorKind::Unsupported(aira_tiff::Unsupported::Interpretation(Interpretation(
+            32844
+        )))
+    );
+}
@@ -0,0 +1,190 @@
+#![cfg(feature = "deflate")]
+#![cfg_attr(feature = "f16", feature(f16))]
+
+use std::io::{Read, Seek};
+
+use aira_tiff::{
+    compression::DecompressReader,
+    float,
+    predictor::{FloatPredictorReader, FloatPredictorWriter},
+    reader::PixelReader,
+    ByteOrder, Decoder, Metadata, SampleFormat,
+};
+use claims::*;
+
+/// The first row of `random-fp16.tiff`, as computed by an independent decoder.
+const RANDOM_FP16_FIRST_ROW: [f64; 16] = [
+    1.0,
+    0.69921875,
+    0.5498046875,
+    1.0,
+    0.6357421875,
+    1.0,
+    1.0,
+    0.86279296875,
+    1.0,
+    1.0,
+    0.5068359375,
+    1.0,
+    1.0,
+    1.0,
+    1.0,
+    0.9130859375,
+];
+
+/// The sum of all the samples of `random-fp16.tiff`.
+const RANDOM_FP16_SUM: f64 = 213.475341796875;
+
+/// Returns the width of `random-fp16.tiff` and its decompressed samples in native byte order.
+fn decompress_random_fp16() -> (u32, Vec<u8>) {
+    let file = assert_ok!(std::fs::File::open("tests/images/random-fp16.tiff"));
+    let mut reader = std::io::BufReader::new(file);
+    let mut decoder = assert_ok!(Decoder::new(&mut reader));
+    let byteorder = decoder.byteorder();
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    let metadata = assert_ok!(Metadata::from_decoder(directory));
+
+    let [sample] = metadata.samples() else {
+        panic!("random-fp16.tiff has a single sample per pixel");
+    };
+    assert_eq!(sample.format, SampleFormat::FLOAT);
+    assert_eq!(sample.bits, 16);
+
+    let mut data = Vec::new();
+    for chunk in assert_some!(metadata.chunks()) {
+        assert_ok!(reader.seek(std::io::SeekFrom::Start(chunk.offset)));
+        let chunk_reader = (&mut reader).take(chunk.byte_count);
+        let mut chunk_reader =
+            assert_ok!(DecompressReader::new(chunk_reader, metadata.compression));
+        assert_ok!(chunk_reader.read_to_end(&mut data));
+    }
+
+    let (width, height) = metadata.dimensions;
+    assert_eq!(data.len(), width as usize * height as usize * 2);
+    if byteorder != ByteOrder::native() {
+        data.chunks_exact_mut(2)
+            .for_each(|sample| sample.swap(0, 1));
+    }
+    (width, data)
+}
+
+/// Checks the decoded samples of `random-fp16.tiff` against the reference.
+fn check_random_fp16(values: &[f32]) {
+    let values = values.iter().map(|&value| value as f64).collect::<Vec<_>>();
+    assert_eq!(values[..16], RANDOM_FP16_FIRST_ROW);
+    assert_eq!(values.iter().sum::<f64>(), RANDOM_FP16_SUM);
+}
+
+#[test]
+fn decode_f16_samples() {
+    let (_, data) = decompress_random_fp16();
+
+    let mut values = vec![0f32; data.len() / 2];
+    assert_ok!(float::read_into(
+        &mut &data[..],
+        ByteOrder::native(),
+        2,
+        &mut values
+    ));
+
+    check_random_fp16(&values);
+}
+
+#[test]
+fn read_f16_pixels() {
+    let file = assert_ok!(std::fs::File::open("tests/images/random-fp16.tiff"));
+    let mut reader = std::io::BufReader::new(file);
+    let mut decoder = assert_ok!(Decoder::new(&mut reader));
+    let byteorder = decoder.byteorder();
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    let metadata = assert_ok!(Metadata::from_decoder(directory));
+
+    let pixels = assert_ok!(PixelReader::new(&metadata, byteorder));
+    let mut data = vec![0u8; pixels.image_len(1)];
+    assert_ok!(pixels.read_image(&mut reader, &mut data));
+
+    let mut values = vec![0f32; data.len() / 2];
+    assert_ok!(float::widen_into(
+        ByteOrder::native(),
+        2,
+        &data,
+        &mut values
+    ));
+    check_random_fp16(&values);
+}
+
+#[test]
+fn decode_f16_samples_with_predictor() {
+    use std::io::Write;
+
+    let (width, data) = decompress_random_fp16();
+
+    let mut writer = assert_ok!(FloatPredictorWriter::new(Vec::new(), width, 1, 2));
+    assert_ok!(writer.write_all(&data));
+    let encoded = assert_ok!(writer.finish());
+
+    let mut reader = FloatPredictorReader::new(&encoded[..], width, 1, 2);
+    let mut values = vec![0f32; data.len() / 2];
+    assert_ok!(reader.read_samples_f32(&mut values));
+
+    check_random_fp16(&values);
+}
+
+/// Two rows of four half precision samples, encoded by hand with the floating point predictor:
+/// the bytes of each row are split into planes from the most significant one, then each byte is
+/// replaced by its difference with the previous one.
+const F16_PREDICTOR_ROWS: [u8; 16] = [
+    // 1.0, 2.0, -1.5, 0.5 as 0x3c00, 0x4000, 0xbe00, 0x3800.
+    0x3c, 0x04, 0x7e, 0x7a, 0xc8, 0x00, 0x00, 0x00,
+    // 0.25, -2.0, 65504.0, 2^-24 as 0x3400, 0xc000, 0x7bff, 0x0001.
+    0x34, 0x8c, 0xbb, 0x85, 0x00, 0x00, 0xff, 0x02,
+];
+
+/// The samples of [`F16_PREDICTOR_ROWS`].
+const F16_PREDICTOR_VALUES: [f32; 8] = [1.0, 2.0, -1.5, 0.5, 0.25, -2.0, 65504.0, 5.9604645e-8];
+
+#[test]
+fn decode_f16_samples_with_predictor_reference() {
+    let mut reader = FloatPredictorReader::new(&F16_PREDICTOR_ROWS[..], 4, 1, 2);
+    let mut values = [0f32; 8];
+    assert_ok!(reader.read_samples_f32(&mut values));
+
+    assert_eq!(values, F16_PREDICTOR_VALUES);
+    assert_eq!(values[7], 2f32.powi(-24));
+}
+
+#[test]
+fn encode_f16_samples_with_predictor_reference() {
+    use std::io::Write;
+
+    let samples = [
+        0x3c00u16, 0x4000, 0xbe00, 0x3800, 0x3400, 0xc000, 0x7bff, 0x0001,
+    ];
+    let mut writer = assert_ok!(FloatPredictorWriter::new(Vec::new(), 4, 1, 2));
+    for sample in samples {
+        assert_ok!(writer.write_all(&sample.to_ne_bytes()));
+    }
+
+    assert_eq!(assert_ok!(writer.finish()), F16_PREDICTOR_ROWS);
+}
+
+#[cfg(feature = "f16")]
+#[test]
+fn decode_f16_samples_as_primitive() {
+    use std::io::Write;
+
+    let (width, data) = decompress_random_fp16();
+
+    let mut writer = assert_ok!(FloatPredictorWriter::new(Vec::new(), width, 1, 2));
+    assert_ok!(writer.write_all(&data));
+    let encoded = assert_ok!(writer.finish());
+
+    let mut reader = FloatPredictorReader::new(&encoded[..], width, 1, 2);
+    let mut values = vec![0f16; data.len() / 2];
+    assert_ok!(reader.read_samples_f16(&mut values));
+
+    let values = values.iter().map(|&value| value as f32).collect::<Vec<_>>();
+    check_random_fp16(&values);
+}
@@ -0,0 +1,159 @@
+use aira_tiff::{compression::DecompressReader, logluv::LogLuvReader, Compression, Interpretation};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Builds a little-endian TIFF file storing a LogL image of 2x2 pixels in a single strip.
+fn build_logl(compression: u16, strip: &[u8]) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    let [offsets, byte_counts] = tiff.strips(&[strip]);
+    tiff.directory(&[
+        short(256, 2),
+        short(257, 2),
+        short(258, 16),
+        short(259, compression),
+        short(262, 34844),
+        offsets,
+        short(277, 1),
+        short(278, 2),
+        byte_counts,
+    ]);
+    tiff.finish()
+}
+
+#[test]
+fn read_logluv_image() {
+    let bytes = assert_ok!(std::fs::read("tests/images/logluv-3c-16b.tiff"));
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    assert_eq!(metadata.interpretation, Interpretation::LOGLUV);
+    assert_eq!(metadata.compression, Compression::SGILOG);
+
+    let pixels = assert_ok!(LogLuvReader::new(&metadata));
+    assert_eq!(pixels.samples_per_pixel(), 3);
+
+    // The only pixel is black.
+    let mut xyz = vec![f32::NAN; pixels.image_len()];
+    assert_ok!(pixels.read_image(&mut reader, &mut xyz));
+    assert_eq!(xyz, [0.0; 3]);
+    let mut rgb = vec![f32::NAN; 3];
+    assert_ok!(pixels.read_linear_rgb(&mut reader, &mut rgb));
+    assert_eq!(rgb, [0.0; 3]);
+}
+
+#[test]
+fn read_logl_image() {
+    // The planes of the most and least significant bytes of each row.
+    let strip = b"\x02\x40\x41\x80\x00\x02\x00\xc0\x80\x00";
+
+    for compression in [Compression::SGILOG, Compression::SGILOG24] {
+        let bytes = build_logl(compression.0, strip);
+        let mut reader = std::io::Cursor::new(&bytes);
+        let metadata = utils::get_the_only_one_directory(&mut reader);
+        let pixels = assert_ok!(LogLuvReader::new(&metadata));
+        assert_eq!(pixels.samples_per_pixel(), 1);
+
+        let mut luminance = vec![f32::NAN; pixels.image_len()];
+        assert_ok!(pixels.read_image(&mut reader, &mut luminance));
+        let expected = [1.0, 2.0, 0.0, -1.0];
+        for (y, expected) in luminance.iter().zip(expected) {
+            assert!((y - expected).abs() < 1e-2, "{luminance:?}");
+        }
+
+        let mut rgb = vec![f32::NAN; 12];
+        assert_ok!(pixels.read_linear_rgb(&mut reader, &mut rgb));
+        for (pixel, y) in rgb.chunks_exact(3).zip(&luminance) {
+            assert_eq!(pixel, [*y; 3]);
+        }
+    }
+}
+
+#[test]
+fn read_truncated_logl_image() {
+    let bytes = build_logl(Compression::SGILOG.0, b"\x02\x40\x41\x80\x00\x02\x00");
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(LogLuvReader::new(&metadata));
+    let mut luminance = vec![0.0; pixels.image_len()];
+    assert_err!(pixels.read_image(&mut reader, &mut luminance));
+}
+
+/// Builds a little-endian TIFF file storing a 24-bit LogLuv image of 3x2 pixels, with a strip
+/// for each row.
+fn build_logluv24(strips: &[&[u8]]) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    let [offsets, byte_counts] = tiff.strips(strips);
+    tiff.directory(&[
+        short(256, 3),
+        short(257, 2),
+        shorts(258, &[16; 3]),
+        short(259, Compression::SGILOG24.0),
+        short(262, 32845),
+        offsets,
+        short(277, 3),
+        short(278, 1),
+        byte_counts,
+    ]);
+    tiff.finish()
+}
+
+#[test]
+fn read_logluv24_image() {
+    // The 24-bit pixels are stored uncompressed in big-endian order.
+    let rows: [&[u8]; 2] = [
+        b"\x00\x00\x64\x80\x00\x00\x80\x1f\x40",
+        b"\xaf\x3f\xa0\x4b\x3f\xac\xff\xf0\x39",
+    ];
+    let bytes = build_logluv24(&rows);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    assert_eq!(metadata.compression, Compression::SGILOG24);
+    let pixels = assert_ok!(LogLuvReader::new(&metadata));
+    assert_eq!(pixels.samples_per_pixel(), 3);
+
+    // The values decoded by libtiff.
+    let mut xyz = vec![f32::NAN; pixels.image_len()];
+    assert_ok!(pixels.read_image(&mut reader, &mut xyz));
+    assert_eq!(
+        xyz,
+        [
+            [0.0; 3],
+            [1.8867925, 0.06283937, 9.143449],
+            [0.18030214, 0.06283937, 0.12749408],
+            [0.17644586, 0.48140147, -5.6197996e-6],
+            [0.0063251373; 3],
+            [36.793976, 15.91359, 8.952228],
+        ]
+        .concat()
+    );
+
+    // A truncated strip cannot be read.
+    let bytes = build_logluv24(&[rows[0], &rows[1][..8]]);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(LogLuvReader::new(&metadata));
+    assert_err!(pixels.read_image(&mut reader, &mut xyz));
+}
+
+#[test]
+fn read_sparse_logluv24_image() {
+    let bytes = build_logluv24(&[b"\x80\x00\x00\x80\x00\x00\x80\x00\x00", &[]]);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(LogLuvReader::new(&metadata));
+
+    // The sparse strip is black.
+    let mut xyz = vec![f32::NAN; pixels.image_len()];
+    assert_ok!(pixels.read_image(&mut reader, &mut xyz));
+    assert_eq!(xyz[..9], [1.8867925, 0.06283937, 9.143449].repeat(3));
+    assert_eq!(xyz[9..], [0.0; 9]);
+}
+
+#[test]
+fn decompress_sgilog() {
+    // The SGI Log compressions are only decoded by the LogLuv reader.
+    for compression in [Compression::SGILOG, Compression::SGILOG24] {
+        assert_err!(DecompressReader::new(&[][..], compression));
+    }
+}
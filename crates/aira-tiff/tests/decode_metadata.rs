@@ -1,13 +1,16 @@
 use std::io::{Read, Seek};
 
 use aira_tiff::{
-    metadata::{Layout, Resolution, Sample},
-    Compression, Interpretation, PlanarConfiguration, Ratio, ResolutionUnit, SampleFormat,
-    SubfileType,
+    metadata::{Chunk, Layout, MetadataBuilder, Resolution, Sample},
+    reader::Window,
+    Compression, DType, Decoder, EntryPart, ErrorKind, ExtraSample, FillOrder, Interpretation,
+    Metadata, Orientation, PlanarConfiguration, Ratio, ResolutionUnit, SampleFormat, SubfileType,
+    Tag,
 };
 use claims::*;
 
 mod utils;
+use utils::*;
 
 #[test]
 fn decode_metadata() {
@@ -38,6 +41,19 @@ fn decode_metadata() {
         metadata.samples(),
         [Sample::new(SampleFormat::UNSIGNED, 8); 3]
     );
+    assert_eq!(metadata.orientation, Orientation::TOP_LEFT);
+    assert_eq!(metadata.fill_order, FillOrder::MSB_TO_LSB);
+    assert!(metadata.extra_samples().is_empty());
+    assert_eq!(metadata.sample_values(), [(0, 255); 3]);
+    assert_eq!(metadata.sample_ranges(), [(0.0, 255.0); 3]);
+    assert_none!(metadata.color_map());
+    assert!(metadata.ink_names().is_empty());
+    assert_none!(metadata.document_name());
+    assert_none!(metadata.page_name());
+    assert_some_eq!(metadata.page_number(), (0, 1));
+    assert_none!(metadata.position());
+    assert_none!(metadata.make());
+    assert_none!(metadata.model());
 
     assert_none!(metadata.artist());
     assert_none!(metadata.copyright());
@@ -50,10 +66,450 @@ fn decode_metadata() {
     assert_eq!(metadata.chunks_count(), 48);
 
     let mut buffer = Vec::<u8>::new();
-    for chunk in metadata.chunks() {
+    for chunk in assert_some!(metadata.chunks()) {
         buffer.resize(chunk.byte_count as usize, 0u8);
 
         assert_ok!(reader.seek(std::io::SeekFrom::Start(chunk.offset)));
         assert_ok!(reader.read_exact(&mut buffer));
     }
 }
+
+/// Returns the offset of the header of the entry with the given tag in the first directory of a
+/// little-endian classic TIFF file.
+fn find_entry_header(bytes: &[u8], tag: Tag) -> usize {
+    let ifd = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let count = u16::from_le_bytes(bytes[ifd..ifd + 2].try_into().unwrap()) as usize;
+    (0..count)
+        .map(|index| ifd + 2 + 12 * index)
+        .find(|&offset| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) == tag.0)
+        .expect("Missing entry")
+}
+
+/// Returns the offset of the data of an entry stored out of line.
+fn entry_data_offset(bytes: &[u8], header: usize) -> usize {
+    u32::from_le_bytes(bytes[header + 8..header + 12].try_into().unwrap()) as usize
+}
+
+fn decode_first_directory(bytes: &[u8]) -> Result<Metadata, aira_tiff::Error> {
+    let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(bytes)));
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    Metadata::from_decoder(directory)
+}
+
+fn decode_first_directory_leniently(bytes: &[u8]) -> Metadata {
+    let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(bytes)));
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    assert_ok!(MetadataBuilder::default().lenient(true).decode(directory))
+}
+
+#[test]
+fn locate_error_in_entry_header() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::IMAGE_LENGTH);
+    // Unknown datatype.
+    bytes[header + 2..header + 4].copy_from_slice(&99u16.to_le_bytes());
+
+    let err = assert_err!(decode_first_directory(&bytes));
+    assert_eq!(err.kind(), ErrorKind::Format);
+
+    let location = assert_some!(err.location());
+    assert_eq!(location.offset, header as u64);
+    assert_some_eq!(location.directory, 0);
+    assert_some_eq!(location.entry, 1);
+    assert_some_eq!(location.tag, Tag::IMAGE_LENGTH);
+    assert_some_eq!(location.part, EntryPart::Header);
+}
+
+#[test]
+fn locate_error_in_entry_payload() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::STRIP_OFFSETS);
+    // Data stored beyond the end of file.
+    bytes[header + 8..header + 12].copy_from_slice(&0xFFFF_FF00u32.to_le_bytes());
+
+    let err = assert_err!(decode_first_directory(&bytes));
+    assert_eq!(err.kind(), ErrorKind::Format);
+
+    let location = assert_some!(err.location());
+    assert_eq!(location.offset, 0xFFFF_FF00);
+    assert_some_eq!(location.directory, 0);
+    assert_some_eq!(location.tag, Tag::STRIP_OFFSETS);
+    assert_some_eq!(location.part, EntryPart::Payload);
+}
+
+#[test]
+fn locate_error_in_directory() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::IMAGE_WIDTH);
+    // Image width cannot be zero.
+    bytes[header + 8..header + 12].copy_from_slice(&0u32.to_le_bytes());
+
+    let err = assert_err!(decode_first_directory(&bytes));
+    assert_eq!(err.kind(), ErrorKind::Format);
+
+    let location = assert_some!(err.location());
+    assert_eq!(
+        location.offset,
+        u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as u64
+    );
+    assert_some_eq!(location.directory, 0);
+    assert_none!(location.entry);
+    assert_none!(location.part);
+}
+
+#[test]
+fn lenient_rows_per_strip_with_wrong_type() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::ROWS_PER_STRIP);
+    bytes[header + 2..header + 4].copy_from_slice(&(DType::Byte as u16).to_le_bytes());
+
+    let err = assert_err!(decode_first_directory(&bytes));
+    assert_eq!(err.kind(), ErrorKind::UnexpectedDType(DType::Byte));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_eq!(metadata.layout, Layout::Strips { length: 64 });
+    let [warning] = metadata.warnings() else {
+        panic!("expected a single warning: {:?}", metadata.warnings());
+    };
+    assert_eq!(warning.kind(), ErrorKind::UnexpectedDType(DType::Byte));
+    let location = assert_some!(warning.location());
+    assert_eq!(location.offset, header as u64);
+    assert_some_eq!(location.part, EntryPart::Header);
+}
+
+#[test]
+fn lenient_zero_strip_byte_counts() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::STRIP_BYTE_COUNTS);
+    let data = entry_data_offset(&bytes, header);
+    bytes[data..data + 8].fill(0);
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    let byte_counts = assert_some!(metadata.chunks())
+        .map(|chunk| chunk.byte_count)
+        .collect::<Vec<_>>();
+    // The strips are compressed, the byte counts are estimated from the following offset.
+    assert_eq!(byte_counts, [2459, bytes.len() as u64 - 2813]);
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn lenient_missing_strip_byte_counts() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::STRIP_BYTE_COUNTS);
+    bytes[header..header + 2].copy_from_slice(&0xfde8u16.to_le_bytes());
+
+    assert_err!(decode_first_directory(&bytes));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_eq!(metadata.chunks_count(), 2);
+    let chunk = assert_some!(assert_some!(metadata.chunks()).next());
+    assert_eq!(chunk.byte_count, 2459);
+    assert!(!metadata.warnings().is_empty());
+}
+
+#[test]
+fn lenient_truncated_last_strip() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::STRIP_BYTE_COUNTS);
+    let data = entry_data_offset(&bytes, header);
+    bytes[data + 4..data + 8].copy_from_slice(&100_000u32.to_le_bytes());
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    let last = assert_some!(assert_some!(metadata.chunks()).next_back());
+    assert_eq!(last.offset + last.byte_count, bytes.len() as u64);
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn lenient_ycbcr_without_samples() {
+    let mut tiff = TiffBuilder::default();
+    let mut entries = vec![
+        short(256, 4),
+        short(257, 2),
+        short(259, 1),
+        short(262, 6),
+        short(277, 0),
+        short(278, 2),
+        shorts(530, &[2, 2]),
+    ];
+    // The byte count of the strip is estimated from the size of the uncompressed data.
+    let [offsets, _] = tiff.strips(&[&[0; 12]]);
+    entries.extend([offsets, long(279, 0)]);
+    entries.sort_by_key(|entry| entry.0);
+    tiff.directory(&entries);
+    let bytes = tiff.finish();
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert!(metadata.samples().is_empty());
+    let chunk = assert_some!(assert_some!(metadata.chunks()).next());
+    assert_eq!(chunk.byte_count, 0);
+    assert!(!metadata.warnings().is_empty());
+}
+
+#[test]
+fn lenient_string_without_nul() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::ARTIST);
+    let data = entry_data_offset(&bytes, header);
+    // The count of the entry is 10.
+    bytes[data + 9] = b'!';
+
+    let err = assert_err!(decode_first_directory(&bytes));
+    assert_some_eq!(assert_some!(err.location()).part, EntryPart::Payload);
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    let artist = assert_some!(metadata.artist());
+    assert_eq!(artist.len(), 10);
+    assert!(artist.ends_with('!'));
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_extra_samples() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_eq!(metadata.extra_samples(), [ExtraSample::ASSOCIATED_ALPHA]);
+
+    // More extra samples than samples.
+    let header = find_entry_header(&bytes, Tag::EXTRA_SAMPLES);
+    bytes[header + 4..header + 8].copy_from_slice(&3u32.to_le_bytes());
+    assert_err!(decode_first_directory(&bytes));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert!(metadata.extra_samples().is_empty());
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_color_map() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    // The resolution unit entry is turned into a color map entry, whose values are read from the
+    // beginning of the file.
+    let header = find_entry_header(&bytes, Tag::RESOLUTION_UNIT);
+    bytes[header..header + 2].copy_from_slice(&Tag::COLOR_MAP.0.to_le_bytes());
+    bytes[header + 4..header + 8].copy_from_slice(&(3u32 << 8).to_le_bytes());
+    bytes[header + 8..header + 12].copy_from_slice(&0u32.to_le_bytes());
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    let color_map = assert_some!(metadata.color_map());
+    assert_eq!(color_map.red.len(), 256);
+    assert_eq!(color_map.green.len(), 256);
+    assert_eq!(color_map.blue.len(), 256);
+    assert_eq!(color_map.red[0], u16::from_le_bytes([bytes[0], bytes[1]]));
+    assert_eq!(
+        color_map.green[0],
+        u16::from_le_bytes([bytes[512], bytes[513]])
+    );
+    assert_eq!(
+        color_map.blue[0],
+        u16::from_le_bytes([bytes[1024], bytes[1025]])
+    );
+
+    // The color map must hold 3 values for each of the 256 indices of the 8-bit samples.
+    bytes[header + 4..header + 8].copy_from_slice(&(3u32 << 4).to_le_bytes());
+    assert_err!(decode_first_directory(&bytes));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_none!(metadata.color_map());
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_color_map_without_samples() {
+    let mut tiff = TiffBuilder::default();
+    let mut entries = vec![
+        short(256, 1),
+        short(257, 1),
+        short(259, 1),
+        short(262, 3),
+        short(277, 0),
+        short(278, 1),
+        shorts(320, &[0; 6]),
+    ];
+    entries.extend(tiff.strips(&[&[0]]));
+    entries.sort_by_key(|entry| entry.0);
+    tiff.directory(&entries);
+    let bytes = tiff.finish();
+
+    // The size of the color map depends on the bits of the first sample.
+    assert_err!(decode_first_directory(&bytes));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_none!(metadata.color_map());
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_orientation() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    // The resolution unit entry is turned into an orientation entry.
+    let header = find_entry_header(&bytes, Tag::RESOLUTION_UNIT);
+    bytes[header..header + 2].copy_from_slice(&Tag::ORIENTATION.0.to_le_bytes());
+    bytes[header + 8..header + 10].copy_from_slice(&8u16.to_le_bytes());
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_eq!(metadata.orientation, Orientation::LEFT_BOTTOM);
+    assert_none!(metadata.custom_entry(Tag::ORIENTATION));
+
+    // An invalid orientation is kept as a custom entry.
+    bytes[header + 8..header + 10].copy_from_slice(&9u16.to_le_bytes());
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_eq!(metadata.orientation, Orientation::TOP_LEFT);
+    assert_some!(metadata.custom_entry(Tag::ORIENTATION));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_eq!(metadata.orientation, Orientation::TOP_LEFT);
+    assert_some!(metadata.custom_entry(Tag::ORIENTATION));
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_nodata() {
+    let build = |nodata: &str| {
+        let mut tiff = TiffBuilder::default();
+        let mut entries = vec![
+            short(256, 1),
+            short(257, 1),
+            short(258, 8),
+            short(259, 1),
+            short(262, 1),
+            short(278, 1),
+            ascii(42113, nodata),
+        ];
+        entries.extend(tiff.strips(&[&[0]]));
+        entries.sort_by_key(|entry| entry.0);
+        tiff.directory(&entries);
+        tiff.finish()
+    };
+
+    let metadata = assert_ok!(decode_first_directory(&build(" -9999 ")));
+    assert_some_eq!(metadata.nodata(), -9999.0);
+    assert_none!(metadata.custom_entry(Tag::GDAL_NO_DATA));
+
+    // A nodata value which is not a number is kept as a custom entry.
+    let bytes = build("none");
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_none!(metadata.nodata());
+    assert_some!(metadata.custom_entry(Tag::GDAL_NO_DATA));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_none!(metadata.nodata());
+    assert_some!(metadata.custom_entry(Tag::GDAL_NO_DATA));
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_sample_values() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    // The resolution unit entry is turned into a maximum sample value entry.
+    let header = find_entry_header(&bytes, Tag::RESOLUTION_UNIT);
+    bytes[header..header + 2].copy_from_slice(&Tag::MAX_SAMPLE_VALUE.0.to_le_bytes());
+    bytes[header + 8..header + 10].copy_from_slice(&200u16.to_le_bytes());
+
+    // A single value is given for 2 samples.
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_eq!(metadata.sample_values(), [(0, 200); 2]);
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_eq!(metadata.sample_values(), [(0, 200); 2]);
+    assert_eq!(metadata.warnings().len(), 1);
+
+    // The number of values matches neither the number of samples nor a single value.
+    bytes[header + 4..header + 8].copy_from_slice(&3u32.to_le_bytes());
+    bytes[header + 8..header + 12].copy_from_slice(&0u32.to_le_bytes());
+    assert_err!(decode_first_directory(&bytes));
+}
+
+#[test]
+fn decode_page_number() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::RESOLUTION_UNIT);
+    bytes[header..header + 2].copy_from_slice(&Tag::PAGE_NUMBER.0.to_le_bytes());
+    bytes[header + 4..header + 8].copy_from_slice(&2u32.to_le_bytes());
+    bytes[header + 8..header + 12].copy_from_slice(&[3, 0, 10, 0]);
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_some_eq!(metadata.page_number(), (3, 10));
+
+    // The page number has a single value.
+    bytes[header + 4..header + 8].copy_from_slice(&1u32.to_le_bytes());
+    assert_err!(decode_first_directory(&bytes));
+
+    let metadata = decode_first_directory_leniently(&bytes);
+    assert_none!(metadata.page_number());
+    assert_eq!(metadata.warnings().len(), 1);
+}
+
+#[test]
+fn decode_strings() {
+    let mut bytes = assert_ok!(std::fs::read("tests/images/minisblack-2c-8b-alpha.tiff"));
+    let header = find_entry_header(&bytes, Tag::IMAGE_DESCRIPTION);
+    bytes[header..header + 2].copy_from_slice(&Tag::DOCUMENT_NAME.0.to_le_bytes());
+    let header = find_entry_header(&bytes, Tag::ARTIST);
+    bytes[header..header + 2].copy_from_slice(&Tag::INK_NAMES.0.to_le_bytes());
+    // The count of the entry is 10.
+    let data = entry_data_offset(&bytes, header);
+    bytes[data..data + 10].copy_from_slice(b"Cyan\0Pink\0");
+
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert_some_eq!(metadata.document_name(), "Close up of a ladder");
+    assert_none!(metadata.description());
+    assert_eq!(metadata.ink_names(), ["Cyan", "Pink"]);
+}
+
+#[test]
+fn decode_deferred_chunks() {
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = assert_ok!(decode_first_directory(&bytes));
+    assert!(!metadata.has_deferred_chunks());
+
+    let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(&bytes)));
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    let deferred = assert_ok!(MetadataBuilder::default()
+        .defer_chunks(true)
+        .decode(directory));
+    assert!(deferred.has_deferred_chunks());
+    assert_eq!(deferred.chunks_count(), metadata.chunks_count());
+    // The deferred chunks are only read by window.
+    assert_none!(deferred.chunks());
+    assert_none!(deferred.plane_chunks(0));
+
+    let location = |chunk: Chunk| (chunk.origin, chunk.size, chunk.offset, chunk.byte_count);
+    for window in [
+        Window::new((0, 0), metadata.dimensions),
+        Window::new((10, 20), (1, 1)),
+        Window::new((30, 120), (70, 150)),
+        Window::new((450, 300), (40, 67)),
+    ] {
+        let expected = assert_some!(metadata.plane_chunks(0))
+            .filter(|chunk| {
+                Window::new(chunk.origin, chunk.size)
+                    .intersection(&window)
+                    .is_some()
+            })
+            .map(location)
+            .collect::<Vec<_>>();
+        let chunks = assert_ok!(deferred.window_chunks(&mut reader, 0, window));
+        assert_eq!(
+            chunks.into_iter().map(location).collect::<Vec<_>>(),
+            expected
+        );
+        let chunks = assert_ok!(metadata.window_chunks(&mut reader, 0, window));
+        assert_eq!(
+            chunks.into_iter().map(location).collect::<Vec<_>>(),
+            expected
+        );
+    }
+
+    // The arrays are decoded in lenient mode.
+    let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(&bytes)));
+    let mut directories = decoder.directories();
+    let directory = assert_some!(assert_ok!(directories.next_directory()));
+    let lenient = assert_ok!(MetadataBuilder::default()
+        .lenient(true)
+        .defer_chunks(true)
+        .decode(directory));
+    assert!(!lenient.has_deferred_chunks());
+}
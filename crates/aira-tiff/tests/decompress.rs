@@ -1,6 +1,9 @@
 use std::io::Read;
 
-use aira_tiff::{compression::DecompressReader, Compression, Metadata};
+use aira_tiff::{
+    compression::{CompressWriter, DecompressReader},
+    Compression, ErrorKind, Metadata, Unsupported,
+};
 use claims::*;
 
 mod utils;
@@ -10,7 +13,7 @@ where
     R: std::io::Read + std::io::Seek,
 {
     let mut buffer = Vec::<u8>::new();
-    for chunk in metadata.chunks() {
+    for chunk in assert_some!(metadata.chunks()) {
         assert_ok!(reader.seek(std::io::SeekFrom::Start(chunk.offset)));
         let chunk_reader = reader.take(chunk.byte_count);
         let mut chunk_reader =
@@ -52,3 +55,52 @@ fn decompress_deflate() {
     assert_eq!(metadata.compression, Compression::DEFLATE);
     try_decompress_all_chunks(metadata, &mut reader);
 }
+
+#[test]
+fn decompress_unsupported() {
+    let file = assert_ok!(std::fs::File::open("tests/images/logluv-3c-16b.tiff"));
+    let mut reader = std::io::BufReader::new(file);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+
+    let err = assert_err!(DecompressReader::new(&mut reader, metadata.compression));
+    assert_eq!(
+        err.kind(),
+        ErrorKind::Unsupported(Unsupported::Compression(metadata.compression))
+    );
+}
+
+#[test]
+fn compress_round_trip() {
+    use std::io::{Seek, Write};
+
+    let file = assert_ok!(std::fs::File::open("tests/images/tiled-rect-rgb-u8.tif"));
+    let mut reader = std::io::BufReader::new(file);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let row_size = metadata.chunk_size().0 as usize * metadata.samples().len();
+
+    let compressions = [
+        Compression::NONE,
+        Compression::PACKBITS,
+        #[cfg(feature = "deflate")]
+        Compression::DEFLATE,
+        #[cfg(feature = "lzw")]
+        Compression::LZW,
+    ];
+
+    for chunk in assert_some!(metadata.chunks()).take(4) {
+        assert_ok!(reader.seek(std::io::SeekFrom::Start(chunk.offset)));
+        let mut data = Vec::new();
+        assert_ok!((&mut reader).take(chunk.byte_count).read_to_end(&mut data));
+
+        for compression in compressions {
+            let mut writer = assert_ok!(CompressWriter::new(Vec::new(), compression, row_size));
+            assert_ok!(writer.write_all(&data));
+            let compressed = assert_ok!(writer.finish());
+
+            let mut reader = assert_ok!(DecompressReader::new(&compressed[..], compression));
+            let mut decompressed = Vec::new();
+            assert_ok!(reader.read_to_end(&mut decompressed));
+            assert_eq!(decompressed, data, "{compression:?}");
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Replay the inputs that made the fuzz targets crash.
+//!
+//! Each directory in `tests/fuzz` contains the inputs found by the fuzz target with the same name,
+//! the body of each test mirrors the one of the fuzz target.
+
+use std::io::Read;
+
+use aira_tiff::{
+    compression::PackBitsReader,
+    predictor::{FloatPredictorReader, IntPredictorReader},
+    ByteOrder, Decoder, Entry, Metadata,
+};
+use claims::*;
+
+/// The maximum number of directories visited, the chain of directories may contain cycles.
+const MAX_DIRECTORIES: usize = 16;
+
+fn inputs(target: &str) -> impl Iterator<Item = Vec<u8>> {
+    let path = std::path::Path::new("tests/fuzz").join(target);
+    assert_ok!(std::fs::read_dir(path)).map(|entry| {
+        let entry = assert_ok!(entry);
+        assert_ok!(std::fs::read(entry.path()))
+    })
+}
+
+#[test]
+fn decoder() {
+    for data in inputs("decoder") {
+        let Ok(mut decoder) = Decoder::new(std::io::Cursor::new(&data)) else {
+            continue;
+        };
+
+        let mut directories = decoder.directories();
+        for _ in 0..MAX_DIRECTORIES {
+            let Ok(Some(directory)) = directories.next_directory() else {
+                break;
+            };
+
+            let mut entries = directory.entries();
+            while let Ok(Some(entry)) = entries.next_entry() {
+                let _ = Entry::from_decoder(entry);
+            }
+        }
+    }
+}
+
+#[test]
+fn metadata() {
+    for data in inputs("metadata") {
+        let Ok(mut decoder) = Decoder::new(std::io::Cursor::new(&data)) else {
+            continue;
+        };
+
+        let mut directories = decoder.directories();
+        for _ in 0..MAX_DIRECTORIES {
+            let Ok(Some(directory)) = directories.next_directory() else {
+                break;
+            };
+
+            let Ok(metadata) = Metadata::from_decoder(directory) else {
+                continue;
+            };
+
+            for chunk in metadata.chunks().into_iter().flatten() {
+                std::hint::black_box(chunk);
+            }
+        }
+    }
+}
+
+#[test]
+fn packbits() {
+    for data in inputs("packbits") {
+        let mut reader = PackBitsReader::new(&data[..]);
+        let _ = reader.read_to_end(&mut Vec::new());
+    }
+}
+
+#[test]
+fn predictor() {
+    for data in inputs("predictor") {
+        let [flags, ncols, samples, bytespersample, data @ ..] = &data[..] else {
+            continue;
+        };
+
+        let byteorder = if flags & 0x01 == 0 {
+            ByteOrder::LittleEndian
+        } else {
+            ByteOrder::BigEndian
+        };
+        let ncols = *ncols as u32;
+        let samples = *samples as u16;
+        let bytespersample = *bytespersample as u16;
+
+        if flags & 0x02 == 0 {
+            let reader = if flags & 0x04 == 0 {
+                IntPredictorReader::new(data, byteorder, ncols, samples, bytespersample)
+            } else {
+                IntPredictorReader::with_bits(data, byteorder, ncols, samples, bytespersample)
+            };
+            let Ok(mut reader) = reader else {
+                continue;
+            };
+            let _ = reader.read_to_end(&mut Vec::new());
+        } else {
+            let mut reader = FloatPredictorReader::new(data, ncols, samples, bytespersample);
+            let _ = reader.read_to_end(&mut Vec::new());
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use aira_tiff::{
+    lint::{check, Rule},
+    Decoder,
+};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Builds a little-endian classic TIFF file with a single directory.
+fn build_tiff(entries: &[Entry]) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    tiff.directory(entries);
+    tiff.finish()
+}
+
+fn rules(bytes: Vec<u8>) -> Vec<Rule> {
+    let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(bytes)));
+    check(&mut decoder)
+        .into_iter()
+        .map(|violation| violation.rule)
+        .collect()
+}
+
+#[test]
+fn conforming_images() {
+    for path in [
+        "tests/images/minisblack-2c-8b-alpha.tiff",
+        "tests/images/random-fp16.tiff",
+        "tests/images/tiled-rect-rgb-u8.tif",
+    ] {
+        let file = assert_ok!(std::fs::File::open(path));
+        let mut decoder = assert_ok!(Decoder::new(file));
+        let violations = check(&mut decoder);
+        assert!(violations.is_empty(), "{path}: {violations:?}");
+    }
+}
+
+#[test]
+fn unsorted_and_wrong_dtype() {
+    let bytes = build_tiff(&[short(257, 1), short(256, 1), long(262, 1)]);
+    assert_eq!(rules(bytes), [Rule::SortedTags, Rule::TagDType]);
+}
+
+#[test]
+fn misaligned_and_overlapping_chunks() {
+    // The offsets of two overlapping strips of 8 bytes are stored at the odd offset 9, and the
+    // directory follows at the odd offset 17.
+    let mut tiff = TiffBuilder::default();
+    let mut data = vec![0; 1];
+    data.extend_from_slice(&12u32.to_le_bytes());
+    data.extend_from_slice(&16u32.to_le_bytes());
+    tiff.data(&data);
+    tiff.directory(&[
+        short(256, 1),
+        short(257, 2),
+        (273, 4, 2, 9u32.to_le_bytes().to_vec()),
+        short(278, 1),
+        shorts(279, &[8, 8]),
+    ]);
+    assert_eq!(
+        rules(tiff.finish()),
+        [
+            Rule::WordAlignedOffset,
+            Rule::WordAlignedOffset,
+            Rule::OverlappingChunks
+        ]
+    );
+}
+
+#[test]
+fn chunks_beyond_end_of_file() {
+    let bytes = build_tiff(&[
+        short(256, 1),
+        short(257, 1),
+        long(273, 8),
+        long(279, 1 << 20),
+    ]);
+    assert_eq!(rules(bytes), [Rule::ChunkWithinFile]);
+}
+
+#[test]
+fn samples_count_mismatch() {
+    let bytes = build_tiff(&[short(256, 1), shorts(258, &[8, 8]), short(277, 3)]);
+    assert_eq!(rules(bytes), [Rule::SampleCount]);
+}
+
+#[test]
+fn inconsistent_geo_keys() {
+    // Header and two keys: the second one is not sorted and refers to missing doubles.
+    let keys: [u16; 12] = [1, 1, 0, 2, 1024, 0, 1, 2, 1025, 34736, 1, 0];
+    assert_eq!(rules(build_tiff(&[shorts(34735, &keys)])), [Rule::GeoKeys]);
+
+    let keys: [u16; 12] = [1, 1, 0, 2, 1025, 0, 1, 2, 1024, 0, 1, 1];
+    assert_eq!(rules(build_tiff(&[shorts(34735, &keys)])), [Rule::GeoKeys]);
+}
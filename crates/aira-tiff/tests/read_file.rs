@@ -0,0 +1,213 @@
+use aira_tiff::{
+    file::{ImageKind, Level, TiffFile, TiffFileBuilder},
+    reader::{PixelReader, Window},
+    SubfileType,
+};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Builds a little-endian TIFF file with a directory for each image of the given subfile type and
+/// size, each storing 8-bit gray pixels filled with its index in a single strip.
+///
+/// The last directory points to the directory at the index `next`, if any.
+fn build_images(images: &[(u32, u32, u32)], next: Option<usize>) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    let strips = images
+        .iter()
+        .enumerate()
+        .map(|(index, &(_, width, length))| {
+            tiff.strips(&[&vec![index as u8; (width * length) as usize]])
+        })
+        .collect::<Vec<_>>();
+
+    let mut offsets = Vec::new();
+    for (&(subfile_type, width, length), [strip_offsets, byte_counts]) in images.iter().zip(strips)
+    {
+        offsets.push(tiff.directory(&[
+            long(254, subfile_type),
+            long(256, width),
+            long(257, length),
+            short(258, 8),
+            short(259, 1),
+            short(262, 1),
+            strip_offsets,
+            long(278, length),
+            byte_counts,
+        ]));
+    }
+    if let Some(next) = next {
+        tiff.set_next(offsets[next]);
+    }
+    tiff.finish()
+}
+
+const REDUCED_IMAGE: u32 = 1;
+const PAGE: u32 = 2;
+const MASK: u32 = 4;
+
+#[test]
+fn index_images() {
+    // A page with an overview, masks for both, and a second page with an overview.
+    let images = [
+        (0, 8, 6),
+        (MASK, 8, 6),
+        (REDUCED_IMAGE, 4, 3),
+        (REDUCED_IMAGE | MASK, 4, 3),
+        (PAGE, 5, 5),
+        (REDUCED_IMAGE, 3, 3),
+    ];
+    let bytes = build_images(&images, None);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    assert_eq!(tiff.len(), 6);
+    assert!(tiff.warnings().is_empty());
+
+    let kinds = tiff
+        .images()
+        .iter()
+        .map(|image| image.kind())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            ImageKind::Main,
+            ImageKind::Mask,
+            ImageKind::Overview,
+            ImageKind::Mask,
+            ImageKind::Page,
+            ImageKind::Overview,
+        ]
+    );
+    let parents = tiff
+        .images()
+        .iter()
+        .map(|image| image.parent())
+        .collect::<Vec<_>>();
+    assert_eq!(parents, [None, Some(0), Some(0), Some(0), None, Some(4)]);
+
+    assert_eq!(tiff.pages().collect::<Vec<_>>(), [0, 4]);
+    assert_eq!(tiff.overviews(0).collect::<Vec<_>>(), [2]);
+    assert_eq!(tiff.masks(0).collect::<Vec<_>>(), [1, 3]);
+    assert_eq!(tiff.overviews(4).collect::<Vec<_>>(), [5]);
+    assert_eq!(tiff.masks(4).count(), 0);
+
+    let overview = assert_some!(tiff.metadata(3));
+    assert_eq!(overview.dimensions, (4, 3));
+    assert_eq!(
+        overview.subfile_type,
+        SubfileType::REDUCED_IMAGE | SubfileType::MASK
+    );
+    assert_none!(tiff.image(6));
+
+    // The images are read in any order.
+    for index in [5, 0, 3] {
+        let metadata = assert_some!(tiff.metadata(index));
+        let pixels = assert_ok!(PixelReader::new(metadata, tiff.byteorder()));
+        let mut dst = vec![0u8; pixels.image_len(1)];
+        assert_ok!(pixels.read_image(&mut reader, &mut dst));
+        assert!(dst.iter().all(|&value| value == index as u8));
+    }
+}
+
+#[test]
+fn directories_loop() {
+    let images = [(0, 2, 2), (REDUCED_IMAGE, 1, 1)];
+    let bytes = build_images(&images, Some(0));
+    assert_err!(TiffFile::new(std::io::Cursor::new(&bytes)));
+
+    let tiff = assert_ok!(TiffFileBuilder::default()
+        .lenient(true)
+        .decode(std::io::Cursor::new(&bytes)));
+    assert_eq!(tiff.len(), 2);
+    assert_eq!(tiff.warnings().len(), 1);
+}
+
+#[test]
+fn skip_invalid_image() {
+    // The width of the second image is zero.
+    let images = [(0, 2, 2), (REDUCED_IMAGE, 0, 1), (REDUCED_IMAGE, 1, 1)];
+    let bytes = build_images(&images, None);
+    assert_err!(TiffFile::new(std::io::Cursor::new(&bytes)));
+
+    let tiff = assert_ok!(TiffFileBuilder::default()
+        .lenient(true)
+        .decode(std::io::Cursor::new(&bytes)));
+    assert_eq!(tiff.len(), 2);
+    assert_eq!(assert_some!(tiff.metadata(1)).dimensions, (1, 1));
+    assert_eq!(tiff.overviews(0).collect::<Vec<_>>(), [1]);
+    assert_eq!(tiff.warnings().len(), 1);
+}
+
+#[test]
+fn select_level() {
+    // A pyramid with masks for the full resolution image and the first overview.
+    let images = [
+        (0, 490, 367),
+        (REDUCED_IMAGE, 245, 184),
+        (REDUCED_IMAGE, 122, 91),
+        (REDUCED_IMAGE, 62, 46),
+        (MASK, 490, 367),
+        (REDUCED_IMAGE | MASK, 245, 184),
+    ];
+    let bytes = build_images(&images, None);
+    let tiff = assert_ok!(TiffFile::new(std::io::Cursor::new(&bytes)));
+
+    let levels = tiff.levels(0);
+    let indices = levels
+        .iter()
+        .map(|level| (level.image, level.mask))
+        .collect::<Vec<_>>();
+    assert_eq!(indices, [(0, Some(4)), (1, Some(5)), (2, None), (3, None)]);
+    assert_eq!(levels[0].downsampling(), (1.0, 1.0));
+    assert!(tiff.levels(1).is_empty());
+
+    let selected = |downsampling| assert_some!(tiff.select_level(0, downsampling)).image;
+    assert_eq!(selected(0.5), 0);
+    assert_eq!(selected(1.0), 0);
+    assert_eq!(selected(1.9), 0);
+    assert_eq!(selected(2.0), 1);
+    assert_eq!(selected(3.9), 1);
+    assert_eq!(selected(4.0), 2);
+    assert_eq!(selected(100.0), 3);
+    assert_none!(tiff.select_level(6, 2.0));
+
+    let level = assert_some!(tiff.select_level_for_size(0, (245, 183)));
+    assert_eq!(level.image, 1);
+    assert_eq!(level.mask, Some(5));
+    let level = assert_some!(tiff.select_level_for_size(0, (300, 100)));
+    assert_eq!(level.image, 0);
+    let level = assert_some!(tiff.select_level_for_size(0, (1, 1)));
+    assert_eq!(level.image, 3);
+}
+
+#[test]
+fn rescale_window() {
+    let images = [(0, 490, 367), (REDUCED_IMAGE, 245, 184)];
+    let bytes = build_images(&images, None);
+    let tiff = assert_ok!(TiffFile::new(std::io::Cursor::new(&bytes)));
+    let level: Level = assert_some!(tiff.select_level(0, 2.0));
+
+    let (x, y) = level.downsampling();
+    assert_eq!(x, 2.0);
+    assert!((y - 367.0 / 184.0).abs() < 1e-9);
+
+    // The rescaled windows cover the whole area of the original ones.
+    assert_eq!(
+        level.rescale(Window::new((100, 100), (200, 100))),
+        Window::new((50, 50), (100, 51))
+    );
+    assert_eq!(
+        level.rescale(Window::new((0, 0), (490, 367))),
+        Window::new((0, 0), (245, 184))
+    );
+    assert_eq!(
+        level.rescale(Window::new((489, 366), (1, 1))),
+        Window::new((244, 183), (1, 1))
+    );
+    assert_eq!(
+        level.rescale(Window::new((10, 10), (0, 0))),
+        Window::new((5, 5), (0, 0))
+    );
+}
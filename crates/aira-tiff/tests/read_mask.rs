@@ -0,0 +1,238 @@
+use aira_tiff::{
+    mask::{MaskReader, MaskSource, INVALID as I, VALID as V},
+    reader::{PixelReader, Window},
+    ByteOrder, Tag, TiffFile,
+};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Returns the entries of an image of the given subfile type and size, with a single strip.
+fn image(subfile_type: u32, (width, length): (u32, u32), mut entries: Vec<Entry>) -> Vec<Entry> {
+    entries.extend([
+        long(254, subfile_type),
+        long(256, width),
+        long(257, length),
+        short(259, 1),
+        long(278, length),
+    ]);
+    entries
+}
+
+/// Builds a little-endian TIFF file with a directory for each image, given its entries and the
+/// bytes of its single strip.
+///
+/// An empty strip is sparse, with both its offset and byte count zero.
+fn build_file(images: &[(Vec<Entry>, Vec<u8>)]) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    for (entries, strip) in images {
+        let mut entries = entries.clone();
+        entries.extend(tiff.strips(&[strip]));
+        entries.sort_by_key(|entry| entry.0);
+        tiff.directory(&entries);
+    }
+    tiff.finish()
+}
+
+const REDUCED_IMAGE: u32 = 1;
+const MASK: u32 = 4;
+
+/// Returns an image of 8-bit gray pixels.
+fn gray(subfile_type: u32, dimensions: (u32, u32), pixels: &[u8]) -> (Vec<Entry>, Vec<u8>) {
+    let entries = vec![short(258, 8), short(262, 1)];
+    (image(subfile_type, dimensions, entries), pixels.to_vec())
+}
+
+/// Returns a transparency mask with 1-bit pixels, one byte per row.
+fn mask(subfile_type: u32, dimensions: (u32, u32), rows: &[u8]) -> (Vec<Entry>, Vec<u8>) {
+    let entries = vec![short(258, 1), short(262, 4)];
+    (
+        image(subfile_type | MASK, dimensions, entries),
+        rows.to_vec(),
+    )
+}
+
+/// Reads the whole mask of the image at `index`.
+fn read_mask(bytes: &[u8], index: usize) -> Vec<u8> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let masks = assert_ok!(MaskReader::from_file(&tiff, index));
+    let (width, length) = tiff.metadata(index).unwrap().dimensions;
+    let mut mask = vec![0; (width * length) as usize];
+    assert_ok!(masks.read_mask(&mut reader, Window::new((0, 0), (width, length)), &mut mask));
+    mask
+}
+
+#[test]
+fn read_mask_image() {
+    let bytes = build_file(&[
+        gray(0, (4, 2), &[1, 2, 3, 4, 5, 6, 7, 8]),
+        mask(0, (4, 2), &[0b1010_0000, 0b0111_0000]),
+    ]);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let masks = assert_ok!(MaskReader::from_file(&tiff, 0));
+    assert_matches!(masks.source(), MaskSource::Mask(_));
+
+    let mut mask = [0; 8];
+    assert_ok!(masks.read_mask(&mut reader, Window::new((0, 0), (4, 2)), &mut mask));
+    assert_eq!(mask, [V, I, V, I, I, V, V, V]);
+
+    let mut mask = [0; 4];
+    assert_ok!(masks.read_mask(&mut reader, Window::new((1, 0), (2, 2)), &mut mask));
+    assert_eq!(mask, [I, V, V, V]);
+
+    assert_err!(masks.read_mask(&mut reader, Window::new((3, 0), (2, 2)), &mut mask));
+}
+
+#[test]
+fn read_overview_mask() {
+    let bytes = build_file(&[
+        gray(0, (4, 2), &[1, 2, 3, 4, 5, 6, 7, 8]),
+        mask(0, (4, 2), &[0b1111_0000, 0b1111_0000]),
+        gray(REDUCED_IMAGE, (2, 1), &[1, 2]),
+        mask(REDUCED_IMAGE, (2, 1), &[0b0100_0000]),
+        gray(REDUCED_IMAGE, (1, 1), &[1]),
+    ]);
+    assert_eq!(read_mask(&bytes, 0), [V; 8]);
+    assert_eq!(read_mask(&bytes, 2), [I, V]);
+
+    // The smallest overview has no mask.
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let masks = assert_ok!(MaskReader::from_file(&tiff, 4));
+    assert_matches!(masks.source(), MaskSource::AllValid);
+    assert_eq!(read_mask(&bytes, 4), [V]);
+
+    // The mask must have the dimensions of the image.
+    let overview = tiff.metadata(2).unwrap();
+    let mask = tiff.metadata(1);
+    assert_err!(MaskReader::new(overview, ByteOrder::LittleEndian, mask));
+}
+
+#[test]
+fn read_nodata_mask() {
+    let (mut entries, pixels) = gray(0, (4, 1), &[0, 5, 0, 7]);
+    entries.push(ascii(42113, " 0 "));
+    let bytes = build_file(&[(entries, pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [I, V, I, V]);
+
+    // All the samples of a pixel must equal the nodata value.
+    let entries = vec![
+        shorts(258, &[8, 8]),
+        short(262, 1),
+        short(277, 2),
+        ascii(42113, "3"),
+    ];
+    let pixels = vec![3, 3, 3, 1, 1, 3];
+    let bytes = build_file(&[(image(0, (3, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [I, V, V]);
+
+    // A nodata value which is not a number is ignored.
+    let (mut entries, pixels) = gray(0, (2, 1), &[0, 5]);
+    entries.push(ascii(42113, "none"));
+    let bytes = build_file(&[(entries, pixels)]);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let metadata = assert_some!(tiff.metadata(0));
+    assert_none!(metadata.nodata());
+    assert_some!(metadata.custom_entry(Tag::GDAL_NO_DATA));
+    assert_eq!(read_mask(&bytes, 0), [V, V]);
+}
+
+#[test]
+fn read_float_nodata_mask() {
+    // The nodata value is compared with the samples in single precision.
+    let entries = vec![
+        short(258, 32),
+        short(262, 1),
+        short(339, 3),
+        ascii(42113, "0.1"),
+    ];
+    let pixels = [0.1f32, 0.2, -0.0, 0.1]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = build_file(&[(image(0, (4, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [I, V, V, I]);
+
+    // Both zeros equal the nodata value.
+    let entries = vec![
+        short(258, 32),
+        short(262, 1),
+        short(339, 3),
+        ascii(42113, "0"),
+    ];
+    let pixels = [0.0f32, -0.0, 1.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = build_file(&[(image(0, (3, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [I, I, V]);
+}
+
+#[test]
+fn read_nan_mask() {
+    let entries = vec![short(258, 32), short(262, 1), short(339, 3)];
+    let pixels = [1.0f32, f32::NAN, 2.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = build_file(&[(image(0, (3, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [V, I, V]);
+
+    // The nodata value has the priority over NaN.
+    let entries = vec![
+        short(258, 32),
+        short(262, 1),
+        short(339, 3),
+        ascii(42113, "2"),
+    ];
+    let pixels = [1.0f32, f32::NAN, 2.0]
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    let bytes = build_file(&[(image(0, (3, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [V, V, I]);
+}
+
+#[test]
+fn read_alpha_mask() {
+    let entries = vec![
+        shorts(258, &[8, 8, 8, 8]),
+        short(262, 2),
+        short(277, 4),
+        short(338, 2),
+    ];
+    let pixels = vec![1, 2, 3, 0, 4, 5, 6, 255, 0, 0, 0, 1];
+    let bytes = build_file(&[(image(0, (3, 1), entries), pixels)]);
+    assert_eq!(read_mask(&bytes, 0), [I, V, V]);
+
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let masks = assert_ok!(MaskReader::from_file(&tiff, 0));
+    assert_matches!(masks.source(), MaskSource::Alpha(3));
+}
+
+#[test]
+fn read_sparse_mask() {
+    // The sparse chunks of the image are filled with the nodata value.
+    let (mut entries, _) = gray(0, (3, 1), &[]);
+    entries.push(ascii(42113, "7"));
+    let bytes = build_file(&[(entries, Vec::new())]);
+    let mut reader = std::io::Cursor::new(&bytes);
+    let tiff = assert_ok!(TiffFile::new(&mut reader));
+    let pixels = assert_ok!(PixelReader::new(
+        tiff.metadata(0).unwrap(),
+        tiff.byteorder()
+    ));
+    let mut samples = [0; 3];
+    assert_ok!(pixels.read_bands(&mut reader, &[0], &mut samples));
+    assert_eq!(samples, [7; 3]);
+    assert_eq!(read_mask(&bytes, 0), [I; 3]);
+
+    // The sparse chunks of a mask are invalid.
+    let bytes = build_file(&[gray(0, (3, 1), &[1, 2, 3]), mask(0, (3, 1), &[])]);
+    assert_eq!(read_mask(&bytes, 0), [I; 3]);
+}
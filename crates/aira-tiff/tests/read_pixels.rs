@@ -0,0 +1,496 @@
+use std::io::{Read, Seek};
+
+use aira_tiff::{
+    metadata::MetadataBuilder,
+    reader::{PixelReader, Window},
+    ByteOrder, Decoder, Metadata, Orientation, PlanarConfiguration, YCbCrPositioning,
+};
+use claims::*;
+
+mod utils;
+use utils::*;
+
+/// Reads all the pixels of the only directory of the given file.
+fn read_image(bytes: &[u8], bands: &[u16]) -> Result<Vec<u8>, aira_tiff::Error> {
+    let mut reader = std::io::Cursor::new(bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+    let mut dst = vec![0u8; pixels.image_len(bands.len())];
+    pixels.read_bands(&mut reader, bands, &mut dst)?;
+    Ok(dst)
+}
+
+/// Reads the RGB pixels of the test image by copying the rows of its uncompressed tiles.
+fn read_rgb_reference() -> (Metadata, Vec<u8>) {
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+
+    let (width, length) = metadata.dimensions;
+    let (tile_width, _) = metadata.chunk_size();
+    let mut pixels = vec![0u8; width as usize * length as usize * 3];
+    for chunk in assert_some!(metadata.chunks()) {
+        let mut tile = vec![0u8; chunk.byte_count as usize];
+        assert_ok!(reader.seek(std::io::SeekFrom::Start(chunk.offset)));
+        assert_ok!(reader.read_exact(&mut tile));
+
+        for row in 0..chunk.size.1 as usize {
+            let src = &tile[row * tile_width as usize * 3..][..chunk.size.0 as usize * 3];
+            let start =
+                ((chunk.origin.1 as usize + row) * width as usize + chunk.origin.0 as usize) * 3;
+            pixels[start..start + src.len()].copy_from_slice(src);
+        }
+    }
+    (metadata, pixels)
+}
+
+/// Builds a little-endian TIFF file storing the RGB pixels in three planes of strips.
+///
+/// The strips of the planes listed in `missing` point beyond the end of the file, and those of
+/// the planes listed in `sparse` are not stored.
+fn build_planar_rgb(
+    (width, length): (u32, u32),
+    pixels: &[u8],
+    rows_per_strip: u32,
+    missing: &[usize],
+    sparse: &[usize],
+) -> Vec<u8> {
+    let strips_per_plane = length.div_ceil(rows_per_strip) as usize;
+
+    let mut strips = Vec::new();
+    for plane in 0..3 {
+        for strip in 0..strips_per_plane {
+            let first = strip * rows_per_strip as usize;
+            let last = (first + rows_per_strip as usize).min(length as usize);
+            let samples = match sparse.contains(&plane) {
+                true => Vec::new(),
+                false => pixels[first * width as usize * 3..last * width as usize * 3]
+                    .iter()
+                    .skip(plane)
+                    .step_by(3)
+                    .copied()
+                    .collect(),
+            };
+            strips.push(samples);
+        }
+    }
+
+    let mut tiff = TiffBuilder::default();
+    let strips = strips.iter().map(Vec::as_slice).collect::<Vec<_>>();
+    let (mut offsets, byte_counts) = tiff.chunks(&strips);
+    for (index, offset) in offsets.iter_mut().enumerate() {
+        if missing.contains(&(index / strips_per_plane)) {
+            *offset = u32::MAX - 1;
+        }
+    }
+    tiff.directory(&[
+        long(256, width),
+        long(257, length),
+        shorts(258, &[8; 3]),
+        short(259, 1),
+        short(262, 2),
+        longs(273, &offsets),
+        short(277, 3),
+        long(278, rows_per_strip),
+        longs(279, &byte_counts),
+        short(284, 2),
+    ]);
+    tiff.finish()
+}
+
+fn select_bands(pixels: &[u8], samples: usize, bands: &[u16]) -> Vec<u8> {
+    pixels
+        .chunks_exact(samples)
+        .flat_map(|pixel| bands.iter().map(|&band| pixel[band as usize]))
+        .collect()
+}
+
+#[test]
+fn read_chunky_image() {
+    let (_, expected) = read_rgb_reference();
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+    assert_eq!(
+        assert_ok!(read_image(&bytes, &[2, 0, 0])),
+        select_bands(&expected, 3, &[2, 0, 0])
+    );
+}
+
+#[test]
+fn read_planar_image() {
+    let (metadata, expected) = read_rgb_reference();
+    let bytes = build_planar_rgb(metadata.dimensions, &expected, 100, &[], &[]);
+
+    let mut reader = std::io::Cursor::new(&bytes);
+    let planar = utils::get_the_only_one_directory(&mut reader);
+    assert_eq!(planar.configuration, PlanarConfiguration::PLANAR);
+    assert_eq!(planar.planes_count(), 3);
+    assert_eq!(planar.chunks_per_plane(), 4);
+    assert_eq!(planar.chunks_count(), 12);
+
+    let planes = assert_some!(planar.chunks())
+        .map(|chunk| chunk.plane)
+        .collect::<Vec<_>>();
+    assert_eq!(planes, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2]);
+    let origins = assert_some!(planar.plane_chunks(2))
+        .map(|chunk| (chunk.plane, chunk.origin, chunk.size))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        origins,
+        [
+            (2, (0, 0), (490, 100)),
+            (2, (0, 100), (490, 100)),
+            (2, (0, 200), (490, 100)),
+            (2, (0, 300), (490, 67)),
+        ]
+    );
+
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+    assert_eq!(
+        assert_ok!(read_image(&bytes, &[1])),
+        select_bands(&expected, 3, &[1])
+    );
+}
+
+#[test]
+fn planar_bands_skip_other_planes() {
+    let (metadata, expected) = read_rgb_reference();
+    let bytes = build_planar_rgb(metadata.dimensions, &expected, 128, &[1], &[]);
+
+    assert_eq!(
+        assert_ok!(read_image(&bytes, &[2, 0])),
+        select_bands(&expected, 3, &[2, 0])
+    );
+    assert_err!(read_image(&bytes, &[0, 1]));
+}
+
+#[test]
+fn read_sparse_chunks() {
+    let (metadata, expected) = read_rgb_reference();
+    let bytes = build_planar_rgb(metadata.dimensions, &expected, 128, &[], &[1]);
+
+    let mut reader = std::io::Cursor::new(&bytes);
+    let planar = utils::get_the_only_one_directory(&mut reader);
+    let sparse = assert_some!(planar.chunks())
+        .map(|chunk| chunk.is_sparse())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sparse,
+        [false, false, false, true, true, true, false, false, false]
+    );
+
+    // The sparse chunks are filled with zero without reading the file.
+    let mut expected = expected;
+    expected
+        .iter_mut()
+        .skip(1)
+        .step_by(3)
+        .for_each(|sample| *sample = 0);
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+}
+
+#[test]
+fn band_out_of_range() {
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+    assert_err!(read_image(&bytes, &[3]));
+}
+
+/// Builds a little-endian TIFF file storing the 8-bit data units of subsampled YCbCr pixels in
+/// strips.
+fn build_ycbcr(
+    (width, length): (u16, u16),
+    rows_per_strip: u16,
+    extra: &[Entry],
+    strips: &[&[u8]],
+) -> Vec<u8> {
+    let mut tiff = TiffBuilder::default();
+    let mut entries = vec![
+        short(256, width),
+        short(257, length),
+        shorts(258, &[8; 3]),
+        short(259, 1),
+        short(262, 6),
+        short(277, 3),
+        short(278, rows_per_strip),
+    ];
+    entries.extend(tiff.strips(strips));
+    entries.extend_from_slice(extra);
+    entries.sort_by_key(|entry| entry.0);
+    tiff.directory(&entries);
+    tiff.finish()
+}
+
+#[test]
+fn read_subsampled_ycbcr() {
+    // Two data units of 2x2 pixels.
+    let units: &[u8] = &[10, 20, 30, 40, 100, 200, 50, 60, 70, 80, 120, 220];
+    let bytes = build_ycbcr((4, 2), 2, &[], &[units]);
+
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    assert_eq!(metadata.ycbcr_subsampling(), (2, 2));
+    assert_eq!(metadata.ycbcr_positioning(), YCbCrPositioning::CENTERED);
+    let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+    let chunk = assert_some!(assert_some!(metadata.chunks()).next());
+    assert_eq!(pixels.chunk_len(&chunk), units.len());
+
+    // The centered chroma samples lie between the pixels of each data unit.
+    let row = |luma: [u8; 4]| {
+        [
+            [luma[0], 100, 200],
+            [luma[1], 105, 205],
+            [luma[2], 115, 215],
+            [luma[3], 120, 220],
+        ]
+    };
+    let expected = [row([10, 20, 50, 60]), row([30, 40, 70, 80])]
+        .concat()
+        .concat();
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+    assert_eq!(
+        assert_ok!(read_image(&bytes, &[2, 0])),
+        select_bands(&expected, 3, &[2, 0])
+    );
+
+    // The cosited chroma samples lie on the first pixel of each data unit.
+    let bytes = build_ycbcr((4, 2), 2, &[short(531, 2)], &[units]);
+    let row = |luma: [u8; 4]| {
+        [
+            [luma[0], 100, 200],
+            [luma[1], 110, 210],
+            [luma[2], 120, 220],
+            [luma[3], 120, 220],
+        ]
+    };
+    let expected = [row([10, 20, 50, 60]), row([30, 40, 70, 80])]
+        .concat()
+        .concat();
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+}
+
+#[test]
+fn read_subsampled_ycbcr_padded_strips() {
+    // The data units of 2x1 pixels of the last column are padded.
+    let strips: [&[u8]; 2] = [
+        &[
+            1, 2, 100, 200, 3, 0, 110, 210, 11, 12, 120, 220, 13, 0, 130, 230,
+        ],
+        &[21, 22, 140, 240, 23, 0, 150, 250],
+    ];
+    let extra = [shorts(530, &[2, 1]), short(531, 2)];
+    let bytes = build_ycbcr((3, 3), 2, &extra, &strips);
+
+    let expected = [
+        [1, 100, 200],
+        [2, 105, 205],
+        [3, 110, 210],
+        [11, 120, 220],
+        [12, 125, 225],
+        [13, 130, 230],
+        [21, 140, 240],
+        [22, 145, 245],
+        [23, 150, 250],
+    ]
+    .concat();
+    assert_eq!(assert_ok!(read_image(&bytes, &[0, 1, 2])), expected);
+}
+
+#[test]
+fn invalid_ycbcr_subsampling() {
+    let units: &[u8] = &[10, 20, 30, 40, 100, 200];
+    for subsampling in [&[3, 1][..], &[1, 2], &[2]] {
+        let bytes = build_ycbcr((2, 2), 2, &[shorts(530, subsampling)], &[units]);
+        let mut reader = std::io::Cursor::new(&bytes);
+        let mut decoder = assert_ok!(Decoder::new(&mut reader));
+        let mut directories = decoder.directories();
+        let directory = assert_some!(assert_ok!(directories.next_directory()));
+        assert_err!(Metadata::from_decoder(directory));
+    }
+}
+
+/// Sets the value of the orientation entry of the first directory of a little-endian file.
+fn set_orientation(bytes: &mut [u8], orientation: u16) {
+    let ifd = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let count = u16::from_le_bytes([bytes[ifd], bytes[ifd + 1]]) as usize;
+    let header = (0..count)
+        .map(|index| ifd + 2 + 12 * index)
+        .find(|&offset| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) == 274)
+        .expect("Missing orientation entry");
+    bytes[header + 8..header + 10].copy_from_slice(&orientation.to_le_bytes());
+}
+
+/// Returns the upright pixels of `(width, length)` stored with the given orientation.
+fn apply_orientation(pixels: &[u8], (width, length): (usize, usize), orientation: u16) -> Vec<u8> {
+    let mut upright = vec![0u8; pixels.len()];
+    let upright_width = match orientation {
+        5..=8 => length,
+        _ => width,
+    };
+    for (index, pixel) in pixels.chunks_exact(3).enumerate() {
+        let (x, y) = (index % width, index / width);
+        let (x, y) = match orientation {
+            1 => (x, y),
+            2 => (width - 1 - x, y),
+            3 => (width - 1 - x, length - 1 - y),
+            4 => (x, length - 1 - y),
+            5 => (y, x),
+            6 => (length - 1 - y, x),
+            7 => (length - 1 - y, width - 1 - x),
+            8 => (y, width - 1 - x),
+            _ => unreachable!(),
+        };
+        let start = (y * upright_width + x) * 3;
+        upright[start..start + 3].copy_from_slice(pixel);
+    }
+    upright
+}
+
+/// Returns the pixels of `window` of an image of the given width.
+fn crop(pixels: &[u8], width: u32, window: Window, samples: usize) -> Vec<u8> {
+    let (x, y) = (window.origin.0 as usize, window.origin.1 as usize);
+    pixels
+        .chunks_exact(width as usize * samples)
+        .skip(y)
+        .take(window.size.1 as usize)
+        .flat_map(|row| &row[x * samples..(x + window.size.0 as usize) * samples])
+        .copied()
+        .collect()
+}
+
+#[test]
+fn read_window() {
+    let (metadata, expected) = read_rgb_reference();
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+    let planar = build_planar_rgb(metadata.dimensions, &expected, 100, &[], &[]);
+
+    // The windows overlap several tiles, the last one is clipped by the image.
+    let windows = [
+        Window::new((0, 0), (490, 367)),
+        Window::new((10, 20), (1, 1)),
+        Window::new((30, 120), (70, 150)),
+        Window::new((450, 300), (40, 67)),
+    ];
+    for bytes in [&bytes, &planar] {
+        let mut reader = std::io::Cursor::new(bytes);
+        let metadata = utils::get_the_only_one_directory(&mut reader);
+        let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+        for window in windows {
+            let mut dst = vec![0u8; pixels.window_len(window, 2)];
+            assert_ok!(pixels.read_window(&mut reader, window, &[2, 0], &mut dst));
+            let expected = select_bands(&crop(&expected, 490, window, 3), 3, &[2, 0]);
+            assert_eq!(dst, expected, "{window:?}");
+        }
+
+        let window = Window::new((480, 0), (11, 1));
+        let mut dst = vec![0u8; pixels.window_len(window, 3)];
+        assert_err!(pixels.read_window(&mut reader, window, &[0, 1, 2], &mut dst));
+    }
+}
+
+#[test]
+fn window_intersection() {
+    let window = Window::new((10, 20), (30, 40));
+    assert_some_eq!(
+        window.intersection(&Window::new((0, 50), (20, 100))),
+        Window::new((10, 50), (10, 10))
+    );
+    assert_none!(window.intersection(&Window::new((40, 20), (10, 10))));
+
+    // The end of the windows exceeds the range of u32.
+    let window = Window::new((u32::MAX - 10, 5), (u32::MAX, u32::MAX));
+    assert_some_eq!(
+        window.intersection(&Window::new((u32::MAX - 20, 0), (u32::MAX, 10))),
+        Window::new((u32::MAX - 10, 5), (u32::MAX - 10, 5))
+    );
+    assert_none!(window.intersection(&Window::new((0, 0), (u32::MAX - 10, u32::MAX))));
+}
+
+#[test]
+fn read_deferred_chunks() {
+    let (metadata, expected) = read_rgb_reference();
+    let bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+    let planar = build_planar_rgb(metadata.dimensions, &expected, 100, &[], &[1]);
+    let mut sparse = expected.clone();
+    sparse
+        .iter_mut()
+        .skip(1)
+        .step_by(3)
+        .for_each(|sample| *sample = 0);
+
+    let windows = [
+        Window::new((0, 0), (490, 367)),
+        Window::new((30, 120), (70, 150)),
+        Window::new((450, 300), (40, 67)),
+    ];
+    for (bytes, expected) in [(&bytes, &expected), (&planar, &sparse)] {
+        let mut decoder = assert_ok!(Decoder::new(std::io::Cursor::new(bytes)));
+        let mut directories = decoder.directories();
+        let directory = assert_some!(assert_ok!(directories.next_directory()));
+        let metadata = assert_ok!(MetadataBuilder::default()
+            .defer_chunks(true)
+            .decode(directory));
+        assert!(metadata.has_deferred_chunks());
+
+        let mut reader = std::io::Cursor::new(bytes);
+        let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+        for window in windows {
+            let mut dst = vec![0u8; pixels.window_len(window, 3)];
+            assert_ok!(pixels.read_window(&mut reader, window, &[0, 1, 2], &mut dst));
+            assert_eq!(dst, crop(expected, 490, window, 3), "{window:?}");
+        }
+    }
+}
+
+#[test]
+fn read_oriented_image() {
+    let (_, expected) = read_rgb_reference();
+    let mut bytes = assert_ok!(std::fs::read("tests/images/tiled-rect-rgb-u8.tif"));
+
+    for orientation in 1..=8 {
+        set_orientation(&mut bytes, orientation);
+        let mut reader = std::io::Cursor::new(&bytes);
+        let metadata = utils::get_the_only_one_directory(&mut reader);
+        assert_eq!(metadata.orientation, Orientation(orientation));
+
+        // The pixels are read as stored by default.
+        let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian));
+        assert_eq!(pixels.dimensions(), (490, 367));
+        let mut dst = vec![0u8; pixels.image_len(3)];
+        assert_ok!(pixels.read_image(&mut reader, &mut dst));
+        assert_eq!(dst, expected);
+
+        let pixels = pixels.oriented(true);
+        let upright = apply_orientation(&expected, (490, 367), orientation);
+        let (width, length) = pixels.dimensions();
+        match orientation {
+            5..=8 => assert_eq!((width, length), (367, 490)),
+            _ => assert_eq!((width, length), (490, 367)),
+        }
+        assert_ok!(pixels.read_image(&mut reader, &mut dst));
+        assert_eq!(dst, upright, "{orientation}");
+
+        // The windows are given in the upright image.
+        let window = Window::new((width - 100, 50), (100, 200));
+        let mut dst = vec![0u8; pixels.window_len(window, 3)];
+        assert_ok!(pixels.read_window(&mut reader, window, &[0, 1, 2], &mut dst));
+        assert_eq!(dst, crop(&upright, width, window, 3), "{orientation}");
+    }
+}
+
+#[test]
+fn read_oriented_subsampled_ycbcr() {
+    let units: &[u8] = &[10, 20, 30, 40, 100, 200, 50, 60, 70, 80, 120, 220];
+    let bytes = build_ycbcr((4, 2), 2, &[short(274, 6)], &[units]);
+
+    let mut reader = std::io::Cursor::new(&bytes);
+    let metadata = utils::get_the_only_one_directory(&mut reader);
+    let pixels = assert_ok!(PixelReader::new(&metadata, ByteOrder::LittleEndian)).oriented(true);
+    assert_eq!(pixels.dimensions(), (2, 4));
+
+    // The rows of luma samples are turned into columns, from right to left.
+    let window = Window::new((0, 1), (2, 2));
+    let mut luma = vec![0u8; pixels.window_len(window, 1)];
+    assert_ok!(pixels.read_window(&mut reader, window, &[0], &mut luma));
+    assert_eq!(luma, [40, 20, 70, 50]);
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use aira_tiff::{decoder::Decoder, Metadata};
 use claims::*;
 
@@ -12,3 +14,151 @@ where
     assert_none!(assert_ok!(directories.next_directory()));
     metadata
 }
+
+/// An entry of a little-endian TIFF file, with its tag, datatype, count and payload.
+///
+/// A payload of at most 4 bytes is written in the entry as is, whatever the count.
+pub type Entry = (u16, u16, u32, Vec<u8>);
+
+pub fn short(tag: u16, value: u16) -> Entry {
+    shorts(tag, &[value])
+}
+
+pub fn shorts(tag: u16, values: &[u16]) -> Entry {
+    let payload = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    (tag, 3, values.len() as u32, payload)
+}
+
+pub fn long(tag: u16, value: u32) -> Entry {
+    longs(tag, &[value])
+}
+
+pub fn longs(tag: u16, values: &[u32]) -> Entry {
+    let payload = values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect();
+    (tag, 4, values.len() as u32, payload)
+}
+
+pub fn rationals(tag: u16, values: &[(u32, u32)]) -> Entry {
+    let payload = values
+        .iter()
+        .flat_map(|(num, den)| [num.to_le_bytes(), den.to_le_bytes()])
+        .flatten()
+        .collect();
+    (tag, 5, values.len() as u32, payload)
+}
+
+pub fn ascii(tag: u16, value: &str) -> Entry {
+    let mut payload = value.as_bytes().to_vec();
+    payload.push(0);
+    (tag, 2, payload.len() as u32, payload)
+}
+
+/// Builder of little-endian classic TIFF files.
+///
+/// The data and the directories are appended in the order they are given, each directory is
+/// linked from the previous one, or from the header for the first one.
+pub struct TiffBuilder {
+    bytes: Vec<u8>,
+    /// The position of the offset of the next directory.
+    next: usize,
+}
+
+impl Default for TiffBuilder {
+    fn default() -> Self {
+        Self {
+            bytes: b"II\x2a\x00\x00\x00\x00\x00".to_vec(),
+            next: 4,
+        }
+    }
+}
+
+impl TiffBuilder {
+    /// Appends raw bytes without alignment, and returns their offset.
+    pub fn data(&mut self, bytes: &[u8]) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.bytes.extend_from_slice(bytes);
+        offset
+    }
+
+    /// Pads the file to a word boundary.
+    pub fn align(&mut self) {
+        self.bytes.resize(self.bytes.len().next_multiple_of(2), 0);
+    }
+
+    /// Appends the chunks, and returns their offsets and byte counts.
+    ///
+    /// An empty chunk is sparse, with both its offset and byte count zero. The file is padded to
+    /// a word boundary after the chunks.
+    pub fn chunks(&mut self, chunks: &[&[u8]]) -> (Vec<u32>, Vec<u32>) {
+        let offsets = chunks
+            .iter()
+            .map(|chunk| match chunk.is_empty() {
+                true => 0,
+                false => self.data(chunk),
+            })
+            .collect();
+        let byte_counts = chunks.iter().map(|chunk| chunk.len() as u32).collect();
+        self.align();
+        (offsets, byte_counts)
+    }
+
+    /// Appends the strips, and returns the `StripOffsets` and `StripByteCounts` entries.
+    pub fn strips(&mut self, strips: &[&[u8]]) -> [Entry; 2] {
+        let (offsets, byte_counts) = self.chunks(strips);
+        [longs(273, &offsets), longs(279, &byte_counts)]
+    }
+
+    /// Appends a directory with the entries in the given order, and returns its offset.
+    ///
+    /// The directory begins at the current position, and the payloads larger than 4 bytes
+    /// follow it on word boundaries.
+    pub fn directory(&mut self, entries: &[Entry]) -> u32 {
+        let offset = self.bytes.len() as u32;
+        self.set_next(offset);
+
+        let mut payloads = Vec::new();
+        let mut payload_offset = offset as usize + 2 + 12 * entries.len() + 4;
+        self.bytes
+            .extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        for (tag, dtype, count, payload) in entries {
+            let mut value = [0; 4];
+            if payload.len() <= 4 {
+                value[..payload.len()].copy_from_slice(payload);
+            } else {
+                payload_offset = payload_offset.next_multiple_of(2);
+                value = (payload_offset as u32).to_le_bytes();
+                payload_offset += payload.len();
+                payloads.push(payload);
+            }
+            self.bytes.extend_from_slice(&tag.to_le_bytes());
+            self.bytes.extend_from_slice(&dtype.to_le_bytes());
+            self.bytes.extend_from_slice(&count.to_le_bytes());
+            self.bytes.extend_from_slice(&value);
+        }
+        self.next = self.bytes.len();
+        self.bytes.extend_from_slice(&0u32.to_le_bytes());
+
+        for payload in payloads {
+            self.align();
+            self.bytes.extend_from_slice(payload);
+        }
+        self.align();
+        offset
+    }
+
+    /// Sets the offset of the directory following the last one.
+    pub fn set_next(&mut self, offset: u32) {
+        self.bytes[self.next..self.next + 4].copy_from_slice(&offset.to_le_bytes());
+    }
+
+    /// Returns the bytes of the file.
+    pub fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
@@ -0,0 +1,109 @@
+//! Builds the seed corpus of the fuzz targets from a directory of TIFF images.
+//!
+//! Every image is used as seed for the `decoder` and `metadata` targets, while the raw data of
+//! its chunks is used as seed for the decompressors and the predictors.
+
+use std::{
+    io::{Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use aira_tiff::{
+    compression::DecompressReader, Compression, Decoder, Metadata, Predictor, SampleFormat,
+};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = std::env::args_os().skip(1).map(PathBuf::from);
+    let (Some(images), Some(corpus)) = (args.next(), args.next()) else {
+        return Err("Usage: seed-corpus <IMAGES> <CORPUS>".into());
+    };
+
+    for target in [
+        "decoder",
+        "metadata",
+        "packbits",
+        "deflate",
+        "lzw",
+        "predictor",
+    ] {
+        std::fs::create_dir_all(corpus.join(target))?;
+    }
+
+    for path in std::fs::read_dir(&images)? {
+        let path = path?.path();
+        if !matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("tif" | "tiff")
+        ) {
+            continue;
+        }
+
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        std::fs::copy(&path, corpus.join("decoder").join(&name))?;
+        std::fs::copy(&path, corpus.join("metadata").join(&name))?;
+
+        seed_chunks(&path, &name, &corpus)?;
+    }
+
+    Ok(())
+}
+
+/// Writes the raw and the decompressed data of each chunk of the image.
+fn seed_chunks(path: &Path, name: &str, corpus: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path)?);
+
+    let mut decoder = Decoder::new(&mut reader)?;
+    let byteorder = decoder.byteorder();
+    let mut metadatas = Vec::new();
+    let mut directories = decoder.directories();
+    while let Some(directory) = directories.next_directory()? {
+        metadatas.push(Metadata::from_decoder(directory)?);
+    }
+
+    for (directory, metadata) in metadatas.iter().enumerate() {
+        let target = match metadata.compression {
+            Compression::PACKBITS => Some("packbits"),
+            Compression::DEFLATE | Compression::LEGACY_DEFLATE => Some("deflate"),
+            Compression::LZW => Some("lzw"),
+            _ => None,
+        };
+
+        for (index, chunk) in metadata.chunks().into_iter().flatten().enumerate() {
+            let seed = format!("{name}-{directory}-{index}");
+
+            let mut data = vec![0u8; chunk.byte_count as usize];
+            reader.seek(std::io::SeekFrom::Start(chunk.offset))?;
+            reader.read_exact(&mut data)?;
+
+            if let Some(target) = target {
+                std::fs::write(corpus.join(target).join(&seed), &data)?;
+            }
+
+            // The layout of the input is described in the `predictor` fuzz target, if the image
+            // doesn't use a predictor the decompressed data is still a good seed.
+            let samples = metadata.samples();
+            let bytespersample = samples[0].bits.div_ceil(8);
+            let flags = match byteorder {
+                aira_tiff::ByteOrder::LittleEndian => 0x00,
+                aira_tiff::ByteOrder::BigEndian => 0x01,
+            } | match (metadata.predictor, samples[0].format) {
+                (Predictor::FLOAT, _) | (Predictor::NONE, SampleFormat::FLOAT) => 0x02,
+                _ => 0x00,
+            };
+            let mut input = vec![
+                flags,
+                chunk.size.0.min(u8::MAX as u32) as u8,
+                samples.len() as u8,
+                bytespersample as u8,
+            ];
+            let Ok(mut decompressed) = DecompressReader::new(&data[..], metadata.compression) else {
+                // The compression algorithm is not supported yet.
+                continue;
+            };
+            decompressed.read_to_end(&mut input)?;
+            std::fs::write(corpus.join("predictor").join(&seed), &input)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+#![no_main]
+
+use aira_tiff::{Decoder, Entry};
+use libfuzzer_sys::fuzz_target;
+
+/// The maximum number of directories visited, the chain of directories may contain cycles.
+const MAX_DIRECTORIES: usize = 16;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(mut decoder) = Decoder::new(std::io::Cursor::new(data)) else {
+        return;
+    };
+
+    let mut directories = decoder.directories();
+    for _ in 0..MAX_DIRECTORIES {
+        let Ok(Some(directory)) = directories.next_directory() else {
+            break;
+        };
+
+        let mut entries = directory.entries();
+        while let Ok(Some(entry)) = entries.next_entry() {
+            let _ = Entry::from_decoder(entry);
+        }
+    }
+});
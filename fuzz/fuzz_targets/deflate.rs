@@ -0,0 +1,14 @@
+#![no_main]
+
+use std::io::Read;
+
+use aira_tiff::compression::DeflateReader;
+use libfuzzer_sys::fuzz_target;
+
+/// The maximum number of decompressed bytes, it prevents the fuzzer from timing out on bombs.
+const MAX_OUTPUT_SIZE: u64 = 16 * 1024 * 1024;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = DeflateReader::new(data).take(MAX_OUTPUT_SIZE);
+    let _ = std::io::copy(&mut reader, &mut std::io::sink());
+});
@@ -0,0 +1,36 @@
+#![no_main]
+
+use aira_tiff::{metadata::MetadataBuilder, Decoder};
+use libfuzzer_sys::fuzz_target;
+
+/// The maximum number of directories visited, the chain of directories may contain cycles.
+const MAX_DIRECTORIES: usize = 16;
+
+fuzz_target!(|data: &[u8]| {
+    // Both the strict and the lenient modes are exercised on the same input.
+    for lenient in [false, true] {
+        let Ok(mut decoder) = Decoder::new(std::io::Cursor::new(data)) else {
+            return;
+        };
+
+        let mut directories = decoder.directories();
+        for _ in 0..MAX_DIRECTORIES {
+            let Ok(Some(directory)) = directories.next_directory() else {
+                break;
+            };
+
+            let builder = MetadataBuilder::default().lenient(lenient);
+            let Ok(metadata) = builder.decode(directory) else {
+                continue;
+            };
+
+            let _ = metadata.chunk_size();
+            for chunk in metadata.chunks().into_iter().flatten() {
+                std::hint::black_box(chunk);
+            }
+            for (tag, entry) in metadata.custom_entries() {
+                std::hint::black_box((tag, entry));
+            }
+        }
+    }
+});
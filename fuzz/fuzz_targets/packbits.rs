@@ -0,0 +1,9 @@
+#![no_main]
+
+use aira_tiff::compression::PackBitsReader;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut reader = PackBitsReader::new(data);
+    let _ = std::io::copy(&mut reader, &mut std::io::sink());
+});
@@ -0,0 +1,40 @@
+#![no_main]
+
+use aira_tiff::{
+    predictor::{FloatPredictorReader, IntPredictorReader},
+    ByteOrder,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // The first bytes of the input select the parameters of the predictor, the remaining ones are
+    // the predicted data.
+    let [flags, ncols, samples, bytespersample, data @ ..] = data else {
+        return;
+    };
+
+    let byteorder = if flags & 0x01 == 0 {
+        ByteOrder::LittleEndian
+    } else {
+        ByteOrder::BigEndian
+    };
+    let ncols = *ncols as u32;
+    let samples = *samples as u16;
+    let bytespersample = *bytespersample as u16;
+
+    if flags & 0x02 == 0 {
+        // The size of samples is in bits when requested by the flags.
+        let reader = if flags & 0x04 == 0 {
+            IntPredictorReader::new(data, byteorder, ncols, samples, bytespersample)
+        } else {
+            IntPredictorReader::with_bits(data, byteorder, ncols, samples, bytespersample)
+        };
+        let Ok(mut reader) = reader else {
+            return;
+        };
+        let _ = std::io::copy(&mut reader, &mut std::io::sink());
+    } else {
+        let mut reader = FloatPredictorReader::new(data, ncols, samples, bytespersample);
+        let _ = std::io::copy(&mut reader, &mut std::io::sink());
+    }
+});